@@ -0,0 +1,368 @@
+/*!
+Parallel reading of CSV files without an index, by splitting them into byte
+ranges.
+
+A file is split into chunks of about the same length, and each chunk is
+guessed to start right after the first line terminator at or after its
+nominal start that isn't inside a quoted field. Whether the nominal start is
+inside a quoted field is inferred from the first quote after it that can
+only be an opening or a closing quote (see `guess_start`). That guess may
+still be wrong, so it is checked: the reader of each chunk reads up to the first
+record that starts at or after the end of its chunk, which is where the next
+chunk really starts. Since the first chunk starts at the start of the file,
+the start of every chunk is known once the chunk before it has been read. If
+a chunk was read from the wrong start, its results are thrown away and it is
+read again (sequentially) from its real start.
+
+Files with an index are split by record instead (see `map_indexed`).
+*/
+
+use std::cmp;
+use std::fs;
+use std::io::{self, Read, Seek};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chan;
+use csv;
+use threadpool::ThreadPool;
+
+use CliResult;
+use config::Config;
+use index::{IndexHeader, IndexedFile};
+use util;
+
+/// The length of the smallest chunk worth reading in parallel.
+const MIN_CHUNK_LEN: u64 = 1 << 20;
+
+/// The number of bytes after the nominal start of a chunk that are used to
+/// guess its real start.
+const GUESS_LEN: u64 = 64 * (1<<10);
+
+/// The records of a chunk.
+pub type RecordChunk<'a> =
+    &'a mut dyn Iterator<Item=csv::Result<csv::ByteRecord>>;
+
+/// A CSV file split into chunks that can be read in parallel.
+pub struct Chunked {
+    conf: Config,
+    path: PathBuf,
+    delimiter: u8,
+    /// The quote character, unless quoting is disabled or quotes are
+    /// escaped (in which case quotes are ignored when guessing where chunks
+    /// start).
+    quote: Option<u8>,
+    len: u64,
+    njobs: usize,
+    nchunks: usize,
+    /// The start of each chunk that has been read.
+    starts: Vec<u64>,
+}
+
+impl Chunked {
+    /// Split the file at `path` (read with `conf`, which has the given
+    /// delimiter and quote) into a chunk for each of `njobs` jobs. Returns
+    /// `None` if the file is too small to split.
+    pub fn new(
+        conf: Config,
+        path: PathBuf,
+        delimiter: u8,
+        quote: Option<u8>,
+        njobs: usize,
+    ) -> io::Result<Option<Chunked>> {
+        let len = fs::metadata(&path)?.len();
+        let nchunks = cmp::min(njobs as u64, len / MIN_CHUNK_LEN);
+        if nchunks < 2 {
+            return Ok(None);
+        }
+        Ok(Some(Chunked {
+            conf: conf,
+            path: path,
+            delimiter: delimiter,
+            quote: quote,
+            len: len,
+            njobs: njobs,
+            nchunks: nchunks as usize,
+            starts: vec![],
+        }))
+    }
+
+    /// Use more chunks, if necessary, so that each chunk is at most about
+    /// `max_len` bytes long. This limits the memory used by results that
+    /// are buffered until the chunks before them have been read.
+    pub fn max_chunk_len(mut self, max_len: u64) -> Chunked {
+        let n = self.len.div_ceil(max_len);
+        self.nchunks = cmp::max(self.nchunks, n as usize);
+        self
+    }
+
+    /// The start of each chunk read by the last call to `for_each` (or
+    /// `map`), in order. There is one for each result.
+    pub fn starts(&self) -> &[u64] {
+        &self.starts
+    }
+
+    /// Open a reader of the file that starts reading records at the byte
+    /// offset `start`, which must be the start of a record (e.g., one of
+    /// `starts`).
+    pub fn reader_at(&self, start: u64) -> CliResult<csv::Reader<fs::File>> {
+        reader_at(&self.conf, start)
+    }
+
+    /// Run `f` on the records of each chunk in parallel, and return the
+    /// results in the order of the chunks.
+    pub fn map<T, F>(&mut self, f: F) -> CliResult<Vec<T>>
+            where T: Send + 'static,
+                  F: Fn(RecordChunk) -> CliResult<T>,
+                  F: Send + Sync + 'static {
+        let mut results = vec![];
+        self.for_each(f, |res| { results.push(res); Ok(()) })?;
+        Ok(results)
+    }
+
+    /// Run `f` on the records of each chunk in parallel, and call `consume`
+    /// with each result, in the order of the chunks.
+    pub fn for_each<T, F, C>(&mut self, f: F, mut consume: C) -> CliResult<()>
+            where T: Send + 'static,
+                  F: Fn(RecordChunk) -> CliResult<T>,
+                  F: Send + Sync + 'static,
+                  C: FnMut(T) -> CliResult<()> {
+        let guesses = self.guess_starts()?;
+        let len = self.len;
+        let end = |i: usize| guesses.get(i + 1).cloned().unwrap_or(len);
+
+        let f = Arc::new(f);
+        let pool = ThreadPool::new(self.njobs);
+        let (send, recv) = chan::sync(0);
+        for (i, &start) in guesses.iter().enumerate() {
+            let (send, conf, f) = (send.clone(), self.conf.clone(), f.clone());
+            let end = end(i);
+            pool.execute(move || {
+                send.send((i, read_chunk(&conf, start, end, &*f)));
+            });
+        }
+        drop(send);
+
+        self.starts.clear();
+        let mut done: Vec<Option<_>> = guesses.iter().map(|_| None).collect();
+        // The start of the next chunk to consume, or `None` at the end of
+        // the file.
+        let mut next = Some(0);
+        let mut i = 0;
+        for (j, chunk) in recv.iter() {
+            done[j] = Some(chunk);
+            while i < done.len() && done[i].is_some() {
+                let (res, chunk_next) = done[i].take().unwrap();
+                if let Some(start) = next {
+                    let (res, chunk_next) = if start == guesses[i] {
+                        (res, chunk_next)
+                    } else {
+                        read_chunk(&self.conf, start, end(i), &*f)
+                    };
+                    self.starts.push(start);
+                    consume(res?)?;
+                    next = chunk_next;
+                }
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Guess the start of each chunk.
+    fn guess_starts(&self) -> CliResult<Vec<u64>> {
+        let mut file = fs::File::open(&self.path)?;
+        let mut starts = vec![0];
+        let mut buf = vec![];
+        for i in 1..self.nchunks as u64 {
+            let nominal = self.len * i / self.nchunks as u64;
+            let prev = *starts.last().unwrap();
+            if nominal <= prev {
+                continue;
+            }
+            // Include the byte before the nominal start, in case it is
+            // already the end of a line.
+            file.seek(io::SeekFrom::Start(nominal - 1))?;
+            buf.clear();
+            (&mut file).take(GUESS_LEN).read_to_end(&mut buf)?;
+            let start = match guess_start(&buf, self.delimiter, self.quote) {
+                Some(n) => nominal - 1 + n as u64,
+                // Without a line terminator nearby, it's probably best to
+                // read a long record in one chunk.
+                None => continue,
+            };
+            if start >= self.len {
+                break;
+            }
+            if start > prev {
+                starts.push(start);
+            }
+        }
+        Ok(starts)
+    }
+}
+
+/// Guess the offset of the first record that starts in `buf` (after its
+/// first byte), i.e., the first line terminator that isn't inside a quoted
+/// field.
+///
+/// Whether `buf` starts inside a quoted field is inferred from the first
+/// quote in `buf` that is either an opening quote (it follows a delimiter
+/// or line terminator, but not another quote) or a closing quote (it is
+/// followed by a delimiter or line terminator, but doesn't follow another
+/// quote), and from the number of quotes before it. If there is no such
+/// quote, `buf` is assumed to start outside of a quoted field.
+fn guess_start(buf: &[u8], delimiter: u8, quote: Option<u8>) -> Option<usize> {
+    let is_sep = |b: u8| b == delimiter || b == b'\n' || b == b'\r';
+    let mut inside = false;
+    if let Some(quote) = quote {
+        let quotes = buf.iter().enumerate().filter(|&(_, &b)| b == quote);
+        for (n, (i, _)) in quotes.enumerate() {
+            if i == 0 || i + 1 == buf.len() {
+                continue;
+            }
+            let (before, after) = (buf[i - 1], buf[i + 1]);
+            let opens = is_sep(before) && !is_sep(after) && after != quote;
+            let closes = is_sep(after) && !is_sep(before) && before != quote;
+            if opens != closes {
+                // The number of quotes before this one tells whether `buf`
+                // starts in the same state as this quote's.
+                inside = closes == (n % 2 == 0);
+                break;
+            }
+        }
+    }
+    for (i, &b) in buf.iter().enumerate() {
+        if i > 0 && !inside && buf[i - 1] == b'\n' {
+            return Some(i);
+        }
+        if Some(b) == quote {
+            inside = !inside;
+        }
+    }
+    None
+}
+
+/// Run `f` on the records that start in `start..end` (`start` is assumed
+/// to be the start of a record), and return its result along with the
+/// start of the first record at or after `end`, if any.
+fn read_chunk<T, F>(
+    conf: &Config,
+    start: u64,
+    end: u64,
+    f: &F,
+) -> (CliResult<T>, Option<u64>)
+        where F: Fn(RecordChunk) -> CliResult<T> {
+    let mut rdr = match reader_at(conf, start) {
+        Ok(rdr) => rdr,
+        Err(err) => return (Err(err), None),
+    };
+    let mut it = ChunkRecords { it: rdr.byte_records(), end: end, next: None };
+    let res = f(&mut it);
+    (res, it.next)
+}
+
+fn reader_at(conf: &Config, start: u64) -> CliResult<csv::Reader<fs::File>> {
+    let mut rdr = conf.reader_file()?;
+    if start > 0 {
+        let mut pos = csv::Position::new();
+        pos.set_byte(start);
+        rdr.seek(pos)?;
+    }
+    Ok(rdr)
+}
+
+/// The records of a chunk, which stops at the first record that starts at
+/// or after `end`.
+struct ChunkRecords<'r, R: 'r> {
+    it: csv::ByteRecordsIter<'r, R>,
+    end: u64,
+    /// The start of the record after the chunk.
+    next: Option<u64>,
+}
+
+impl<'r, R: io::Read> Iterator for ChunkRecords<'r, R> {
+    type Item = csv::Result<csv::ByteRecord>;
+
+    fn next(&mut self) -> Option<csv::Result<csv::ByteRecord>> {
+        if self.next.is_some() {
+            return None;
+        }
+        match self.it.next() {
+            Some(Ok(record)) => {
+                let start = record.position().map_or(0, |p| p.byte());
+                if start >= self.end {
+                    self.next = Some(start);
+                    return None;
+                }
+                Some(Ok(record))
+            }
+            res => res,
+        }
+    }
+}
+
+/// Run `f` on the records of chunks of the indexed file `idx` read with
+/// `conf`, in parallel with `njobs` jobs, and return the results in the
+/// order of the chunks.
+///
+/// Each job opens the index again, without checking it against the CSV data
+/// (see `Config::reindexed`), since `idx` has already been checked.
+pub fn map_indexed<T, F>(
+    conf: &Config,
+    idx: &IndexedFile,
+    njobs: usize,
+    f: F,
+) -> CliResult<Vec<T>>
+        where T: Send + 'static,
+              F: Fn(RecordChunk) -> CliResult<T>,
+              F: Send + Sync + 'static {
+    let header = match idx.header() {
+        Some(header) => Arc::new(header.clone()),
+        None => return fail!("the input has no index file"),
+    };
+    let nrecords = idx.count();
+    let chunk_size = util::chunk_size(nrecords as usize, njobs);
+    let nchunks = util::num_of_chunks(nrecords as usize, chunk_size);
+
+    let f = Arc::new(f);
+    let pool = ThreadPool::new(njobs);
+    let (send, recv) = chan::sync(0);
+    for i in 0..nchunks {
+        let (send, conf) = (send.clone(), conf.clone());
+        let (header, f) = (header.clone(), f.clone());
+        pool.execute(move || {
+            let start = (i * chunk_size) as u64;
+            let res = read_indexed(&conf, &header, start, chunk_size, &*f);
+            send.send((i, res));
+        });
+    }
+    drop(send);
+
+    let mut done: Vec<Option<CliResult<T>>> =
+        (0..nchunks).map(|_| None).collect();
+    for (i, res) in recv.iter() {
+        done[i] = Some(res);
+    }
+    done.into_iter().map(|res| match res {
+        Some(res) => res,
+        // The job panicked, so there is no result to report.
+        None => fail!("failed to read a chunk of the input"),
+    }).collect()
+}
+
+/// Run `f` on the `len` records of the indexed file read with `conf`, whose
+/// index has the given header, that start with the record at index `start`.
+fn read_indexed<T, F>(
+    conf: &Config,
+    header: &IndexHeader,
+    start: u64,
+    len: usize,
+    f: &F,
+) -> CliResult<T>
+        where F: Fn(RecordChunk) -> CliResult<T> {
+    let mut idx = conf.reindexed(header)?;
+    idx.seek(start)?;
+    let mut it = idx.byte_records().take(len);
+    f(&mut it)
+}
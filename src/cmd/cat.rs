@@ -36,6 +36,8 @@ Common options:
                            concatenating columns.
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 #[derive(Deserialize)]
@@ -47,6 +49,7 @@ struct Args {
     flag_output: Option<String>,
     flag_no_headers: bool,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
 }
 
 pub fn run(argv: &[&str]) -> CliResult<()> {
@@ -64,13 +67,16 @@ impl Args {
     fn configs(&self) -> CliResult<Vec<Config>> {
         util::many_configs(&*self.arg_input,
                            self.flag_delimiter,
-                           self.flag_no_headers)
+                           self.flag_no_headers,
+                           self.flag_sniff)
              .map_err(From::from)
     }
 
     fn cat_rows(&self) -> CliResult<()> {
         let mut row = csv::ByteRecord::new();
-        let mut wtr = Config::new(&self.flag_output).writer()?;
+        let mut wtr = Config::new(&self.flag_output)
+            .no_headers(self.flag_no_headers)
+            .writer()?;
         for (i, conf) in self.configs()?.into_iter().enumerate() {
             let mut rdr = conf.reader()?;
             if i == 0 {
@@ -80,11 +86,13 @@ impl Args {
                 wtr.write_byte_record(&row)?;
             }
         }
-        wtr.flush().map_err(From::from)
+        Config::finish(wtr).map_err(From::from)
     }
 
     fn cat_columns(&self) -> CliResult<()> {
-        let mut wtr = Config::new(&self.flag_output).writer()?;
+        let mut wtr = Config::new(&self.flag_output)
+            .no_headers(self.flag_no_headers)
+            .writer()?;
         let mut rdrs = self.configs()?
             .into_iter()
             .map(|conf| conf.no_headers(true).reader())
@@ -127,6 +135,6 @@ impl Args {
             }
             wtr.write_byte_record(&record)?;
         }
-        wtr.flush().map_err(From::from)
+        Config::finish(wtr).map_err(From::from)
     }
 }
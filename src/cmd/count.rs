@@ -13,39 +13,71 @@ given).
 Usage:
     xsv count [options] [<input>]
 
+count options:
+    -j, --jobs <arg>       The number of jobs to run in parallel when the
+                           input is a large file without an index. The file
+                           is split into byte ranges that are counted in
+                           parallel.
+                           When set to '0', the number of jobs is set to the
+                           number of CPUs detected.
+                           [default: 0]
+
 Common options:
     -h, --help             Display this message
     -n, --no-headers       When set, the first row will not be included in
                            the count.
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 #[derive(Deserialize)]
 struct Args {
     arg_input: Option<String>,
+    flag_jobs: usize,
     flag_no_headers: bool,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
 }
 
 pub fn run(argv: &[&str]) -> CliResult<()> {
     let args: Args = util::get_args(USAGE, argv)?;
     let conf = Config::new(&args.arg_input)
         .delimiter(args.flag_delimiter)
-        .no_headers(args.flag_no_headers);
+        .no_headers(args.flag_no_headers)
+        .sniff(args.flag_sniff);
+
+    let njobs = if args.flag_jobs == 0 {
+        util::num_cpus()
+    } else {
+        args.flag_jobs
+    };
 
     let count =
         match conf.indexed()? {
             Some(idx) => idx.count(),
-            None => {
-                let mut rdr = conf.reader()?;
-                let mut count = 0u64;
-                let mut record = csv::ByteRecord::new();
-                while rdr.read_byte_record(&mut record)? {
-                    count += 1;
+            None => match conf.chunked(njobs)? {
+                Some(mut chunked) => {
+                    chunked.map(|it| {
+                        let mut count = 0u64;
+                        for record in it {
+                            record?;
+                            count += 1;
+                        }
+                        Ok(count)
+                    })?.into_iter().sum()
+                }
+                None => {
+                    let mut rdr = conf.reader()?;
+                    let mut count = 0u64;
+                    let mut record = csv::ByteRecord::new();
+                    while rdr.read_byte_record(&mut record)? {
+                        count += 1;
+                    }
+                    count
                 }
-                count
-            }
+            },
         };
     Ok(println!("{}", count))
 }
@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+
+use csv;
+
+use CliResult;
+use config::{Config, Delimiter};
+use select::SelectColumns;
+use util;
+
+static USAGE: &'static str = "
+Compute a cross tabulation of two columns in CSV data.
+
+A cross tabulation (or contingency table) counts the rows for every pair of
+values in two columns. By default, it is formatted as a matrix with a row for
+each value of <row-column> and a column for each value of <column-column>:
+
+    status,east,west
+    ok,10,12
+    failed,3,0
+
+With --long, the table is instead formatted with one row for each pair of
+values that occurs at least once, using the same 'field', 'value' and 'count'
+columns as 'xsv frequency':
+
+    field,value,count,col_field,col_value
+
+As in 'xsv frequency', values are trimmed of surrounding whitespace and empty
+values are shown as '(NULL)'. Values are sorted lexicographically.
+
+Since this computes an exact table, memory proportional to the number of
+distinct pairs of values is required.
+
+Usage:
+    xsv crosstab [options] <row-column> <column-column> [<input>]
+    xsv crosstab --help
+
+crosstab options:
+    --totals               Add a '(total)' column with row totals and a
+                           '(total)' row with column totals. This only
+                           applies to the matrix format. It is an error if
+                           '(total)' is also a value in either column.
+    --percent <arg>        Show percentages instead of counts in the matrix,
+                           or add a 'percent' column to the --long format.
+                           Percentages are relative to the total of the
+                           'row', the 'column' or the whole table ('all').
+    --long                 Write the table in long format.
+
+Common options:
+    -h, --help             Display this message
+    -o, --output <file>    Write output to <file> instead of stdout.
+    -n, --no-headers       When set, the first row will NOT be interpreted
+                           as column names. Columns are then named by their
+                           1-based index.
+    -d, --delimiter <arg>  The field delimiter for reading CSV data.
+                           Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
+";
+
+#[derive(Clone, Deserialize)]
+struct Args {
+    arg_row_column: SelectColumns,
+    arg_column_column: SelectColumns,
+    arg_input: Option<String>,
+    flag_totals: bool,
+    flag_percent: Option<Percent>,
+    flag_long: bool,
+    flag_output: Option<String>,
+    flag_no_headers: bool,
+    flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
+}
+
+/// The label of the row and column of totals.
+const TOTAL: &'static [u8] = b"(total)";
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+enum Percent {
+    Row,
+    Column,
+    All,
+}
+
+pub fn run(argv: &[&str]) -> CliResult<()> {
+    let args: Args = util::get_args(USAGE, argv)?;
+    let rconfig = Config::new(&args.arg_input)
+        .delimiter(args.flag_delimiter)
+        .no_headers(args.flag_no_headers)
+        .sniff(args.flag_sniff);
+
+    let mut rdr = rconfig.reader()?;
+    let headers = rdr.byte_headers()?.clone();
+    let row_col = args.one_column(&args.arg_row_column, &headers)?;
+    let col_col = args.one_column(&args.arg_column_column, &headers)?;
+
+    let mut tab = CrossTab::default();
+    let mut record = csv::ByteRecord::new();
+    while rdr.read_byte_record(&mut record)? {
+        tab.add(normalize(&record[row_col]), normalize(&record[col_col]));
+    }
+
+    if args.flag_totals && !args.flag_long
+            && (tab.rows.contains_key(TOTAL) || tab.cols.contains_key(TOTAL)) {
+        return fail!("--totals can't be used when '(total)' is a value in \
+                      the data, since it couldn't be told apart from the \
+                      totals.");
+    }
+
+    let row_name = args.field_name(&headers, row_col);
+    let col_name = args.field_name(&headers, col_col);
+    let mut wtr = Config::new(&args.flag_output).writer()?;
+    if args.flag_long {
+        let mut header =
+            vec!["field", "value", "count", "col_field", "col_value"];
+        if args.flag_percent.is_some() {
+            header.push("percent");
+        }
+        wtr.write_record(&header)?;
+        for (row_val, cols) in &tab.counts {
+            let row_total = tab.rows[row_val];
+            for (col_val, &count) in cols {
+                let col_total = tab.cols[col_val];
+                let mut record = vec![
+                    row_name.clone(), row_val.clone(),
+                    count.to_string().into_bytes(),
+                    col_name.clone(), col_val.clone(),
+                ];
+                if let Some(percent) = args.flag_percent {
+                    let total = percent.total(row_total, col_total, tab.total);
+                    record.push(show_percent(count, total).into_bytes());
+                }
+                wtr.write_record(&record)?;
+            }
+        }
+    } else {
+        let mut header = vec![row_name];
+        header.extend(tab.cols.keys().cloned());
+        if args.flag_totals {
+            header.push(TOTAL.to_vec());
+        }
+        wtr.write_record(&header)?;
+        for (row_val, &row_total) in &tab.rows {
+            let mut record = vec![row_val.clone()];
+            for (col_val, &col_total) in &tab.cols {
+                let count = tab.count(row_val, col_val);
+                record.push(args.show(count, row_total, col_total, tab.total));
+            }
+            if args.flag_totals {
+                record.push(
+                    args.show(row_total, row_total, tab.total, tab.total));
+            }
+            wtr.write_record(&record)?;
+        }
+        if args.flag_totals {
+            let mut record = vec![TOTAL.to_vec()];
+            for &col_total in tab.cols.values() {
+                record.push(
+                    args.show(col_total, tab.total, col_total, tab.total));
+            }
+            record.push(args.show(tab.total, tab.total, tab.total, tab.total));
+            wtr.write_record(&record)?;
+        }
+    }
+    Config::finish(wtr)?;
+    Ok(())
+}
+
+impl Args {
+    /// Resolve a selection that must refer to exactly one column.
+    fn one_column(
+        &self,
+        sel: &SelectColumns,
+        headers: &csv::ByteRecord,
+    ) -> CliResult<usize> {
+        let sel = sel.selection(headers, !self.flag_no_headers)?;
+        if sel.len() == 1 {
+            Ok(sel[0])
+        } else {
+            fail_usage!("crosstab columns must each select exactly one column")
+        }
+    }
+
+    fn field_name(&self, headers: &csv::ByteRecord, i: usize) -> Vec<u8> {
+        if self.flag_no_headers {
+            (i+1).to_string().into_bytes()
+        } else {
+            headers[i].to_vec()
+        }
+    }
+
+    /// Format a cell of the matrix, which is either a count or a percentage
+    /// of one of the given totals.
+    fn show(
+        &self,
+        count: u64,
+        row_total: u64,
+        col_total: u64,
+        total: u64,
+    ) -> Vec<u8> {
+        match self.flag_percent {
+            None => count.to_string().into_bytes(),
+            Some(percent) => {
+                let total = percent.total(row_total, col_total, total);
+                show_percent(count, total).into_bytes()
+            }
+        }
+    }
+}
+
+impl Percent {
+    fn total(self, row_total: u64, col_total: u64, total: u64) -> u64 {
+        match self {
+            Percent::Row => row_total,
+            Percent::Column => col_total,
+            Percent::All => total,
+        }
+    }
+}
+
+/// CrossTab counts pairs of values, along with the totals of each row and
+/// column.
+#[derive(Default)]
+struct CrossTab {
+    /// The count of each pair of values, by row value and then column
+    /// value. Pairs that don't occur are left out.
+    counts: BTreeMap<Vec<u8>, BTreeMap<Vec<u8>, u64>>,
+    rows: BTreeMap<Vec<u8>, u64>,
+    cols: BTreeMap<Vec<u8>, u64>,
+    total: u64,
+}
+
+impl CrossTab {
+    fn add(&mut self, row_val: Vec<u8>, col_val: Vec<u8>) {
+        *self.rows.entry(row_val.clone()).or_insert(0) += 1;
+        *self.cols.entry(col_val.clone()).or_insert(0) += 1;
+        *self.counts.entry(row_val).or_insert_with(BTreeMap::new)
+                    .entry(col_val).or_insert(0) += 1;
+        self.total += 1;
+    }
+
+    fn count(&self, row_val: &[u8], col_val: &[u8]) -> u64 {
+        self.counts.get(row_val)
+                   .and_then(|cols| cols.get(col_val))
+                   .cloned()
+                   .unwrap_or(0)
+    }
+}
+
+fn show_percent(count: u64, total: u64) -> String {
+    if total == 0 {
+        return "".to_owned();
+    }
+    (100.0 * count as f64 / total as f64).to_string()
+}
+
+/// Trim whitespace and replace empty values with `(NULL)`, like
+/// `xsv frequency` does.
+fn normalize(value: &[u8]) -> Vec<u8> {
+    let value = match ::std::str::from_utf8(value) {
+        Ok(s) => s.trim().as_bytes(),
+        Err(_) => value,
+    };
+    if value.is_empty() {
+        b"(NULL)".to_vec()
+    } else {
+        value.to_vec()
+    }
+}
@@ -31,6 +31,8 @@ Common options:
     -o, --output <file>    Write output to <file> instead of stdout.
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 #[derive(Deserialize)]
@@ -39,6 +41,7 @@ struct Args {
     flag_length: Option<usize>,
     flag_output: Option<String>,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
 }
 
 pub fn run(argv: &[&str]) -> CliResult<()> {
@@ -46,18 +49,19 @@ pub fn run(argv: &[&str]) -> CliResult<()> {
     let config = Config::new(&args.arg_input)
         .delimiter(args.flag_delimiter)
         .no_headers(true)
-        .flexible(true);
+        .flexible(true)
+        .sniff(args.flag_sniff);
     let length = match args.flag_length {
         Some(length) => {
             if length == 0 {
-                return fail!("Length must be greater than 0.");
+                return fail_usage!("Length must be greater than 0.");
             }
             length
         }
         None => {
             if config.is_std() {
-                return fail!("<stdin> cannot be used in this command. \
-                              Please specify a file path.");
+                return fail_usage!("<stdin> cannot be used in this \
+                                    command. Please specify a file path.");
             }
             let mut maxlen = 0usize;
             let mut rdr = config.reader()?;
@@ -90,6 +94,6 @@ pub fn run(argv: &[&str]) -> CliResult<()> {
         }
         wtr.write_byte_record(&r)?;
     }
-    wtr.flush()?;
+    Config::finish(wtr)?;
     Ok(())
 }
@@ -31,6 +31,8 @@ Common options:
     -o, --output <file>    Write output to <file> instead of stdout.
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 #[derive(Deserialize)]
@@ -41,6 +43,7 @@ struct Args {
     flag_ascii: bool,
     flag_output: Option<String>,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
     flag_quote: Delimiter,
     flag_quote_always: bool,
     flag_escape: Option<Delimiter>,
@@ -51,7 +54,8 @@ pub fn run(argv: &[&str]) -> CliResult<()> {
 
     let rconfig = Config::new(&args.arg_input)
         .delimiter(args.flag_delimiter)
-        .no_headers(true);
+        .no_headers(true)
+        .sniff(args.flag_sniff);
     let mut wconfig = Config::new(&args.flag_output)
         .delimiter(args.flag_out_delimiter)
         .crlf(args.flag_crlf);
@@ -76,6 +80,6 @@ pub fn run(argv: &[&str]) -> CliResult<()> {
     while rdr.read_byte_record(&mut r)? {
         wtr.write_byte_record(&r)?;
     }
-    wtr.flush()?;
+    Config::finish(wtr)?;
     Ok(())
 }
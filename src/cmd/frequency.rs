@@ -1,15 +1,17 @@
-use std::fs;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::io;
+use std::str;
 
-use chan;
 use csv;
-use stats::{Frequencies, merge_all};
-use threadpool::ThreadPool;
+use stats::{Commute, Frequencies, merge_all};
 
 use CliResult;
+use chunk;
 use config::{Config, Delimiter};
-use index::Indexed;
+use index::IndexedFile;
 use select::{SelectColumns, Selection};
+use sketch::HeavyHitters;
 use util;
 
 static USAGE: &'static str = "
@@ -21,10 +23,28 @@ The frequency table is formatted as CSV data:
 
 By default, there is a row for the N most frequent values for each field in the
 data. The order and number of values can be tweaked with --asc and --limit,
-respectively.
+respectively. With --other, the values beyond the limit are combined into a
+single '(other)' row, so that the counts of each field still add up to the
+total. With --percent, every row also shows its share of the total and the
+running sum of shares:
+
+    field,value,count,percent,cumulative_percent
+
+With --weight, the 'count' of a value is the sum of the given numeric column
+over the rows with that value, instead of the number of rows. Rows whose
+weight is not a number are ignored.
 
 Since this computes an exact frequency table, memory proportional to the
-cardinality of each column is required.
+cardinality of each column is required. For columns with very many distinct
+values, --approx computes an approximate frequency table in bounded memory
+instead. It then adds an 'error' column to the output:
+
+    field,value,count,error
+
+The true count of each value is at least 'count' and at most 'count + error'.
+Values that don't appear in the table occur at most 'error' times. The error
+is at most N/S, where N is the number of rows and S is the size given to
+--approx.
 
 Usage:
     xsv frequency [options] [<input>]
@@ -40,11 +60,20 @@ frequency options:
                            [default: 10]
     -a, --asc              Sort the frequency tables in ascending order by
                            count. The default is descending order.
+    --other                Add an '(other)' row with the combined count of
+                           the values excluded by --limit.
+    --percent              Add 'percent' and 'cumulative_percent' columns.
+    -w, --weight <column>  Sum the values of this column instead of counting
+                           rows. This cannot be used with --approx.
     --no-nulls             Don't include NULLs in the frequency table.
+    --approx <size>        Compute an approximate frequency table that keeps
+                           track of about <size> values per column. This
+                           cannot be used with --asc.
     -j, --jobs <arg>       The number of jobs to run in parallel.
                            This works better when the given CSV data has
-                           an index already created. Note that a file handle
-                           is opened for each job.
+                           an index already created. Without an index, a
+                           large file is split into byte ranges instead.
+                           Note that a file handle is opened for each job.
                            When set to '0', the number of jobs is set to the
                            number of CPUs detected.
                            [default: 0]
@@ -58,6 +87,8 @@ Common options:
                            names.
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 #[derive(Clone, Deserialize)]
@@ -66,43 +97,180 @@ struct Args {
     flag_select: SelectColumns,
     flag_limit: usize,
     flag_asc: bool,
+    flag_other: bool,
+    flag_percent: bool,
+    flag_weight: Option<SelectColumns>,
     flag_no_nulls: bool,
+    flag_approx: Option<usize>,
     flag_jobs: usize,
     flag_output: Option<String>,
     flag_no_headers: bool,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
 }
 
 pub fn run(argv: &[&str]) -> CliResult<()> {
     let args: Args = util::get_args(USAGE, argv)?;
+    if args.flag_approx.is_some() && args.flag_asc {
+        return fail_usage!("--asc cannot be used with --approx.");
+    }
+    if args.flag_approx.is_some() && args.flag_weight.is_some() {
+        return fail_usage!("--weight cannot be used with --approx.");
+    }
     let rconfig = args.rconfig();
 
     let mut wtr = Config::new(&args.flag_output).writer()?;
     let (headers, tables) = match args.rconfig().indexed()? {
         Some(ref mut idx) if args.njobs() > 1 => args.parallel_ftables(idx),
-        _ => args.sequential_ftables(),
+        Some(_) => args.sequential_ftables(),
+        None => args.chunked_ftables(),
     }?;
 
-    wtr.write_record(vec!["field", "value", "count"])?;
+    let mut headers_row = vec!["field", "value", "count"];
+    if args.flag_approx.is_some() {
+        headers_row.push("error");
+    }
+    if args.flag_percent {
+        headers_row.push("percent");
+        headers_row.push("cumulative_percent");
+    }
+    wtr.write_record(headers_row)?;
     let head_ftables = headers.into_iter().zip(tables.into_iter());
     for (i, (header, ftab)) in head_ftables.enumerate() {
         let mut header = header.to_vec();
         if rconfig.no_headers {
             header = (i+1).to_string().into_bytes();
         }
+        let error = ftab.error().map(|e| e.to_string());
+        let total = ftab.total();
+        let mut cumulative = 0.0;
         for (value, count) in args.counts(&ftab).into_iter() {
-            let count = count.to_string();
-            let row = vec![&*header, &*value, count.as_bytes()];
+            cumulative += count;
+            let mut row = vec![header.clone(), value];
+            row.push(count.to_string().into_bytes());
+            if let Some(ref error) = error {
+                row.push(error.clone().into_bytes());
+            }
+            if args.flag_percent {
+                row.push(percent(count, total).into_bytes());
+                row.push(percent(cumulative, total).into_bytes());
+            }
             wtr.write_record(row)?;
         }
     }
+    Config::finish(wtr)?;
     Ok(())
 }
 
 type ByteString = Vec<u8>;
 type Headers = csv::ByteRecord;
-type FTable = Frequencies<Vec<u8>>;
-type FTables = Vec<Frequencies<Vec<u8>>>;
+type FTables = Vec<FTable>;
+
+/// FTable is a frequency table for a single column.
+#[derive(Clone)]
+enum FTable {
+    /// The exact counts, and the number of values counted.
+    Exact(Frequencies<Vec<u8>>, u64),
+    Weighted(WeightedFrequencies),
+    Approx(HeavyHitters),
+}
+
+impl FTable {
+    /// Add an occurrence of `value`. The weight is only used by weighted
+    /// tables.
+    fn add(&mut self, value: &[u8], weight: f64) {
+        match *self {
+            FTable::Exact(ref mut ftab, ref mut total) => {
+                ftab.add(value.to_vec());
+                *total += 1;
+            }
+            FTable::Weighted(ref mut ftab) => ftab.add(value, weight),
+            FTable::Approx(ref mut ftab) => ftab.add(value),
+        }
+    }
+
+    /// Returns every tracked value with its count, sorted by count.
+    fn counts(&self, asc: bool) -> Vec<(&Vec<u8>, f64)> {
+        let counts = match *self {
+            FTable::Exact(ref ftab, _) if asc => ftab.least_frequent(),
+            FTable::Exact(ref ftab, _) => ftab.most_frequent(),
+            FTable::Weighted(ref ftab) => return ftab.counts(asc),
+            FTable::Approx(ref ftab) => ftab.most_frequent(),
+        };
+        counts.into_iter().map(|(v, c)| (v, c as f64)).collect()
+    }
+
+    /// Returns the sum of all counts, including those of values that an
+    /// approximate table no longer tracks.
+    fn total(&self) -> f64 {
+        match *self {
+            FTable::Exact(_, total) => total as f64,
+            FTable::Weighted(ref ftab) => ftab.weights.values().sum(),
+            FTable::Approx(ref ftab) => ftab.len() as f64,
+        }
+    }
+
+    /// Returns the maximum error of counts in an approximate table.
+    fn error(&self) -> Option<u64> {
+        match *self {
+            FTable::Exact(..) | FTable::Weighted(_) => None,
+            FTable::Approx(ref ftab) => Some(ftab.error()),
+        }
+    }
+}
+
+impl Commute for FTable {
+    fn merge(&mut self, other: FTable) {
+        match (self, other) {
+            (&mut FTable::Exact(ref mut f1, ref mut t1),
+             FTable::Exact(f2, t2)) => {
+                f1.merge(f2);
+                *t1 += t2;
+            }
+            (&mut FTable::Weighted(ref mut f1), FTable::Weighted(f2)) => {
+                f1.merge(f2)
+            }
+            (&mut FTable::Approx(ref mut f1), FTable::Approx(f2)) => {
+                f1.merge(f2)
+            }
+            _ => unreachable!("cannot merge different kinds of tables"),
+        }
+    }
+}
+
+/// WeightedFrequencies sums a weight for each distinct value.
+#[derive(Clone, Default)]
+struct WeightedFrequencies {
+    weights: HashMap<Vec<u8>, f64>,
+}
+
+impl WeightedFrequencies {
+    fn add(&mut self, value: &[u8], weight: f64) {
+        if let Some(w) = self.weights.get_mut(value) {
+            *w += weight;
+            return;
+        }
+        self.weights.insert(value.to_vec(), weight);
+    }
+
+    fn counts(&self, asc: bool) -> Vec<(&Vec<u8>, f64)> {
+        let mut counts: Vec<_> =
+            self.weights.iter().map(|(v, &w)| (v, w)).collect();
+        counts.sort_by(|&(_, w1), &(_, w2)| {
+            let ord = w1.partial_cmp(&w2).unwrap_or(Ordering::Equal);
+            if asc { ord } else { ord.reverse() }
+        });
+        counts
+    }
+}
+
+impl Commute for WeightedFrequencies {
+    fn merge(&mut self, other: WeightedFrequencies) {
+        for (value, weight) in other.weights {
+            *self.weights.entry(value).or_insert(0.0) += weight;
+        }
+    }
+}
 
 impl Args {
     fn rconfig(&self) -> Config {
@@ -110,86 +278,132 @@ impl Args {
             .delimiter(self.flag_delimiter)
             .no_headers(self.flag_no_headers)
             .select(self.flag_select.clone())
+            .sniff(self.flag_sniff)
     }
 
-    fn counts(&self, ftab: &FTable) -> Vec<(ByteString, u64)> {
-        let mut counts = if self.flag_asc {
-            ftab.least_frequent()
-        } else {
-            ftab.most_frequent()
-        };
-        if self.flag_limit > 0 {
-            counts = counts.into_iter().take(self.flag_limit).collect();
+    fn counts(&self, ftab: &FTable) -> Vec<(ByteString, f64)> {
+        let mut counts = ftab.counts(self.flag_asc);
+        let mut other = None;
+        if self.flag_limit > 0 && self.flag_limit < counts.len() {
+            let rest = counts.split_off(self.flag_limit);
+            other = Some(rest.into_iter().map(|(_, c)| c).sum::<f64>());
+        }
+        if let FTable::Approx(_) = *ftab {
+            // Values that are no longer tracked belong in '(other)' too.
+            let tracked: f64 = counts.iter().map(|&(_, c)| c).sum::<f64>()
+                               + other.unwrap_or(0.0);
+            let untracked = ftab.total() - tracked;
+            if untracked > 0.0 {
+                other = Some(other.unwrap_or(0.0) + untracked);
+            }
         }
-        counts.into_iter().map(|(bs, c)| {
+        let mut counts: Vec<_> = counts.into_iter().map(|(bs, c)| {
             if b"" == &**bs {
                 (b"(NULL)"[..].to_vec(), c)
             } else {
                 (bs.clone(), c)
             }
-        }).collect()
+        }).collect();
+        if self.flag_other {
+            if let Some(other) = other {
+                counts.push((b"(other)"[..].to_vec(), other));
+            }
+        }
+        counts
     }
 
     fn sequential_ftables(&self) -> CliResult<(Headers, FTables)> {
         let mut rdr = self.rconfig().reader()?;
-        let (headers, sel) = self.sel_headers(&mut rdr)?;
-        Ok((headers, self.ftables(&sel, rdr.byte_records())?))
+        let (headers, sel, weight) = self.sel_headers(&mut rdr)?;
+        Ok((headers, self.ftables(&sel, weight, rdr.byte_records())?))
     }
 
-    fn parallel_ftables(&self, idx: &mut Indexed<fs::File, fs::File>)
+    fn parallel_ftables(&self, idx: &mut IndexedFile)
                        -> CliResult<(Headers, FTables)> {
         let mut rdr = self.rconfig().reader()?;
-        let (headers, sel) = self.sel_headers(&mut rdr)?;
+        let (headers, sel, weight) = self.sel_headers(&mut rdr)?;
 
         if idx.count() == 0 {
             return Ok((headers, vec![]));
         }
 
-        let chunk_size = util::chunk_size(idx.count() as usize, self.njobs());
-        let nchunks = util::num_of_chunks(idx.count() as usize, chunk_size);
-
-        let pool = ThreadPool::new(self.njobs());
-        let (send, recv) = chan::sync(0);
-        for i in 0..nchunks {
-            let (send, args, sel) = (send.clone(), self.clone(), sel.clone());
-            pool.execute(move || {
-                let mut idx = args.rconfig().indexed().unwrap().unwrap();
-                idx.seek((i * chunk_size) as u64).unwrap();
-                let it = idx.byte_records().take(chunk_size);
-                send.send(args.ftables(&sel, it).unwrap());
-            });
-        }
-        drop(send);
-        Ok((headers, merge_all(recv.iter()).unwrap()))
+        let args = self.clone();
+        let tables = chunk::map_indexed(
+            &self.rconfig(), idx, self.njobs(),
+            move |it| args.ftables(&sel, weight, it))?;
+        Ok((headers, merge_all(tables.into_iter()).unwrap()))
     }
 
-    fn ftables<I>(&self, sel: &Selection, it: I) -> CliResult<FTables>
+    fn chunked_ftables(&self) -> CliResult<(Headers, FTables)> {
+        let mut chunked = match self.rconfig().chunked(self.njobs())? {
+            None => return self.sequential_ftables(),
+            Some(chunked) => chunked,
+        };
+        let mut rdr = self.rconfig().reader()?;
+        let (headers, sel, weight) = self.sel_headers(&mut rdr)?;
+
+        let args = self.clone();
+        let tables = chunked.map(move |it| args.ftables(&sel, weight, it))?;
+        Ok((headers, merge_all(tables.into_iter()).unwrap()))
+    }
+
+    fn ftables<I>(
+        &self,
+        sel: &Selection,
+        weight: Option<usize>,
+        it: I,
+    ) -> CliResult<FTables>
             where I: Iterator<Item=csv::Result<csv::ByteRecord>> {
-        let null = &b""[..].to_vec();
         let nsel = sel.normal();
-        let mut tabs: Vec<_> =
-            (0..nsel.len()).map(|_| Frequencies::new()).collect();
+        let mut tabs: Vec<_> = (0..nsel.len()).map(|_| self.new_ftable())
+                                              .collect();
         for row in it {
             let row = row?;
+            let weight = match weight {
+                None => 1.0,
+                Some(i) => {
+                    match str::from_utf8(&row[i]).ok()
+                                                 .and_then(|s| s.parse().ok()) {
+                        Some(weight) => weight,
+                        None => continue,
+                    }
+                }
+            };
             for (i, field) in nsel.select(row.into_iter()).enumerate() {
                 let field = trim(field.to_vec());
-                if !field.is_empty() {
-                    tabs[i].add(field);
-                } else {
-                    if !self.flag_no_nulls {
-                        tabs[i].add(null.clone());
-                    }
+                if !field.is_empty() || !self.flag_no_nulls {
+                    tabs[i].add(&field, weight);
                 }
             }
         }
         Ok(tabs)
     }
 
+    fn new_ftable(&self) -> FTable {
+        match (self.flag_approx, &self.flag_weight) {
+            (Some(size), _) => FTable::Approx(HeavyHitters::new(size)),
+            (None, &Some(_)) => FTable::Weighted(Default::default()),
+            (None, &None) => FTable::Exact(Frequencies::new(), 0),
+        }
+    }
+
     fn sel_headers<R: io::Read>(&self, rdr: &mut csv::Reader<R>)
-                  -> CliResult<(csv::ByteRecord, Selection)> {
+                  -> CliResult<(csv::ByteRecord, Selection, Option<usize>)> {
         let headers = rdr.byte_headers()?;
         let sel = self.rconfig().selection(headers)?;
-        Ok((sel.select(headers).map(|h| h.to_vec()).collect(), sel))
+        let weight = match self.flag_weight {
+            None => None,
+            Some(ref weight) => {
+                let weight = weight.selection(headers, !self.flag_no_headers)?;
+                if weight.len() != 1 {
+                    return fail_usage!(
+                        "--weight must select exactly one column.");
+                }
+                Some(weight[0])
+            }
+        };
+        let headers = sel.select(headers).map(|h| h.to_vec()).collect();
+        Ok((headers, sel, weight))
     }
 
     fn njobs(&self) -> usize {
@@ -203,3 +417,10 @@ fn trim(bs: ByteString) -> ByteString {
         Err(bs) => bs.into_bytes(),
     }
 }
+
+fn percent(count: f64, total: f64) -> String {
+    if total == 0.0 {
+        return "".to_owned();
+    }
+    (100.0 * count / total).to_string()
+}
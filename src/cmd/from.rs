@@ -0,0 +1,96 @@
+use std::fs;
+
+use arrow::array::{Array, AsArray};
+use arrow::datatypes::{DataType, SchemaRef};
+use arrow::error::ArrowError;
+use arrow::ipc::reader::FileReader;
+use arrow::record_batch::RecordBatch;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+use CliResult;
+use config::Config;
+use util;
+
+static USAGE: &'static str = "
+Convert Apache Parquet or Arrow IPC files to CSV data.
+
+The header row is made up of the names of the columns. Null values are
+written as empty fields and binary values are written as is. Other values are
+written in Arrow's standard text format, e.g., '2018-03-01' for dates and
+'2018-03-01T12:00:00Z' for timestamps.
+
+Record batches (or row groups) are converted one at a time, so that the whole
+file doesn't need to fit in memory.
+
+Usage:
+    xsv from parquet [options] <input>
+    xsv from arrow [options] <input>
+    xsv from --help
+
+Common options:
+    -h, --help             Display this message
+    -o, --output <file>    Write output to <file> instead of stdout.
+";
+
+#[derive(Deserialize)]
+struct Args {
+    cmd_parquet: bool,
+    arg_input: String,
+    flag_output: Option<String>,
+}
+
+type Batches = Box<dyn Iterator<Item=Result<RecordBatch, ArrowError>>>;
+
+pub fn run(argv: &[&str]) -> CliResult<()> {
+    let args: Args = util::get_args(USAGE, argv)?;
+    let (schema, batches) = args.batches()?;
+
+    let mut wtr = Config::new(&args.flag_output).writer()?;
+    wtr.write_record(schema.fields().iter().map(|f| f.name()))?;
+    let options = FormatOptions::default();
+    let mut record = vec![];
+    for batch in batches {
+        let batch = batch?;
+        let columns = batch.columns().iter().map(|array| {
+            Ok((array, ArrayFormatter::try_new(array.as_ref(), &options)?))
+        }).collect::<Result<Vec<_>, ArrowError>>()?;
+        for i in 0..batch.num_rows() {
+            record.clear();
+            for &(array, ref formatter) in &columns {
+                record.push(if array.is_null(i) {
+                    vec![]
+                } else {
+                    match *array.data_type() {
+                        DataType::Binary => {
+                            array.as_binary::<i32>().value(i).to_vec()
+                        }
+                        DataType::LargeBinary => {
+                            array.as_binary::<i64>().value(i).to_vec()
+                        }
+                        _ => formatter.value(i).to_string().into_bytes(),
+                    }
+                });
+            }
+            wtr.write_record(&record)?;
+        }
+    }
+    Config::finish(wtr)?;
+    Ok(())
+}
+
+impl Args {
+    fn batches(&self) -> CliResult<(SchemaRef, Batches)> {
+        let file = fs::File::open(&self.arg_input).map_err(|err| {
+            format!("failed to open {}: {}", self.arg_input, err)
+        })?;
+        if self.cmd_parquet {
+            let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+            let schema = builder.schema().clone();
+            Ok((schema, Box::new(builder.build()?)))
+        } else {
+            let rdr = FileReader::try_new(file, None)?;
+            Ok((rdr.schema(), Box::new(rdr)))
+        }
+    }
+}
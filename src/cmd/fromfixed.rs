@@ -0,0 +1,135 @@
+use std::io::{self, BufRead};
+use std::str;
+
+use CliResult;
+use config::Config;
+use util::{self, FixedLayout, FixedWidths};
+
+static USAGE: &'static str = "
+Convert fixed width data to CSV.
+
+The layout of the columns is given either as a list of widths with --widths,
+or as a spec file with --spec. A spec file is a CSV file with one row for
+each column, a 'width' column and an optional 'name' column.
+
+Widths are measured in characters. (If a line is not valid UTF-8, then they
+are measured in bytes.) Lines that are shorter than the sum of the widths
+have empty fields at the end, and anything past the sum of the widths is
+ignored.
+
+As with other commands, the first line is read as the header row, unless
+'--no-headers' is given. Column names in a spec file replace the header
+row, or are written as the header row when using '--no-headers'.
+
+Usage:
+    xsv fromfixed [options] (--widths <arg> | --spec <file>) [<input>]
+    xsv fromfixed --help
+
+fromfixed options:
+    -w, --widths <arg>     A comma separated list of column widths,
+                           e.g., 10,5,20.
+    --spec <file>          A CSV file describing the columns.
+    --trim <arg>           Trim spaces from fields on the 'left', the 'right',
+                           'both' sides or 'none'. [default: both]
+
+Common options:
+    -h, --help             Display this message
+    -o, --output <file>    Write output to <file> instead of stdout.
+    -n, --no-headers       When set, the first line will NOT be interpreted
+                           as column names.
+";
+
+#[derive(Deserialize)]
+struct Args {
+    arg_input: Option<String>,
+    flag_widths: Option<FixedWidths>,
+    flag_spec: Option<String>,
+    flag_trim: Trim,
+    flag_output: Option<String>,
+    flag_no_headers: bool,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+enum Trim {
+    Left,
+    Right,
+    Both,
+    None,
+}
+
+pub fn run(argv: &[&str]) -> CliResult<()> {
+    let args: Args = util::get_args(USAGE, argv)?;
+    let layout = match (args.flag_widths.as_ref(), args.flag_spec.as_ref()) {
+        (Some(widths), _) => {
+            FixedLayout { names: None, widths: widths.0.clone() }
+        }
+        (None, Some(spec)) => FixedLayout::from_spec(spec)?,
+        (None, None) => unreachable!(),
+    };
+    let rconfig = Config::new(&args.arg_input)
+        .no_headers(args.flag_no_headers);
+
+    let rdr = io::BufReader::new(rconfig.io_reader()?);
+    let mut wtr = Config::new(&args.flag_output)
+        .no_headers(args.flag_no_headers && layout.names.is_none())
+        .writer()?;
+    let mut lines = rdr.split(b'\n');
+    if !rconfig.no_headers {
+        if let Some(line) = lines.next() {
+            let headers = args.split(&layout, &line?);
+            if layout.names.is_none() {
+                wtr.write_record(&headers)?;
+            }
+        }
+    }
+    if let Some(ref names) = layout.names {
+        wtr.write_record(names)?;
+    }
+    for line in lines {
+        let line = line?;
+        if line.is_empty() || line == b"\r" {
+            continue;
+        }
+        wtr.write_record(args.split(&layout, &line))?;
+    }
+    Config::finish(wtr)?;
+    Ok(())
+}
+
+impl Args {
+    /// Split a line into fields.
+    fn split(&self, layout: &FixedLayout, line: &[u8]) -> Vec<Vec<u8>> {
+        let line = if line.ends_with(b"\r") {
+            &line[..line.len() - 1]
+        } else {
+            line
+        };
+        // Find the byte offset of the start of each character, so that
+        // columns can be sliced in characters.
+        let offsets: Vec<usize> = match str::from_utf8(line) {
+            Ok(s) => s.char_indices().map(|(i, _)| i).collect(),
+            Err(_) => (0..line.len()).collect(),
+        };
+        let mut bounds = vec![0];
+        let mut end = 0;
+        for &w in &layout.widths {
+            end += w;
+            bounds.push(offsets.get(end).cloned().unwrap_or(line.len()));
+        }
+        bounds.windows(2).map(|w| self.trim(&line[w[0]..w[1]])).collect()
+    }
+
+    fn trim(&self, mut field: &[u8]) -> Vec<u8> {
+        if let Trim::Left | Trim::Both = self.flag_trim {
+            while field.first() == Some(&b' ') {
+                field = &field[1..];
+            }
+        }
+        if let Trim::Right | Trim::Both = self.flag_trim {
+            while field.last() == Some(&b' ') {
+                field = &field[..field.len() - 1];
+            }
+        }
+        field.to_vec()
+    }
+}
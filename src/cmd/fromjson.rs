@@ -0,0 +1,184 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+use serde_json::{self, Map, Value};
+
+use CliResult;
+use config::Config;
+use util;
+
+static USAGE: &'static str = "
+Convert JSON data to CSV.
+
+The input may be newline-delimited JSON (NDJSON), with one object per line,
+or a JSON array of objects. More generally, the input is a sequence of JSON
+values separated by whitespace, where each value is either an object or an
+array of objects.
+
+Each object becomes a row. The columns are the union of the keys of every
+object, in the order in which they are first seen. Nested objects are
+flattened, so that {\"a\": {\"b\": 1}} has a column named 'a.b'. It is an error
+if an object has two fields with the same name once flattened, as in
+{\"a.b\": 1, \"a\": {\"b\": 2}}. Arrays are written as JSON, unless
+given --join-arrays. Null values, empty objects and missing keys are written
+as empty fields.
+
+Since the columns are only known once all objects have been seen, input read
+from a file is read twice, while input read from stdin is kept in memory.
+
+Usage:
+    xsv fromjson [options] [<input>]
+    xsv fromjson --help
+
+fromjson options:
+    --join-arrays <sep>    Write arrays as their elements joined by <sep>,
+                           instead of as JSON.
+
+Common options:
+    -h, --help             Display this message
+    -o, --output <file>    Write output to <file> instead of stdout.
+";
+
+#[derive(Deserialize)]
+struct Args {
+    arg_input: Option<String>,
+    flag_join_arrays: Option<String>,
+    flag_output: Option<String>,
+}
+
+/// A flattened JSON object, as a list of column names and values.
+type Row = Vec<(String, String)>;
+
+pub fn run(argv: &[&str]) -> CliResult<()> {
+    let args: Args = util::get_args(USAGE, argv)?;
+    let rconfig = Config::new(&args.arg_input);
+
+    let mut columns = Columns::default();
+    let mut buffered = vec![];
+    args.rows(rconfig.io_reader()?, |row| {
+        for (name, _) in &row {
+            columns.add(name);
+        }
+        if rconfig.is_std() {
+            buffered.push(row);
+        }
+        Ok(())
+    })?;
+
+    let mut wtr = Config::new(&args.flag_output).writer()?;
+    if columns.names.is_empty() {
+        return Ok(Config::finish(wtr)?);
+    }
+    wtr.write_record(&columns.names)?;
+    let mut write_row = |row: Row| -> CliResult<()> {
+        let mut record = vec![String::new(); columns.names.len()];
+        for (name, value) in row {
+            record[columns.index[&name]] = value;
+        }
+        wtr.write_record(&record)?;
+        Ok(())
+    };
+    if rconfig.is_std() {
+        for row in buffered {
+            write_row(row)?;
+        }
+    } else {
+        args.rows(rconfig.io_reader()?, write_row)?;
+    }
+    Config::finish(wtr)?;
+    Ok(())
+}
+
+impl Args {
+    /// Call `f` with each object in the JSON data read from `rdr`, flattened
+    /// into a row.
+    fn rows<R, F>(&self, rdr: R, mut f: F) -> CliResult<()>
+            where R: io::Read, F: FnMut(Row) -> CliResult<()> {
+        let rdr = io::BufReader::new(rdr);
+        let values = serde_json::Deserializer::from_reader(rdr)
+                                              .into_iter::<Value>();
+        let mut nobjects = 0;
+        for value in values {
+            let value = value.map_err(|err| {
+                format!("could not parse JSON: {}", err)
+            })?;
+            let objects = match value {
+                Value::Array(values) => values,
+                value => vec![value],
+            };
+            for value in objects {
+                nobjects += 1;
+                match value {
+                    Value::Object(obj) => {
+                        let mut row = vec![];
+                        self.flatten("", obj, &mut row);
+                        let mut names = HashSet::new();
+                        for &(ref name, _) in &row {
+                            if !names.insert(name) {
+                                return fail!(format!(
+                                    "JSON value {} has more than one field \
+                                     named '{}' once flattened",
+                                    nobjects, name));
+                            }
+                        }
+                        f(row)?;
+                    }
+                    value => return fail!(format!(
+                        "JSON value {} is not an object: {}",
+                        nobjects, value)),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the fields of `obj` to `row`, prefixing each name with `prefix`.
+    /// An empty object is a single empty field.
+    fn flatten(&self, prefix: &str, obj: Map<String, Value>, row: &mut Row) {
+        for (key, value) in obj {
+            let name = format!("{}{}", prefix, key);
+            match value {
+                Value::Object(ref obj) if obj.is_empty() => {
+                    row.push((name, String::new()));
+                }
+                Value::Object(obj) => {
+                    self.flatten(&format!("{}.", name), obj, row);
+                }
+                value => row.push((name, self.show(value))),
+            }
+        }
+    }
+
+    fn show(&self, value: Value) -> String {
+        match value {
+            Value::Null => String::new(),
+            Value::String(s) => s,
+            Value::Array(values) => match self.flag_join_arrays {
+                None => Value::Array(values).to_string(),
+                Some(ref sep) => {
+                    let values: Vec<String> = values.into_iter()
+                                                    .map(|v| self.show(v))
+                                                    .collect();
+                    values.join(sep)
+                }
+            },
+            value => value.to_string(),
+        }
+    }
+}
+
+/// The names of every column seen so far, in the order they were first seen.
+#[derive(Default)]
+struct Columns {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+}
+
+impl Columns {
+    fn add(&mut self, name: &str) {
+        if !self.index.contains_key(name) {
+            self.index.insert(name.to_owned(), self.names.len());
+            self.names.push(name.to_owned());
+        }
+    }
+}
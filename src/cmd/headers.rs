@@ -29,6 +29,8 @@ Common options:
     -h, --help             Display this message
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 #[derive(Deserialize)]
@@ -37,12 +39,13 @@ struct Args {
     flag_just_names: bool,
     flag_intersect: bool,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
 }
 
 pub fn run(argv: &[&str]) -> CliResult<()> {
     let args: Args = util::get_args(USAGE, argv)?;
     let configs = util::many_configs(
-        &*args.arg_input, args.flag_delimiter, true)?;
+        &*args.arg_input, args.flag_delimiter, true, args.flag_sniff)?;
 
     let num_inputs = configs.len();
     let mut headers: Vec<Vec<u8>> = vec![];
@@ -0,0 +1,428 @@
+use std::cmp;
+use std::io;
+use std::sync::Arc;
+
+use csv;
+use stats::{Commute, MinMax, merge_all};
+
+use CliResult;
+use chunk::{self, RecordChunk};
+use config::{Config, Delimiter, Output};
+use index::IndexedFile;
+use select::{SelectColumns, Selection};
+use sketch::TDigest;
+use util;
+
+static USAGE: &'static str = "
+Compute histograms of the numeric columns in CSV data.
+
+The histograms are formatted as CSV data:
+
+    field,bin_start,bin_end,count
+
+By default, the range of each column is split into bins of equal width. Every
+bin includes its start but not its end, except for the last bin, which also
+includes the maximum value. Alternatively, --quantile-bins picks the bin edges
+so that each bin contains roughly the same number of values, which is more
+useful for skewed data. Empty and non-numeric values are ignored.
+
+Computing a histogram requires two passes over the data: one to find the
+range of each column and one to count the values in each bin. When reading
+from <stdin>, every number is therefore kept in memory. Computing histograms
+on a large file can be made much faster if you create an index for it first
+with 'xsv index'.
+
+Usage:
+    xsv hist [options] [<input>]
+
+hist options:
+    -s, --select <arg>     Select a subset of columns to compute histograms
+                           for. See 'xsv select --help' for the format
+                           details. This is provided here because piping 'xsv
+                           select' into 'xsv hist' will disable the use of
+                           indexing.
+    -b, --bins <arg>       The number of bins for each column. [default: 10]
+    --quantile-bins        Use estimated quantiles as bin edges, so that
+                           every bin has about the same count.
+    --bars                 Draw the histograms as bar charts instead of
+                           writing CSV data.
+    --width <arg>          The width, in characters, of the longest bar drawn
+                           by --bars. [default: 50]
+    -j, --jobs <arg>       The number of jobs to run in parallel.
+                           This works only when the given CSV data has
+                           an index already created. Note that a file handle
+                           is opened for each job.
+                           When set to '0', the number of jobs is set to the
+                           number of CPUs detected.
+                           [default: 0]
+
+Common options:
+    -h, --help             Display this message
+    -o, --output <file>    Write output to <file> instead of stdout.
+    -n, --no-headers       When set, the first row will NOT be interpreted
+                           as column names. i.e., It will be included in the
+                           histograms. Additionally, the 'field' column will
+                           be 1-based indices instead of header names.
+    -d, --delimiter <arg>  The field delimiter for reading CSV data.
+                           Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
+";
+
+#[derive(Clone, Deserialize)]
+struct Args {
+    arg_input: Option<String>,
+    flag_select: SelectColumns,
+    flag_bins: usize,
+    flag_quantile_bins: bool,
+    flag_bars: bool,
+    flag_width: usize,
+    flag_jobs: usize,
+    flag_output: Option<String>,
+    flag_no_headers: bool,
+    flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
+}
+
+pub fn run(argv: &[&str]) -> CliResult<()> {
+    let args: Args = util::get_args(USAGE, argv)?;
+    if args.flag_bins == 0 {
+        return fail_usage!("--bins must be greater than 0.");
+    }
+    let rconfig = args.rconfig();
+
+    let (headers, hists) = match rconfig.indexed()? {
+        Some(ref idx) if args.njobs() > 1 => args.parallel_hists(idx),
+        _ if rconfig.is_std() => args.buffered_hists(),
+        _ => args.sequential_hists(),
+    }?;
+
+    let mut headers: Vec<Vec<u8>> =
+        headers.iter().map(|h| h.to_vec()).collect();
+    if rconfig.no_headers {
+        for (i, header) in headers.iter_mut().enumerate() {
+            *header = (i+1).to_string().into_bytes();
+        }
+    }
+    let wtr = Config::new(&args.flag_output).io_writer()?;
+    if args.flag_bars {
+        args.write_bars(wtr, &headers, &hists)
+    } else {
+        args.write_csv(wtr, &headers, &hists)
+    }
+}
+
+type Headers = csv::ByteRecord;
+
+impl Args {
+    fn rconfig(&self) -> Config {
+        Config::new(&self.arg_input)
+            .delimiter(self.flag_delimiter)
+            .no_headers(self.flag_no_headers)
+            .select(self.flag_select.clone())
+            .sniff(self.flag_sniff)
+    }
+
+    fn njobs(&self) -> usize {
+        if self.flag_jobs == 0 { util::num_cpus() } else { self.flag_jobs }
+    }
+
+    fn sequential_hists(&self) -> CliResult<(Headers, Vec<Histogram>)> {
+        let mut rdr = self.rconfig().reader()?;
+        let (headers, sel) = self.sel_headers(&mut rdr)?;
+        let mut ranges = self.ranges(&sel, rdr.byte_records())?;
+        let edges = self.edges(&mut ranges);
+
+        let mut rdr = self.rconfig().reader()?;
+        let hists = self.histograms(&sel, &edges, rdr.byte_records())?;
+        Ok((headers, hists))
+    }
+
+    fn buffered_hists(&self) -> CliResult<(Headers, Vec<Histogram>)> {
+        let mut rdr = self.rconfig().reader()?;
+        let (headers, sel) = self.sel_headers(&mut rdr)?;
+        let mut columns = vec![vec![]; sel.len()];
+        for row in rdr.byte_records() {
+            let row = row?;
+            for (i, field) in sel.select(&row).enumerate() {
+                if let Some(n) = parse_number(field) {
+                    columns[i].push(n);
+                }
+            }
+        }
+        let mut ranges: Vec<Range> = columns.iter().map(|column| {
+            let mut range = self.new_range();
+            for &n in column {
+                range.add(n);
+            }
+            range
+        }).collect();
+        let edges = self.edges(&mut ranges);
+        let hists = columns.iter().zip(edges).map(|(column, edges)| {
+            let mut hist = Histogram::new(edges);
+            for &n in column {
+                hist.add(n);
+            }
+            hist
+        }).collect();
+        Ok((headers, hists))
+    }
+
+    fn parallel_hists(
+        &self,
+        idx: &IndexedFile,
+    ) -> CliResult<(Headers, Vec<Histogram>)> {
+        let mut rdr = self.rconfig().reader()?;
+        let (headers, sel) = self.sel_headers(&mut rdr)?;
+        if idx.count() == 0 {
+            return Ok((headers, vec![]));
+        }
+
+        let sel = Arc::new(sel);
+        let mut ranges = {
+            let sel = sel.clone();
+            self.parallel(idx, move |args, it| args.ranges(&sel, it))?
+        };
+        let edges = Arc::new(self.edges(&mut ranges));
+        let hists = self.parallel(idx, move |args, it| {
+            args.histograms(&sel, &edges, it)
+        })?;
+        Ok((headers, hists))
+    }
+
+    /// Run `f` on chunks of an indexed CSV file in parallel, and merge the
+    /// results.
+    fn parallel<T, F>(&self, idx: &IndexedFile, f: F) -> CliResult<T>
+            where T: Commute + Send + 'static,
+                  F: Fn(&Args, RecordChunk) -> CliResult<T>,
+                  F: Send + Sync + 'static {
+        let args = self.clone();
+        let results = chunk::map_indexed(
+            &self.rconfig(), idx, self.njobs(), move |it| f(&args, it))?;
+        Ok(merge_all(results.into_iter()).unwrap())
+    }
+
+    fn ranges<I>(&self, sel: &Selection, it: I) -> CliResult<Vec<Range>>
+            where I: Iterator<Item=csv::Result<csv::ByteRecord>> {
+        let mut ranges: Vec<_> = (0..sel.len()).map(|_| self.new_range())
+                                               .collect();
+        for row in it {
+            let row = row?;
+            for (i, field) in sel.select(&row).enumerate() {
+                if let Some(n) = parse_number(field) {
+                    ranges[i].add(n);
+                }
+            }
+        }
+        Ok(ranges)
+    }
+
+    fn histograms<I>(
+        &self,
+        sel: &Selection,
+        edges: &[Vec<f64>],
+        it: I,
+    ) -> CliResult<Vec<Histogram>>
+            where I: Iterator<Item=csv::Result<csv::ByteRecord>> {
+        let mut hists: Vec<_> = edges.iter()
+                                     .map(|e| Histogram::new(e.clone()))
+                                     .collect();
+        for row in it {
+            let row = row?;
+            for (i, field) in sel.select(&row).enumerate() {
+                if let Some(n) = parse_number(field) {
+                    hists[i].add(n);
+                }
+            }
+        }
+        Ok(hists)
+    }
+
+    fn new_range(&self) -> Range {
+        Range {
+            minmax: MinMax::new(),
+            digest: if self.flag_quantile_bins {
+                Some(TDigest::new(200.0))
+            } else {
+                None
+            },
+        }
+    }
+
+    fn edges(&self, ranges: &mut [Range]) -> Vec<Vec<f64>> {
+        ranges.iter_mut().map(|r| r.edges(self.flag_bins)).collect()
+    }
+
+    fn sel_headers<R: io::Read>(&self, rdr: &mut csv::Reader<R>)
+                  -> CliResult<(Headers, Selection)> {
+        let headers = rdr.byte_headers()?;
+        let sel = self.rconfig().selection(headers)?;
+        Ok((sel.select(headers).map(|h| h.to_vec()).collect(), sel))
+    }
+
+    fn write_csv<W: Output>(
+        &self,
+        wtr: W,
+        headers: &[Vec<u8>],
+        hists: &[Histogram],
+    ) -> CliResult<()> {
+        let mut wtr = Config::new(&None).from_writer(wtr);
+        wtr.write_record(vec!["field", "bin_start", "bin_end", "count"])?;
+        for (header, hist) in headers.iter().zip(hists) {
+            for (start, end, count) in hist.bins() {
+                wtr.write_record(vec![
+                    &**header,
+                    start.to_string().as_bytes(),
+                    end.to_string().as_bytes(),
+                    count.to_string().as_bytes(),
+                ])?;
+            }
+        }
+        Config::finish(wtr)?;
+        Ok(())
+    }
+
+    fn write_bars<W: Output>(
+        &self,
+        mut wtr: W,
+        headers: &[Vec<u8>],
+        hists: &[Histogram],
+    ) -> CliResult<()> {
+        let mut first = true;
+        for (header, hist) in headers.iter().zip(hists) {
+            if hist.counts.is_empty() {
+                continue;
+            }
+            if !first {
+                writeln!(wtr)?;
+            }
+            first = false;
+
+            let nbins = hist.counts.len();
+            let labels: Vec<String> = hist.bins().enumerate().map(|(i, b)| {
+                let close = if i + 1 == nbins { ']' } else { ')' };
+                format!("[{}, {}{}", b.0, b.1, close)
+            }).collect();
+            let label_width = labels.iter().map(|l| l.len()).max().unwrap();
+            let max_count = *hist.counts.iter().max().unwrap();
+            let count_width = max_count.to_string().len();
+
+            writeln!(wtr, "{}", String::from_utf8_lossy(header))?;
+            for (label, &count) in labels.iter().zip(&hist.counts) {
+                let bar = bar_length(count, max_count, self.flag_width);
+                writeln!(wtr, "  {:<lw$}  {:>cw$}  {}",
+                         label, count, "#".repeat(bar),
+                         lw = label_width, cw = count_width)?;
+            }
+        }
+        wtr.finish()?;
+        Ok(())
+    }
+}
+
+/// Range tracks the range of values in a column, which determines the edges
+/// of its histogram bins.
+#[derive(Clone)]
+struct Range {
+    minmax: MinMax<f64>,
+    /// Only present when bin edges are quantiles.
+    digest: Option<TDigest>,
+}
+
+impl Range {
+    fn add(&mut self, n: f64) {
+        self.minmax.add(n);
+        if let Some(ref mut digest) = self.digest {
+            digest.add(n);
+        }
+    }
+
+    /// Returns the edges of `nbins` bins spanning this range. Fewer bins are
+    /// returned if some of them would be empty intervals, and no edges are
+    /// returned if no values were seen.
+    fn edges(&mut self, nbins: usize) -> Vec<f64> {
+        let (min, max) = match (self.minmax.min(), self.minmax.max()) {
+            (Some(&min), Some(&max)) => (min, max),
+            _ => return vec![],
+        };
+        if min == max {
+            return vec![min, max];
+        }
+        let mut edges: Vec<f64> = match self.digest {
+            None => (0..nbins + 1).map(|i| {
+                min + (max - min) * (i as f64 / nbins as f64)
+            }).collect(),
+            Some(ref mut digest) => (0..nbins + 1).map(|i| {
+                digest.quantile(i as f64 / nbins as f64).unwrap()
+            }).collect(),
+        };
+        edges[0] = min;
+        edges[nbins] = max;
+        edges.dedup();
+        edges
+    }
+}
+
+impl Commute for Range {
+    fn merge(&mut self, other: Range) {
+        self.minmax.merge(other.minmax);
+        self.digest.merge(other.digest);
+    }
+}
+
+/// Histogram counts the values of a column that fall into each bin.
+///
+/// Bins are delimited by consecutive edges.
+#[derive(Clone)]
+struct Histogram {
+    edges: Vec<f64>,
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    fn new(edges: Vec<f64>) -> Histogram {
+        let nbins = edges.len().saturating_sub(1);
+        Histogram { edges, counts: vec![0; nbins] }
+    }
+
+    fn add(&mut self, n: f64) {
+        if self.counts.is_empty() {
+            return;
+        }
+        // Values are never smaller than the first edge (the minimum), and
+        // the maximum belongs to the last bin.
+        let last = self.counts.len() - 1;
+        let bin = self.edges[1..].iter().take_while(|&&e| e <= n).count();
+        self.counts[cmp::min(bin, last)] += 1;
+    }
+
+    fn bins<'a>(&'a self) -> impl Iterator<Item=(f64, f64, u64)> + 'a {
+        self.edges.windows(2)
+                  .zip(&self.counts)
+                  .map(|(e, &count)| (e[0], e[1], count))
+    }
+}
+
+impl Commute for Histogram {
+    fn merge(&mut self, other: Histogram) {
+        for (c1, c2) in self.counts.iter_mut().zip(other.counts) {
+            *c1 += c2;
+        }
+    }
+}
+
+fn parse_number(field: &[u8]) -> Option<f64> {
+    ::std::str::from_utf8(field).ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .and_then(|n| if n.is_finite() { Some(n) } else { None })
+}
+
+/// Returns the length of a bar for `count`, scaled so that `max_count` has
+/// length `width`. Non-zero counts always get a bar of at least length 1.
+fn bar_length(count: u64, max_count: u64, width: usize) -> usize {
+    if count == 0 || max_count == 0 {
+        return 0;
+    }
+    let len = (count as f64 / max_count as f64 * width as f64).round();
+    cmp::max(1, len as usize)
+}
@@ -2,12 +2,13 @@ use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 
-use csv_index::RandomAccessSimple;
-
 use CliResult;
 use config::{Config, Delimiter};
 use util;
 
+/// Compressed files larger than this are worth compressing in blocks.
+const SINGLE_BLOCK_WARN_LEN: u64 = 1 << 20;
+
 static USAGE: &'static str = "
 Creates an index of the given CSV data, which can make other operations like
 slicing, splitting and gathering statistics much faster.
@@ -18,6 +19,22 @@ automatically used by commands that can benefit from it. If the original CSV
 data changes after the index is made, commands that try to use it will result
 in an error (you have to regenerate the index before it can be used again).
 
+To tell whether the CSV data changed, the index records its size and a hash of
+blocks sampled from it (so the index stays valid when the file is copied with
+a new modification time). The index also records the delimiter and quote
+character it was created with, and can't be used with others.
+
+Files compressed with gzip or zstd can be indexed too, so that commands like
+slice, sample and split can read them from any record. This is only fast if
+the file is compressed in blocks that can be decompressed on their own, as
+'bgzip' or 'pzstd' do, since reading a record means decompressing the block it
+is in from its start. Other compression formats can't be indexed.
+
+An index normally has 8 bytes for every record. With --sparse <n>, it only
+has the offset of every <n>th record, in 4 bytes, which makes it about 2*n
+times smaller. Finding a record then means parsing up to n-1 records before
+it, so a small <n> (e.g., 64) keeps seeking fast.
+
 Usage:
     xsv index [options] <input>
     xsv index --help
@@ -27,18 +44,28 @@ index options:
                            Generally, this is not currently useful because
                            the only way to use an index is if it is specially
                            named <input>.idx.
+    --check                Check that the index of <input> is up to date
+                           instead of creating it. If it isn't, xsv exits
+                           with status 5.
+    --sparse <n>           Create a sparse index, with the offset of one
+                           record out of every <n>.
 
 Common options:
     -h, --help             Display this message
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 #[derive(Deserialize)]
 struct Args {
     arg_input: String,
     flag_output: Option<String>,
+    flag_check: bool,
+    flag_sparse: Option<u32>,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
 }
 
 pub fn run(argv: &[&str]) -> CliResult<()> {
@@ -49,10 +76,63 @@ pub fn run(argv: &[&str]) -> CliResult<()> {
         Some(p) => PathBuf::from(&p),
     };
 
-    let rconfig = Config::new(&Some(args.arg_input))
-                         .delimiter(args.flag_delimiter);
-    let mut rdr = rconfig.reader_file()?;
-    let mut wtr = io::BufWriter::new(fs::File::create(&pidx)?);
-    RandomAccessSimple::create(&mut rdr, &mut wtr)?;
+    let rconfig = Config::new(&Some(args.arg_input.clone()))
+                         .delimiter(args.flag_delimiter)
+                         .sniff(args.flag_sniff);
+    let mut data = fs::File::open(&args.arg_input)?;
+    if args.flag_check {
+        let mut idx = fs::File::open(&pidx)?;
+        return rconfig.check_index(&mut data, &mut idx).map(|_| ());
+    }
+    if args.flag_sparse == Some(0) {
+        return fail_usage!("--sparse must be greater than 0");
+    }
+    let mut header = rconfig.index_header(&mut data)?
+                            .sparse(args.flag_sparse);
+    let compression = match header.compression() {
+        None => {
+            let mut rdr = rconfig.reader_file()?;
+            return write_index(&pidx, |wtr| {
+                header.write(&mut *wtr)?;
+                header.write_offsets(&mut rdr, wtr)
+            });
+        }
+        Some(c) => c,
+    };
+    let mut rdr = rconfig.reader_blocks(compression, data)?;
+    let mut blocks = vec![];
+    write_index(&pidx, |wtr| {
+        header.write(&mut *wtr)?;
+        header.write_offsets(&mut rdr, &mut *wtr)?;
+        blocks = rdr.get_ref().blocks().to_vec();
+        Ok(header.write_blocks(wtr, &blocks)?)
+    })?;
+    let path = Path::new(&args.arg_input);
+    if blocks.len() == 1 && path.metadata()?.len() > SINGLE_BLOCK_WARN_LEN {
+        werr!("Warning: {} is compressed in a single block, so reading a \
+               record from it means decompressing everything before it. \
+               Compress it in blocks (e.g., with bgzip or pzstd) for faster \
+               access.", path.display());
+    }
     Ok(())
 }
+
+/// Write an index to `path` with `write`. It is written to a temporary file
+/// that only replaces `path` once it is complete, so that a command that
+/// fails doesn't leave a truncated index behind.
+fn write_index<F>(path: &Path, write: F) -> CliResult<()>
+        where F: FnOnce(&mut io::BufWriter<fs::File>) -> CliResult<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut wtr = io::BufWriter::new(fs::File::create(&tmp)?);
+    let res = write(&mut wtr).and_then(|()| {
+        wtr.into_inner().map_err(io::Error::from)?;
+        Ok(())
+    });
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp);
+        return res;
+    }
+    Ok(fs::rename(&tmp, path)?)
+}
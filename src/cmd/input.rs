@@ -59,6 +59,6 @@ pub fn run(argv: &[&str]) -> CliResult<()> {
     while rdr.read_byte_record(&mut row)? {
         wtr.write_record(&row)?;
     }
-    wtr.flush()?;
+    Config::finish(wtr)?;
     Ok(())
 }
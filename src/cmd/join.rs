@@ -9,7 +9,7 @@ use byteorder::{WriteBytesExt, BigEndian};
 use csv;
 
 use CliResult;
-use config::{Config, Delimiter};
+use config::{Config, Delimiter, Output};
 use index::Indexed;
 use select::{SelectColumns, Selection};
 use util;
@@ -69,6 +69,8 @@ Common options:
                            sliced, etc.)
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 type ByteString = Vec<u8>;
@@ -88,6 +90,7 @@ struct Args {
     flag_no_case: bool,
     flag_nulls: bool,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
 }
 
 pub fn run(argv: &[&str]) -> CliResult<()> {
@@ -119,11 +122,11 @@ pub fn run(argv: &[&str]) -> CliResult<()> {
             state.write_headers()?;
             state.inner_join()
         }
-        _ => fail!("Please pick exactly one join operation.")
+        _ => fail_usage!("Please pick exactly one join operation.")
     }
 }
 
-struct IoState<R, W: io::Write> {
+struct IoState<R, W: Output> {
     wtr: csv::Writer<W>,
     rdr1: csv::Reader<R>,
     sel1: Selection,
@@ -134,7 +137,7 @@ struct IoState<R, W: io::Write> {
     nulls: bool,
 }
 
-impl<R: io::Read + io::Seek, W: io::Write> IoState<R, W> {
+impl<R: io::Read + io::Seek, W: Output> IoState<R, W> {
     fn write_headers(&mut self) -> CliResult<()> {
         if !self.no_headers {
             let mut headers = self.rdr1.byte_headers()?.clone();
@@ -164,7 +167,7 @@ impl<R: io::Read + io::Seek, W: io::Write> IoState<R, W> {
                 }
             }
         }
-        Ok(())
+        Ok(Config::finish(self.wtr)?)
     }
 
     fn outer_join(mut self, right: bool) -> CliResult<()> {
@@ -202,7 +205,7 @@ impl<R: io::Read + io::Seek, W: io::Write> IoState<R, W> {
                 }
             }
         }
-        Ok(())
+        Ok(Config::finish(self.wtr)?)
     }
 
     fn full_outer_join(mut self) -> CliResult<()> {
@@ -242,7 +245,7 @@ impl<R: io::Read + io::Seek, W: io::Write> IoState<R, W> {
                 self.wtr.write_record(pad1.iter().chain(&scratch))?;
             }
         }
-        Ok(())
+        Ok(Config::finish(self.wtr)?)
     }
 
     fn cross_join(mut self) -> CliResult<()> {
@@ -261,7 +264,7 @@ impl<R: io::Read + io::Seek, W: io::Write> IoState<R, W> {
                 self.wtr.write_record(row1.iter().chain(&row2))?;
             }
         }
-        Ok(())
+        Ok(Config::finish(self.wtr)?)
     }
 
     fn get_padding(
@@ -278,22 +281,26 @@ impl<R: io::Read + io::Seek, W: io::Write> IoState<R, W> {
 
 impl Args {
     fn new_io_state(&self)
-        -> CliResult<IoState<fs::File, Box<io::Write+'static>>> {
+        -> CliResult<IoState<fs::File, Box<Output+'static>>> {
         let rconf1 = Config::new(&Some(self.arg_input1.clone()))
             .delimiter(self.flag_delimiter)
             .no_headers(self.flag_no_headers)
-            .select(self.arg_columns1.clone());
+            .select(self.arg_columns1.clone())
+            .sniff(self.flag_sniff);
         let rconf2 = Config::new(&Some(self.arg_input2.clone()))
             .delimiter(self.flag_delimiter)
             .no_headers(self.flag_no_headers)
-            .select(self.arg_columns2.clone());
+            .select(self.arg_columns2.clone())
+            .sniff(self.flag_sniff);
 
         let mut rdr1 = rconf1.reader_file()?;
         let mut rdr2 = rconf2.reader_file()?;
         let (sel1, sel2) = self.get_selections(
             &rconf1, &mut rdr1, &rconf2, &mut rdr2)?;
         Ok(IoState {
-            wtr: Config::new(&self.flag_output).writer()?,
+            wtr: Config::new(&self.flag_output)
+                .no_headers(self.flag_no_headers)
+                .writer()?,
             rdr1: rdr1,
             sel1: sel1,
             rdr2: rdr2,
@@ -314,7 +321,7 @@ impl Args {
         let select1 = rconf1.selection(&*headers1)?;
         let select2 = rconf2.selection(&*headers2)?;
         if select1.len() != select2.len() {
-            return fail!(format!(
+            return fail_usage!(format!(
                 "Column selections must have the same number of columns, \
                  but found column selections with {} and {} columns.",
                 select1.len(), select2.len()));
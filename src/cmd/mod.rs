@@ -1,10 +1,15 @@
 pub mod cat;
 pub mod count;
+pub mod crosstab;
 pub mod fixlengths;
 pub mod flatten;
 pub mod fmt;
+pub mod from;
+pub mod fromfixed;
+pub mod fromjson;
 pub mod frequency;
 pub mod headers;
+pub mod hist;
 pub mod index;
 pub mod input;
 pub mod join;
@@ -13,7 +18,11 @@ pub mod sample;
 pub mod search;
 pub mod select;
 pub mod slice;
+pub mod sniff;
 pub mod sort;
 pub mod split;
 pub mod stats;
 pub mod table;
+pub mod to;
+pub mod tofixed;
+pub mod tosqlite;
@@ -1,14 +1,14 @@
-use std::collections::{HashMap, HashSet};
-use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use csv;
 use regex::Regex;
 
 use CliResult;
-use config::{Config, Delimiter};
+use config::{Config, Delimiter, Output};
+use json::OutputFormat;
 use select::SelectColumns;
 use util::{self, FilenameTemplate};
 
@@ -18,6 +18,20 @@ Partitions the given CSV data into chunks based on the value of a column
 The files are written to the output directory with filenames based on the
 values in the partition column and the `--filename` flag.
 
+Several partition columns may be selected (e.g., 'year,month'), in which case
+their values are nested directories, e.g., '2024/05.csv'. With --hive, each
+directory is instead named '<column>=<value>', as in Hive-style layouts, e.g.,
+'year=2024/month=05/part.csv'. Hive names are not sanitized, but characters
+that aren't safe in a path (and bytes that aren't valid UTF-8) are escaped as
+'%XX', and an empty value is named __HIVE_DEFAULT_PARTITION__.
+
+Each partition's file is kept open until the end, which may exceed the limit
+on open files when there are many partitions. --max-open-files bounds the
+number of open files: when the limit is reached, the least recently used file
+is closed, and reopened later to add more rows to it. (A compressed file, such
+as '{}.csv.gz', then has several compressed streams, which are read as one.)
+This can't be used with JSON output (see --output-format in 'xsv --help').
+
 Usage:
     xsv partition [options] <column> <outdir> [<input>]
     xsv partition --help
@@ -27,10 +41,20 @@ partition options:
                            the names of the output files.  The string '{}'
                            will be replaced by a value based on the value
                            of the field, but sanitized for shell safety.
-                           [default: {}.csv]
+                           '{seq}' may be used instead, for the number of
+                           the file (in the order they are created), and
+                           either one may be padded with zeros, as in
+                           '{:04}' or '{seq:04}'.
+                           (default: {}.csv, or {}/part.csv with --hive)
     -p, --prefix-length <n>  Truncate the partition column after the
                            specified number of bytes when creating the
                            output file.
+    --hive                 Name the directories of the partitions
+                           '<column>=<value>'. This requires a header row.
+    --drop-key             Remove the partition columns from the rows that
+                           are written.
+    --max-open-files <n>   The maximum number of files to keep open at the
+                           same time. By default, there is no limit.
 
 Common options:
     -h, --help             Display this message
@@ -39,6 +63,8 @@ Common options:
                            appear in all chunks as the header row.
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 #[derive(Clone, Deserialize)]
@@ -46,14 +72,29 @@ struct Args {
     arg_column: SelectColumns,
     arg_input: Option<String>,
     arg_outdir: String,
-    flag_filename: FilenameTemplate,
+    flag_filename: Option<FilenameTemplate>,
     flag_prefix_length: Option<usize>,
+    flag_hive: bool,
+    flag_drop_key: bool,
+    flag_max_open_files: Option<usize>,
     flag_no_headers: bool,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
 }
 
 pub fn run(argv: &[&str]) -> CliResult<()> {
     let args: Args = util::get_args(USAGE, argv)?;
+    if args.flag_max_open_files == Some(0) {
+        return fail_usage!("--max-open-files must be greater than 0");
+    }
+    // A JSON file can't be continued once it has been closed, so this is
+    // checked before anything is written.
+    if args.flag_max_open_files.is_some() {
+        if let OutputFormat::Json { .. } = Config::new(&None).output_format()? {
+            return fail_usage!("--max-open-files can't be used with JSON \
+                                output");
+        }
+    }
     fs::create_dir_all(&args.arg_outdir)?;
 
     // It would be nice to support efficient parallel partitions, but doing
@@ -70,19 +111,28 @@ impl Args {
             .delimiter(self.flag_delimiter)
             .no_headers(self.flag_no_headers)
             .select(self.arg_column.clone())
+            .sniff(self.flag_sniff)
     }
 
-    /// Get the column to use as a key.
-    fn key_column(
+    /// Get the columns to use as a key.
+    fn key_columns(
         &self,
         rconfig: &Config,
         headers: &csv::ByteRecord,
-    ) -> CliResult<usize> {
+    ) -> CliResult<Vec<usize>> {
         let select_cols = rconfig.selection(headers)?;
-        if select_cols.len() == 1 {
-            Ok(select_cols[0])
-        } else {
-            fail!("can only partition on one column")
+        Ok(select_cols.iter().cloned().collect())
+    }
+
+    /// The filename template, whose default depends on `--hive`.
+    fn template(&self) -> FilenameTemplate {
+        match self.flag_filename {
+            Some(ref template) => template.clone(),
+            None => {
+                let default =
+                    if self.flag_hive { "{}/part.csv" } else { "{}.csv" };
+                FilenameTemplate::parse(default).unwrap()
+            }
         }
     }
 
@@ -91,79 +141,210 @@ impl Args {
         let rconfig = self.rconfig();
         let mut rdr = rconfig.reader()?;
         let headers = rdr.byte_headers()?.clone();
-        let key_col = self.key_column(&rconfig, &headers)?;
-        let mut gen = WriterGenerator::new(self.flag_filename.clone());
+        let key_cols = self.key_columns(&rconfig, &headers)?;
+        let names = if !self.flag_hive {
+            None
+        } else if rconfig.no_headers {
+            return fail_usage!("--hive requires a header row to name the \
+                                directories of the partitions");
+        } else {
+            Some(key_cols.iter().map(|&i| headers[i].to_vec()).collect())
+        };
+        let mut gen = WriterGenerator::new(self.template(), names);
 
-        let mut writers: HashMap<Vec<u8>, BoxedWriter> =
-            HashMap::new();
+        // The columns that are written.
+        let keep: Vec<usize> = (0..headers.len())
+            .filter(|i| !self.flag_drop_key || !key_cols.contains(i))
+            .collect();
+        let out_headers = project(&headers, &keep);
+
+        let mut writers =
+            Writers::new(self.flag_max_open_files, self.flag_no_headers);
         let mut row = csv::ByteRecord::new();
         while rdr.read_byte_record(&mut row)? {
             // Decide what file to put this in.
-            let column = &row[key_col];
-            let key = match self.flag_prefix_length {
-                // We exceed --prefix-length, so ignore the extra bytes.
-                Some(len) if len < column.len() => &column[0..len],
-                _ => &column[..],
-            };
-            let mut entry = writers.entry(key.to_vec());
-            let wtr = match entry {
-                Entry::Occupied(ref mut occupied) => occupied.get_mut(),
-                Entry::Vacant(vacant) => {
-                    // We have a new key, so make a new writer.
-                    let mut wtr = gen.writer(&*self.arg_outdir, key)?;
-                    if !rconfig.no_headers {
-                        wtr.write_record(&headers)?;
-                    }
-                    vacant.insert(wtr)
+            let key: Vec<Vec<u8>> = key_cols.iter().map(|&i| {
+                let column = &row[i];
+                match self.flag_prefix_length {
+                    // We exceed --prefix-length, so ignore the extra bytes.
+                    Some(len) if len < column.len() => column[0..len].to_vec(),
+                    _ => column.to_vec(),
+                }
+            }).collect();
+            let wtr = if writers.contains(&key) {
+                writers.get(&key)?
+            } else {
+                // We have a new key, so make a new writer.
+                let path = gen.path(&*self.arg_outdir, &key)?;
+                let wtr = writers.create(key, path)?;
+                if !rconfig.no_headers {
+                    wtr.write_record(&out_headers)?;
                 }
+                wtr
             };
-            wtr.write_byte_record(&row)?;
+            if self.flag_drop_key {
+                wtr.write_byte_record(&project(&row, &keep))?;
+            } else {
+                wtr.write_byte_record(&row)?;
+            }
         }
+        writers.finish()?;
         Ok(())
     }
 }
 
-type BoxedWriter = csv::Writer<Box<io::Write+'static>>;
+/// The fields of `record` at the indices `cols`.
+fn project(record: &csv::ByteRecord, cols: &[usize]) -> csv::ByteRecord {
+    cols.iter().map(|&i| &record[i]).collect()
+}
+
+type BoxedWriter = csv::Writer<Box<Output+'static>>;
+
+/// The writers of the partitions, of which at most `max_open` are open at
+/// the same time. A closed writer is reopened in append mode.
+struct Writers {
+    max_open: Option<usize>,
+    /// Whether the rows that are written have no header row.
+    no_headers: bool,
+    /// The file of each partition, whether its writer is open or not.
+    paths: HashMap<Vec<Vec<u8>>, PathBuf>,
+    /// The open writers, with the time each one was last used.
+    open: HashMap<Vec<Vec<u8>>, (BoxedWriter, u64)>,
+    /// The keys of the open writers, by the time they were last used. This
+    /// is only kept up to date when the number of writers is bounded.
+    used: BTreeMap<u64, Vec<Vec<u8>>>,
+    /// The number of times a writer has been used so far.
+    time: u64,
+}
+
+impl Writers {
+    fn new(max_open: Option<usize>, no_headers: bool) -> Writers {
+        Writers {
+            max_open: max_open,
+            no_headers: no_headers,
+            paths: HashMap::new(),
+            open: HashMap::new(),
+            used: BTreeMap::new(),
+            time: 0,
+        }
+    }
+
+    /// Whether the file of the partition `key` has been created.
+    fn contains(&self, key: &[Vec<u8>]) -> bool {
+        self.paths.contains_key(key)
+    }
+
+    /// Create the file of the new partition `key` at `path`, and return
+    /// its writer.
+    fn create(
+        &mut self,
+        key: Vec<Vec<u8>>,
+        path: PathBuf,
+    ) -> io::Result<&mut BoxedWriter> {
+        self.paths.insert(key.clone(), path);
+        self.open(&key, false)?;
+        Ok(&mut self.open.get_mut(&key).unwrap().0)
+    }
+
+    /// The writer of the partition `key`, whose file has been created.
+    fn get(&mut self, key: &[Vec<u8>]) -> io::Result<&mut BoxedWriter> {
+        if !self.open.contains_key(key) {
+            self.open(key, true)?;
+        } else if self.max_open.is_some() {
+            self.time += 1;
+            let last = &mut self.open.get_mut(key).unwrap().1;
+            let key = self.used.remove(last).unwrap();
+            *last = self.time;
+            self.used.insert(self.time, key);
+        }
+        Ok(&mut self.open.get_mut(key).unwrap().0)
+    }
+
+    /// Open the writer of `key`, after closing the least recently used
+    /// writer if there are too many open.
+    fn open(&mut self, key: &[Vec<u8>], append: bool) -> io::Result<()> {
+        if Some(self.open.len()) == self.max_open {
+            let (_, old) = self.used.pop_first().unwrap();
+            let (wtr, _) = self.open.remove(&old).unwrap();
+            Config::finish(wtr)?;
+        }
+        let path = Some(self.paths[key].display().to_string());
+        let wtr = Config::new(&path)
+            .no_headers(self.no_headers)
+            .append(append)
+            .writer()?;
+        self.time += 1;
+        if self.max_open.is_some() {
+            self.used.insert(self.time, key.to_vec());
+        }
+        self.open.insert(key.to_vec(), (wtr, self.time));
+        Ok(())
+    }
+
+    /// Finish the output of every writer that is still open.
+    fn finish(self) -> io::Result<()> {
+        for (_, (wtr, _)) in self.open {
+            Config::finish(wtr)?;
+        }
+        Ok(())
+    }
+}
 
 /// Generates unique filenames based on CSV values.
 struct WriterGenerator {
     template: FilenameTemplate,
+    /// The names of the key columns, if partitions are named Hive-style.
+    hive: Option<Vec<Vec<u8>>>,
     counter: usize,
     used: HashSet<String>,
+    /// The number of writers created so far.
+    nfiles: usize,
     non_word_char: Regex,
 }
 
 impl WriterGenerator {
-    fn new(template: FilenameTemplate) -> WriterGenerator {
+    fn new(
+        template: FilenameTemplate,
+        hive: Option<Vec<Vec<u8>>>,
+    ) -> WriterGenerator {
         WriterGenerator {
             template: template,
+            hive: hive,
             counter: 1,
             used: HashSet::new(),
+            nfiles: 0,
             non_word_char: Regex::new(r"\W").unwrap(),
         }
     }
 
-    /// Create a CSV writer for `key`.  Does not add headers.
-    fn writer<P>(&mut self, path: P, key: &[u8]) -> io::Result<BoxedWriter>
+    /// Generate the path of the file for `key`, which has a value for
+    /// each key column, in directory `path`.
+    fn path<P>(&mut self, path: P, key: &[Vec<u8>]) -> io::Result<PathBuf>
         where P: AsRef<Path>
     {
-        let unique_value = self.unique_value(key);
-        self.template.writer(path.as_ref(), &unique_value)
+        let unique_value = match self.hive {
+            Some(ref names) => hive_value(names, key),
+            None => self.unique_value(key),
+        };
+        self.nfiles += 1;
+        self.template.path(path.as_ref(), &unique_value, self.nfiles - 1)
     }
 
     /// Generate a unique value for `key`, suitable for use in a
-    /// "shell-safe" filename.  If you pass `key` twice, you'll get two
-    /// different values.
-    fn unique_value(&mut self, key: &[u8]) -> String {
+    /// "shell-safe" filename (with a directory for each value but the
+    /// last).  If you pass `key` twice, you'll get two different values.
+    fn unique_value(&mut self, key: &[Vec<u8>]) -> String {
         // Sanitize our key.
-        let utf8 = String::from_utf8_lossy(key);
-        let safe = self.non_word_char.replace_all(&*utf8, "").into_owned();
-        let base =
+        let parts: Vec<String> = key.iter().map(|value| {
+            let utf8 = String::from_utf8_lossy(value);
+            let safe = self.non_word_char.replace_all(&*utf8, "").into_owned();
             if safe.is_empty() {
                 "empty".to_owned()
             } else {
                 safe
-            };
+            }
+        }).collect();
+        let base = parts.join("/");
 
         // Now check for collisions.
         if !self.used.contains(&base) {
@@ -186,3 +367,42 @@ impl WriterGenerator {
         }
     }
 }
+
+/// The Hive-style name of the partition of `key`, with a `name=value`
+/// directory for each key column. Since names and values are escaped (in
+/// the same way as Hive does), different keys always have different names.
+fn hive_value(names: &[Vec<u8>], key: &[Vec<u8>]) -> String {
+    let parts: Vec<String> = names.iter().zip(key).map(|(name, value)| {
+        let value = if value.is_empty() {
+            "__HIVE_DEFAULT_PARTITION__".to_owned()
+        } else {
+            hive_escape(value)
+        };
+        format!("{}={}", hive_escape(name), value)
+    }).collect();
+    parts.join("/")
+}
+
+/// Escape the characters of a Hive partition name that aren't safe in a
+/// path as `%XX`. Bytes that aren't valid UTF-8 are escaped too, so that
+/// they keep distinct names.
+fn hive_escape(s: &[u8]) -> String {
+    let mut escaped = String::new();
+    for chunk in s.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\x00'..='\x1f' | '\x7f' | '"' | '#' | '%' | '\'' | '*'
+                | '/' | ':' | '=' | '?' | '\\' | '{' | '[' | ']' | '^' => {
+                    escaped.push_str(&format!("%{:02X}", c as u32));
+                }
+                // A name that is all dots would be a special directory.
+                '.' if escaped.is_empty() => escaped.push_str("%2E"),
+                c => escaped.push(c),
+            }
+        }
+        for b in chunk.invalid() {
+            escaped.push_str(&format!("%{:02X}", b));
+        }
+    }
+    escaped
+}
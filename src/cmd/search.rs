@@ -24,6 +24,14 @@ search options:
     -s, --select <arg>     Select the columns to search. See 'xsv select -h'
                            for the full syntax.
     -v, --invert-match     Select only rows that did not match
+    -j, --jobs <arg>       The number of jobs to run in parallel when the
+                           input is a large file. The file is split into
+                           byte ranges that are searched in parallel, and
+                           matching rows are written in their original
+                           order.
+                           When set to '0', the number of jobs is set to the
+                           number of CPUs detected.
+                           [default: 0]
 
 Common options:
     -h, --help             Display this message
@@ -33,6 +41,8 @@ Common options:
                            sliced, etc.)
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 #[derive(Deserialize)]
@@ -43,10 +53,16 @@ struct Args {
     flag_output: Option<String>,
     flag_no_headers: bool,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
     flag_invert_match: bool,
     flag_ignore_case: bool,
+    flag_jobs: usize,
 }
 
+/// The length of the byte ranges searched in parallel, which bounds the
+/// memory used by matching rows that wait to be written.
+const CHUNK_LEN: u64 = 16 * (1<<20);
+
 pub fn run(argv: &[&str]) -> CliResult<()> {
     let args: Args = util::get_args(USAGE, argv)?;
     let pattern = RegexBuilder::new(&*args.arg_regex)
@@ -55,10 +71,13 @@ pub fn run(argv: &[&str]) -> CliResult<()> {
     let rconfig = Config::new(&args.arg_input)
         .delimiter(args.flag_delimiter)
         .no_headers(args.flag_no_headers)
-        .select(args.flag_select);
+        .select(args.flag_select)
+        .sniff(args.flag_sniff);
 
     let mut rdr = rconfig.reader()?;
-    let mut wtr = Config::new(&args.flag_output).writer()?;
+    let mut wtr = Config::new(&args.flag_output)
+        .no_headers(args.flag_no_headers)
+        .writer()?;
 
     let headers = rdr.byte_headers()?.clone();
     let sel = rconfig.selection(&headers)?;
@@ -66,15 +85,44 @@ pub fn run(argv: &[&str]) -> CliResult<()> {
     if !rconfig.no_headers {
         wtr.write_record(&headers)?;
     }
-    let mut record = csv::ByteRecord::new();
-    while rdr.read_byte_record(&mut record)? {
-        let mut m = sel.select(&record).any(|f| pattern.is_match(f));
-        if args.flag_invert_match {
-            m = !m;
+    let invert = args.flag_invert_match;
+    let njobs = if args.flag_jobs == 0 {
+        util::num_cpus()
+    } else {
+        args.flag_jobs
+    };
+    match rconfig.chunked(njobs)? {
+        Some(chunked) => {
+            let mut chunked = chunked.max_chunk_len(CHUNK_LEN);
+            chunked.for_each(move |it| {
+                let mut matches = vec![];
+                for record in it {
+                    let record = record?;
+                    let m = sel.select(&record).any(|f| pattern.is_match(f));
+                    if m != invert {
+                        matches.push(record);
+                    }
+                }
+                Ok(matches)
+            }, |matches| {
+                for record in matches {
+                    wtr.write_byte_record(&record)?;
+                }
+                Ok(())
+            })?;
         }
-        if m {
-            wtr.write_byte_record(&record)?;
+        None => {
+            let mut record = csv::ByteRecord::new();
+            while rdr.read_byte_record(&mut record)? {
+                let mut m = sel.select(&record).any(|f| pattern.is_match(f));
+                if invert {
+                    m = !m;
+                }
+                if m {
+                    wtr.write_byte_record(&record)?;
+                }
+            }
         }
     }
-    Ok(wtr.flush()?)
+    Ok(Config::finish(wtr)?)
 }
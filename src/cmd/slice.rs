@@ -1,9 +1,6 @@
-use std::fs;
-
-
 use CliResult;
 use config::{Config, Delimiter};
-use index::Indexed;
+use index::IndexedFile;
 use util;
 
 static USAGE: &'static str = "
@@ -39,6 +36,8 @@ Common options:
                            appear in the output as the header row.
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 #[derive(Deserialize)]
@@ -51,6 +50,7 @@ struct Args {
     flag_output: Option<String>,
     flag_no_headers: bool,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
 }
 
 pub fn run(argv: &[&str]) -> CliResult<()> {
@@ -71,12 +71,12 @@ impl Args {
         for r in rdr.byte_records().skip(start).take(end - start) {
             wtr.write_byte_record(&r?)?;
         }
-        Ok(wtr.flush()?)
+        Ok(Config::finish(wtr)?)
     }
 
     fn with_index(
         &self,
-        mut idx: Indexed<fs::File, fs::File>,
+        mut idx: IndexedFile,
     ) -> CliResult<()> {
         let mut wtr = self.wconfig().writer()?;
         self.rconfig().write_headers(&mut *idx, &mut wtr)?;
@@ -89,7 +89,7 @@ impl Args {
         for r in idx.byte_records().take(end - start) {
             wtr.write_byte_record(&r?)?;
         }
-        wtr.flush()?;
+        Config::finish(wtr)?;
         Ok(())
     }
 
@@ -102,9 +102,10 @@ impl Args {
         Config::new(&self.arg_input)
             .delimiter(self.flag_delimiter)
             .no_headers(self.flag_no_headers)
+            .sniff(self.flag_sniff)
     }
 
     fn wconfig(&self) -> Config {
-        Config::new(&self.flag_output)
+        Config::new(&self.flag_output).no_headers(self.flag_no_headers)
     }
 }
@@ -0,0 +1,97 @@
+use std::io::Read;
+
+use serde_json;
+
+use CliResult;
+use config::Config;
+use sniff::{self, Dialect};
+use util;
+
+static USAGE: &'static str = "
+Infer the dialect of CSV data: its delimiter, quote character, how quotes are
+escaped, whether it has a header row and its line terminator.
+
+Only the start of the data is read. It is parsed with each candidate
+delimiter (comma, tab, semicolon, pipe and colon) and quote character (double
+and single quote), and the one whose rows most consistently have the same
+number of fields is chosen. A header row is assumed, unless the types of the
+fields in the first row look like the rest of their columns.
+
+Other commands infer the dialect of their input in the same way when given
+their --sniff option.
+
+Usage:
+    xsv sniff [options] [<input>]
+    xsv sniff --help
+
+sniff options:
+    --json                 Write the dialect as a JSON object.
+    --sample-size <arg>    The number of kilobytes to read from the start of
+                           the input. [default: 64]
+
+Common options:
+    -h, --help             Display this message
+";
+
+#[derive(Deserialize)]
+struct Args {
+    arg_input: Option<String>,
+    flag_json: bool,
+    flag_sample_size: usize,
+}
+
+#[derive(Serialize)]
+struct JsonDialect {
+    delimiter: String,
+    quote: String,
+    escape: Option<String>,
+    has_headers: bool,
+    terminator: &'static str,
+}
+
+pub fn run(argv: &[&str]) -> CliResult<()> {
+    let args: Args = util::get_args(USAGE, argv)?;
+    if args.flag_sample_size == 0 {
+        return fail_usage!("--sample-size must be greater than 0");
+    }
+    let len = args.flag_sample_size * (1<<10);
+    let rdr = Config::new(&args.arg_input).io_reader()?;
+    let mut sample = vec![];
+    rdr.take(len as u64).read_to_end(&mut sample)?;
+    let dialect = sniff::sniff(&sample, sample.len() == len);
+
+    if args.flag_json {
+        let json = JsonDialect {
+            delimiter: char_string(dialect.delimiter),
+            quote: char_string(dialect.quote),
+            escape: dialect.escape.map(char_string),
+            has_headers: dialect.has_headers,
+            terminator: dialect.terminator.as_str(),
+        };
+        wout!("{}", serde_json::to_string(&json).unwrap());
+    } else {
+        write_human(&dialect);
+    }
+    Ok(())
+}
+
+fn write_human(dialect: &Dialect) {
+    let delimiter = match dialect.delimiter {
+        b'\t' => r"\t".to_owned(),
+        d => char_string(d),
+    };
+    let escape = match dialect.escape {
+        None => "quotes are doubled".to_owned(),
+        Some(e) => char_string(e),
+    };
+    wout!("Delimiter:       {}", delimiter);
+    wout!("Quote:           {}", char_string(dialect.quote));
+    wout!("Escape:          {}", escape);
+    let headers = if dialect.has_headers { "yes" } else { "no" };
+    wout!("Header row:      {}", headers);
+    wout!("Line terminator: {}", dialect.terminator);
+}
+
+fn char_string(b: u8) -> String {
+    (b as char).to_string()
+}
@@ -1,14 +1,19 @@
+use std::cell::Cell;
 use std::fs;
 use std::io;
 use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
 
 use chan;
 use csv;
 use threadpool::ThreadPool;
 
 use CliResult;
-use config::{Config, Delimiter};
-use index::Indexed;
+use chunk::RecordChunk;
+use compress::Compression;
+use config::{Config, Delimiter, Output};
+use index::{IndexHeader, IndexedFile};
 use util::{self, FilenameTemplate};
 
 static USAGE: &'static str = "
@@ -17,6 +22,12 @@ Splits the given CSV data into chunks.
 The files are written to the directory given with the name '{start}.csv',
 where {start} is the index of the first record of the chunk (starting at 0).
 
+Chunks have --size records each by default. With --bytes, each chunk instead
+has as many records as fit in the given number of bytes (including its header
+row), and with --chunks, the records are split into exactly that many chunks
+with about as many records each. (Counting the records for --chunks means
+reading the input twice, unless it has an index.)
+
 Usage:
     xsv split [options] <outdir> [<input>]
     xsv split --help
@@ -24,18 +35,35 @@ Usage:
 split options:
     -s, --size <arg>       The number of records to write into each chunk.
                            [default: 500]
+    -b, --bytes <arg>      The maximum number of bytes of CSV data in each
+                           chunk, e.g., 100MB. The suffixes KB, MB and GB
+                           are powers of 1000, and KiB, MiB and GiB are
+                           powers of 1024. A record that is longer on its
+                           own is written to a chunk by itself. Chunks are
+                           always written one at a time.
+    -c, --chunks <n>       Split the records into <n> chunks.
     -j, --jobs <arg>       The number of spliting jobs to run in parallel.
-                           This only works when the given CSV data has
-                           an index already created. Note that a file handle
-                           is opened for each job.
+                           This works best when the given CSV data has an
+                           index already created. Without an index, a large
+                           file is split into byte ranges, which are read
+                           twice (first to count their records). Note that
+                           a file handle is opened for each job.
                            When set to '0', the number of jobs is set to the
                            number of CPUs detected.
                            [default: 0]
     --filename <filename>  A filename template to use when constructing
                            the names of the output files.  The string '{}'
-                           will be replaced by a value based on the value
-                           of the field, but sanitized for shell safety.
+                           will be replaced by the index of the first
+                           record of the chunk, and the string '{seq}' by
+                           the number of the chunk (starting at 0). Either
+                           one may be padded with zeros so that the files
+                           sort in order, e.g., '{seq:04}.csv'.
                            [default: {}.csv]
+    -z, --gzip             Compress each chunk with gzip, adding '.gz' to
+                           its filename unless it already ends with it.
+                           (Chunks are also compressed when the --filename
+                           template ends in '.gz', '.zst', '.bz2' or
+                           '.xz'.)
 
 Common options:
     -h, --help             Display this message
@@ -44,6 +72,8 @@ Common options:
                            appear in all chunks as the header row.
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 #[derive(Clone, Deserialize)]
@@ -51,22 +81,84 @@ struct Args {
     arg_input: Option<String>,
     arg_outdir: String,
     flag_size: usize,
+    flag_bytes: Option<String>,
+    flag_chunks: Option<usize>,
     flag_jobs: usize,
     flag_filename: FilenameTemplate,
+    flag_gzip: bool,
     flag_no_headers: bool,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
 }
 
 pub fn run(argv: &[&str]) -> CliResult<()> {
     let args: Args = util::get_args(USAGE, argv)?;
     if args.flag_size == 0 {
-        return fail!("--size must be greater than 0.");
+        return fail_usage!("--size must be greater than 0.");
+    }
+    if args.flag_bytes.is_some() && args.flag_chunks.is_some() {
+        return fail_usage!("--bytes and --chunks can't be used together.");
+    }
+    let limit = match args.flag_bytes {
+        None => None,
+        Some(ref b) => Some(parse_bytes(b)?),
+    };
+    if args.flag_chunks == Some(0) {
+        return fail_usage!("--chunks must be greater than 0.");
     }
     fs::create_dir_all(&args.arg_outdir)?;
 
+    if let Some(limit) = limit {
+        return args.bytes_split(limit);
+    }
+    if let Some(nchunks) = args.flag_chunks {
+        return args.chunks_split(nchunks);
+    }
     match args.rconfig().indexed()? {
-        Some(idx) => args.parallel_split(idx),
-        None => args.sequential_split(),
+        Some(idx) => {
+            let size = args.flag_size;
+            let nchunks = util::num_of_chunks(idx.count() as usize, size);
+            let chunks = (0..nchunks).map(|i| (i * size, size)).collect();
+            args.parallel_split(&idx, chunks)
+        }
+        None => args.chunked_split(),
+    }
+}
+
+/// Parse a number of bytes, with an optional suffix like `MB` or `MiB`.
+fn parse_bytes(arg: &str) -> CliResult<u64> {
+    let arg = arg.trim();
+    let split = arg.find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len());
+    let (n, suffix) = arg.split_at(split);
+    let unit: u64 = match &*suffix.trim().to_lowercase() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "m" | "mb" => 1_000_000,
+        "g" | "gb" => 1_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        _ => return fail_usage!(format!("Unknown unit in --bytes '{}'.", arg)),
+    };
+    match n.parse::<u64>().ok().and_then(|n| n.checked_mul(unit)) {
+        Some(n) if n > 0 => Ok(n),
+        _ => fail_usage!(format!(
+            "--bytes must be a number of bytes greater than 0, not '{}'.",
+            arg)),
+    }
+}
+
+/// A writer that only counts the bytes written to it.
+struct ByteCounter(Rc<Cell<u64>>);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.set(self.0.get() + buf.len() as u64);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
 }
 
@@ -76,51 +168,196 @@ impl Args {
         let mut rdr = rconfig.reader()?;
         let headers = rdr.byte_headers()?.clone();
 
-        let mut wtr = self.new_writer(&headers, 0)?;
+        let mut wtr = self.new_writer(&headers, 0, 0)?;
         let mut i = 0;
         let mut row = csv::ByteRecord::new();
         while rdr.read_byte_record(&mut row)? {
             if i > 0 && i % self.flag_size == 0 {
-                wtr.flush()?;
-                wtr = self.new_writer(&headers, i)?;
+                Config::finish(wtr)?;
+                wtr = self.new_writer(&headers, i, i / self.flag_size)?;
             }
             wtr.write_byte_record(&row)?;
             i += 1;
         }
-        wtr.flush()?;
+        Config::finish(wtr)?;
         Ok(())
     }
 
+    /// Write each chunk, given as the index of its first record and its
+    /// number of records, with the index `idx`.
     fn parallel_split(
         &self,
-        idx: Indexed<fs::File, fs::File>,
+        idx: &IndexedFile,
+        chunks: Vec<(usize, usize)>,
     ) -> CliResult<()> {
-        let nchunks = util::num_of_chunks(
-            idx.count() as usize, self.flag_size);
-        let pool = ThreadPool::new(self.njobs());
-        let wg = chan::WaitGroup::new();
-        for i in 0..nchunks {
-            wg.add(1);
-            let args = self.clone();
-            let wg = wg.clone();
-            pool.execute(move || {
-                let conf = args.rconfig();
-                let mut idx = conf.indexed().unwrap().unwrap();
-                let headers = idx.byte_headers().unwrap().clone();
-                let mut wtr = args
-                    .new_writer(&headers, i * args.flag_size)
-                    .unwrap();
-
-                idx.seek((i * args.flag_size) as u64).unwrap();
-                for row in idx.byte_records().take(args.flag_size) {
-                    let row = row.unwrap();
-                    wtr.write_byte_record(&row).unwrap();
+        let header = match idx.header() {
+            Some(header) => Arc::new(header.clone()),
+            None => return fail!("the input has no index file"),
+        };
+        let mut jobs = vec![];
+        for (seq, (start, len)) in chunks.into_iter().enumerate() {
+            let (args, header) = (self.clone(), header.clone());
+            jobs.push(move || {
+                args.write_indexed_chunk(&header, start, len, seq)
+            });
+        }
+        run_jobs(self.njobs(), jobs)
+    }
+
+    /// Write the `len` records starting with record `start` to the file
+    /// with sequence number `seq`, reading them through the index of the
+    /// input, which has the given header.
+    fn write_indexed_chunk(
+        &self,
+        header: &IndexHeader,
+        start: usize,
+        len: usize,
+        seq: usize,
+    ) -> CliResult<()> {
+        let mut idx = self.rconfig().reindexed(header)?;
+        let headers = idx.byte_headers()?.clone();
+        let mut wtr = self.new_writer(&headers, start, seq)?;
+        if len > 0 {
+            idx.seek(start as u64)?;
+        }
+        for row in idx.byte_records().take(len) {
+            wtr.write_byte_record(&row?)?;
+        }
+        Config::finish(wtr)?;
+        Ok(())
+    }
+
+    fn chunked_split(&self) -> CliResult<()> {
+        let mut chunked = match self.rconfig().chunked(self.njobs())? {
+            None => return self.sequential_split(),
+            Some(chunked) => chunked,
+        };
+        // Count the records in each chunk, so that each job knows the index
+        // of the first record in its chunk.
+        let counts = chunked.map(count_records)?;
+
+        let chunked = Arc::new(chunked);
+        let mut jobs = vec![];
+        let mut first = 0;
+        for (i, &count) in counts.iter().enumerate() {
+            let (start, end) = (first, first + count);
+            first = end;
+            // Each job writes the files that start in its chunk. The last
+            // one may continue into the next chunk.
+            let size = self.flag_size;
+            let file_start = start.div_ceil(size) * size;
+            if file_start >= end {
+                continue;
+            }
+            let (args, chunked) = (self.clone(), chunked.clone());
+            jobs.push(move || {
+                let mut rdr = chunked.reader_at(chunked.starts()[i])?;
+                let headers = rdr.byte_headers()?.clone();
+                let skip = file_start - start;
+                let mut rows = rdr.into_byte_records().skip(skip);
+                let mut i = file_start;
+                while i < end {
+                    let mut wtr = args.new_writer(&headers, i, i / size)?;
+                    for row in rows.by_ref().take(size) {
+                        wtr.write_byte_record(&row?)?;
+                    }
+                    Config::finish(wtr)?;
+                    i += size;
                 }
-                wtr.flush().unwrap();
-                wg.done();
+                Ok(())
             });
         }
-        wg.wait();
+        run_jobs(self.njobs(), jobs)
+    }
+
+    /// Split the records into `nchunks` chunks, whose numbers of records
+    /// differ by at most one.
+    fn chunks_split(&self, nchunks: usize) -> CliResult<()> {
+        let idx = self.rconfig().indexed()?;
+        let count = match idx {
+            Some(ref idx) => idx.count() as usize,
+            None => self.count()?,
+        };
+        let mut chunks = vec![];
+        let mut start = 0;
+        for i in 0..nchunks {
+            let extra = if i < count % nchunks { 1 } else { 0 };
+            let len = count / nchunks + extra;
+            chunks.push((start, len));
+            start += len;
+        }
+        if let Some(ref idx) = idx {
+            if self.njobs() > 1 {
+                return self.parallel_split(idx, chunks);
+            }
+        }
+        let mut rdr = self.rconfig().reader()?;
+        let headers = rdr.byte_headers()?.clone();
+        let mut rows = rdr.byte_records();
+        for (seq, &(start, len)) in chunks.iter().enumerate() {
+            let mut wtr = self.new_writer(&headers, start, seq)?;
+            for row in rows.by_ref().take(len) {
+                wtr.write_byte_record(&row?)?;
+            }
+            Config::finish(wtr)?;
+        }
+        Ok(())
+    }
+
+    /// Count the records of the input, which is then read again.
+    fn count(&self) -> CliResult<usize> {
+        let conf = self.rconfig();
+        if self.arg_input.is_none() {
+            return fail_usage!("--chunks can't be used with <stdin>, since \
+                                the records have to be counted first.");
+        }
+        if let Some(mut chunked) = conf.chunked(self.njobs())? {
+            return Ok(chunked.map(count_records)?.into_iter().sum());
+        }
+        count_records(&mut conf.reader()?.into_byte_records())
+    }
+
+    /// Write the records to chunks of at most `limit` bytes each.
+    fn bytes_split(&self, limit: u64) -> CliResult<()> {
+        let mut rdr = self.rconfig().reader()?;
+        let headers = rdr.byte_headers()?.clone();
+
+        // Each record is also written to `measure`, to find out how long it
+        // is in the output.
+        let written = Rc::new(Cell::new(0));
+        let mut measure = self.output_config(0, 0)
+                              .from_writer(ByteCounter(written.clone()));
+        let mut record_len = |record: &csv::ByteRecord| -> CliResult<u64> {
+            let before = written.get();
+            measure.write_byte_record(record)?;
+            measure.flush()?;
+            Ok(written.get() - before)
+        };
+        let header_len = if self.rconfig().no_headers {
+            0
+        } else {
+            record_len(&headers)?
+        };
+
+        let mut wtr = self.new_writer(&headers, 0, 0)?;
+        let (mut len, mut nrows) = (header_len, 0);
+        let (mut i, mut seq) = (0, 0);
+        let mut row = csv::ByteRecord::new();
+        while rdr.read_byte_record(&mut row)? {
+            let row_len = record_len(&row)?;
+            if nrows > 0 && len + row_len > limit {
+                Config::finish(wtr)?;
+                seq += 1;
+                wtr = self.new_writer(&headers, i, seq)?;
+                len = header_len;
+                nrows = 0;
+            }
+            wtr.write_byte_record(&row)?;
+            len += row_len;
+            nrows += 1;
+            i += 1;
+        }
+        Config::finish(wtr)?;
         Ok(())
     }
 
@@ -128,21 +365,36 @@ impl Args {
         &self,
         headers: &csv::ByteRecord,
         start: usize,
-    ) -> CliResult<csv::Writer<Box<io::Write+'static>>> {
-        let dir = Path::new(&self.arg_outdir);
-        let path = dir.join(self.flag_filename.filename(&format!("{}", start)));
-        let spath = Some(path.display().to_string());
-        let mut wtr = Config::new(&spath).writer()?;
+        seq: usize,
+    ) -> CliResult<csv::Writer<Box<Output+'static>>> {
+        let mut wtr = self.output_config(start, seq).writer()?;
         if !self.rconfig().no_headers {
             wtr.write_record(headers)?;
         }
         Ok(wtr)
     }
 
+    /// The configuration of the file of the chunk that starts with record
+    /// `start` and has the sequence number `seq`.
+    fn output_config(&self, start: usize, seq: usize) -> Config {
+        let dir = Path::new(&self.arg_outdir);
+        let mut filename =
+            self.flag_filename.filename(&start.to_string(), seq);
+        let gzipped = Compression::from_path(Path::new(&filename))
+                      == Some(Compression::Gzip);
+        if self.flag_gzip && !gzipped {
+            filename.push_str(".gz");
+        }
+        let path = dir.join(filename);
+        Config::new(&Some(path.display().to_string()))
+            .no_headers(self.flag_no_headers)
+    }
+
     fn rconfig(&self) -> Config {
         Config::new(&self.arg_input)
             .delimiter(self.flag_delimiter)
             .no_headers(self.flag_no_headers)
+            .sniff(self.flag_sniff)
     }
 
     fn njobs(&self) -> usize {
@@ -153,3 +405,41 @@ impl Args {
         }
     }
 }
+
+/// Run `jobs` on a pool of `njobs` threads, and return the first error of
+/// any of them.
+fn run_jobs<F>(njobs: usize, jobs: Vec<F>) -> CliResult<()>
+        where F: FnOnce() -> CliResult<()> + Send + 'static {
+    let pool = ThreadPool::new(njobs);
+    let (send, recv) = chan::sync(0);
+    let njobs = jobs.len();
+    for job in jobs {
+        let send = send.clone();
+        pool.execute(move || send.send(job()));
+    }
+    drop(send);
+
+    let mut ndone = 0;
+    let mut result = Ok(());
+    for res in recv.iter() {
+        ndone += 1;
+        if result.is_ok() {
+            result = res;
+        }
+    }
+    result?;
+    if ndone < njobs {
+        // A job panicked, so there is no error to report.
+        return fail!("failed to write a part of the input");
+    }
+    Ok(())
+}
+
+fn count_records(rows: RecordChunk) -> CliResult<usize> {
+    let mut count = 0;
+    for row in rows {
+        row?;
+        count += 1;
+    }
+    Ok(count)
+}
@@ -1,7 +1,6 @@
 use std::borrow::ToOwned;
+use std::cmp::Ordering;
 use std::default::Default;
-use std::fmt;
-use std::fs;
 use std::io;
 use std::iter::{FromIterator, repeat};
 use std::str::{self, FromStr};
@@ -9,15 +8,21 @@ use std::str::{self, FromStr};
 use chan;
 use csv;
 use stats::{Commute, OnlineStats, MinMax, Unsorted, merge_all};
+use serde::de::{Deserializer, Deserialize, Error};
 use threadpool::ThreadPool;
 
 use CliResult;
+use chunk;
 use config::{Config, Delimiter};
-use index::Indexed;
+use index::IndexedFile;
 use select::{SelectColumns, Selection};
+use sketch::{HyperLogLog, TDigest};
+use types::{Date, DateFormat, FieldType, MILLIS_PER_DAY, show_date};
 use util;
 
-use self::FieldType::{TUnknown, TNull, TUnicode, TFloat, TInteger};
+use types::FieldType::{
+    TUnknown, TNull, TUnicode, TFloat, TInteger, TDate, TDateTime,
+};
 
 static USAGE: &'static str = "
 Computes basic statistics on CSV data.
@@ -29,6 +34,22 @@ be enabled explicitly. By default, the following statistics are reported for
 default set of statistics corresponds to statistics that can be computed
 efficiently on a stream of data (i.e., constant memory).
 
+Dates and timestamps are detected when they are written in ISO 8601 (e.g.,
+2018-03-21 or 2018-03-21T14:05:00), RFC 3339 (e.g., 2018-03-21T14:05:00Z or
+2018-03-21T14:05:00+01:00) or MM/DD/YYYY format. Such columns are reported
+with a type of Date or DateTime, their min/max values are chronological and
+the 'date_format' column shows which format was detected. The 'range' of a
+Date column is given in days, while the range of a DateTime column is given in
+seconds. Timestamps with a UTC offset are normalized to UTC.
+
+With --counts, every column also reports data quality counts: the number of
+values ('count'), the number of empty values ('null_count' and
+'null_percent'), the number of non-empty values ('non_null_count') and the
+number of values with leading or trailing whitespace. With --distinct-approx,
+'distinct_approx' estimates the number of distinct values with a HyperLogLog,
+which has a typical error below 1%. Unlike the exact cardinality, this
+requires only constant memory.
+
 Computing statistics on a large file can be made much faster if you create
 an index for it first with 'xsv index'.
 
@@ -41,18 +62,37 @@ stats options:
                            This is provided here because piping 'xsv select'
                            into 'xsv stats' will disable the use of indexing.
     --everything           Show all statistics available.
+    --counts               Show the number of values, of empty and
+                           non-empty values, and of values with leading or
+                           trailing whitespace.
+    --distinct-approx      Show an estimate of the number of distinct
+                           values, in constant memory.
     --mode                 Show the mode.
                            This requires storing all CSV data in memory.
     --cardinality          Show the cardinality.
                            This requires storing all CSV data in memory.
     --median               Show the median.
                            This requires storing all CSV data in memory.
+    --quantiles <arg>      Show the given quantiles of each numeric column,
+                           as a comma separated list of numbers between 0
+                           and 1. e.g., '0.05,0.25,0.5,0.75,0.95'.
+                           Quantiles are computed exactly while a column
+                           has few numbers (see --quantile-exact), and are
+                           otherwise estimated with a t-digest in constant
+                           memory.
+    --quantile-exact <n>   The maximum number of values per column that are
+                           kept in memory to compute quantiles exactly.
+                           [default: 10000]
     --nulls                Include NULLs in the population size for computing
                            mean and standard deviation.
+    --epoch-seconds        Interpret integers as Unix timestamps (seconds
+                           since 1970-01-01T00:00:00Z), which makes columns
+                           of integers have a DateTime type.
     -j, --jobs <arg>       The number of jobs to run in parallel.
                            This works better when the given CSV data has
-                           an index already created. Note that a file handle
-                           is opened for each job.
+                           an index already created. Without an index, a
+                           large file is split into byte ranges instead.
+                           Note that a file handle is opened for each job.
                            When set to '0', the number of jobs is set to the
                            number of CPUs detected.
                            [default: 0]
@@ -65,6 +105,8 @@ Common options:
                            in statistics.
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 #[derive(Clone, Deserialize)]
@@ -72,14 +114,20 @@ struct Args {
     arg_input: Option<String>,
     flag_select: SelectColumns,
     flag_everything: bool,
+    flag_counts: bool,
+    flag_distinct_approx: bool,
     flag_mode: bool,
     flag_cardinality: bool,
     flag_median: bool,
+    flag_quantiles: Option<QuantileList>,
+    flag_quantile_exact: usize,
     flag_nulls: bool,
+    flag_epoch_seconds: bool,
     flag_jobs: usize,
     flag_output: Option<String>,
     flag_no_headers: bool,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
 }
 
 pub fn run(argv: &[&str]) -> CliResult<()> {
@@ -87,7 +135,7 @@ pub fn run(argv: &[&str]) -> CliResult<()> {
 
     let mut wtr = Config::new(&args.flag_output).writer()?;
     let (headers, stats) = match args.rconfig().indexed()? {
-        None => args.sequential_stats(),
+        None => args.chunked_stats(),
         Some(idx) => {
             if args.flag_jobs == 1 {
                 args.sequential_stats()
@@ -110,7 +158,7 @@ pub fn run(argv: &[&str]) -> CliResult<()> {
         let stat = stat.iter().map(|f| f.as_bytes());
         wtr.write_record(vec![&*header].into_iter().chain(stat))?;
     }
-    wtr.flush()?;
+    Config::finish(wtr)?;
     Ok(())
 }
 
@@ -124,7 +172,7 @@ impl Args {
 
     fn parallel_stats(
         &self,
-        idx: Indexed<fs::File, fs::File>,
+        idx: IndexedFile,
     ) -> CliResult<(csv::ByteRecord, Vec<Stats>)> {
         // N.B. This method doesn't handle the case when the number of records
         // is zero correctly. So we use `sequential_stats` instead.
@@ -135,22 +183,24 @@ impl Args {
         let mut rdr = self.rconfig().reader()?;
         let (headers, sel) = self.sel_headers(&mut rdr)?;
 
-        let chunk_size = util::chunk_size(idx.count() as usize, self.njobs());
-        let nchunks = util::num_of_chunks(idx.count() as usize, chunk_size);
+        let args = self.clone();
+        let stats = chunk::map_indexed(
+            &self.rconfig(), &idx, self.njobs(),
+            move |it| args.compute(&sel, it))?;
+        Ok((headers, merge_all(stats.into_iter()).unwrap_or_else(Vec::new)))
+    }
 
-        let pool = ThreadPool::new(self.njobs());
-        let (send, recv) = chan::sync(0);
-        for i in 0..nchunks {
-            let (send, args, sel) = (send.clone(), self.clone(), sel.clone());
-            pool.execute(move || {
-                let mut idx = args.rconfig().indexed().unwrap().unwrap();
-                idx.seek((i * chunk_size) as u64).unwrap();
-                let it = idx.byte_records().take(chunk_size);
-                send.send(args.compute(&sel, it).unwrap());
-            });
-        }
-        drop(send);
-        Ok((headers, merge_all(recv.iter()).unwrap_or_else(Vec::new)))
+    fn chunked_stats(&self) -> CliResult<(csv::ByteRecord, Vec<Stats>)> {
+        let mut chunked = match self.rconfig().chunked(self.njobs())? {
+            None => return self.sequential_stats(),
+            Some(chunked) => chunked,
+        };
+        let mut rdr = self.rconfig().reader()?;
+        let (headers, sel) = self.sel_headers(&mut rdr)?;
+
+        let args = self.clone();
+        let stats = chunked.map(move |it| args.compute(&sel, it))?;
+        Ok((headers, merge_all(stats.into_iter()).unwrap_or_else(Vec::new)))
     }
 
     fn stats_to_records(&self, stats: Vec<Stats>) -> Vec<csv::StringRecord> {
@@ -196,6 +246,7 @@ impl Args {
             .delimiter(self.flag_delimiter)
             .no_headers(self.flag_no_headers)
             .select(self.flag_select.clone())
+            .sniff(self.flag_sniff)
     }
 
     fn njobs(&self) -> usize {
@@ -205,16 +256,23 @@ impl Args {
     fn new_stats(&self, record_len: usize) -> Vec<Stats> {
         repeat(Stats::new(WhichStats {
             include_nulls: self.flag_nulls,
+            epoch_seconds: self.flag_epoch_seconds,
             sum: true,
+            counts: self.flag_counts || self.flag_everything,
+            distinct: self.flag_distinct_approx || self.flag_everything,
             range: true,
             dist: true,
             cardinality: self.flag_cardinality || self.flag_everything,
             median: self.flag_median || self.flag_everything,
+            quantiles: self.quantiles().to_vec(),
+            quantile_exact: self.flag_quantile_exact,
             mode: self.flag_mode || self.flag_everything,
         })).take(record_len).collect()
     }
 
     fn stat_headers(&self) -> csv::StringRecord {
+        // Columns that were added later come after the original ones, so
+        // that those keep their positions.
         let mut fields = vec![
             "field", "type", "sum", "min", "max", "min_length", "max_length",
             "mean", "stddev",
@@ -223,18 +281,66 @@ impl Args {
         if self.flag_median || all { fields.push("median"); }
         if self.flag_mode || all { fields.push("mode"); }
         if self.flag_cardinality || all { fields.push("cardinality"); }
-        csv::StringRecord::from(fields)
+        fields.extend(&["range", "date_format"]);
+        if self.flag_counts || all {
+            fields.extend(&[
+                "count", "null_count", "null_percent", "non_null_count",
+                "leading_whitespace", "trailing_whitespace",
+            ]);
+        }
+        if self.flag_distinct_approx || all {
+            fields.push("distinct_approx");
+        }
+        let mut record = csv::StringRecord::from(fields);
+        for q in self.quantiles() {
+            record.push_field(&format!("q{}", q));
+        }
+        record
+    }
+
+    fn quantiles(&self) -> &[f64] {
+        self.flag_quantiles.as_ref().map_or(&[], |qs| &qs.0)
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+/// QuantileList is a comma separated list of quantiles given on the command
+/// line, each of which must be in the interval `[0, 1]`.
+#[derive(Clone, Debug)]
+struct QuantileList(Vec<f64>);
+
+impl<'de> Deserialize<'de> for QuantileList {
+    fn deserialize<D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<QuantileList, D::Error> {
+        let raw = String::deserialize(d)?;
+        let mut qs = vec![];
+        for s in raw.split(',') {
+            match s.trim().parse::<f64>() {
+                Ok(q) if (0.0..=1.0).contains(&q) => qs.push(q),
+                _ => {
+                    let msg = format!("Could not parse '{}' as a quantile \
+                                       between 0 and 1.", s);
+                    return Err(D::Error::custom(msg));
+                }
+            }
+        }
+        Ok(QuantileList(qs))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 struct WhichStats {
     include_nulls: bool,
+    epoch_seconds: bool,
     sum: bool,
+    counts: bool,
+    distinct: bool,
     range: bool,
     dist: bool,
     cardinality: bool,
     median: bool,
+    quantiles: Vec<f64>,
+    quantile_exact: usize,
     mode: bool,
 }
 
@@ -248,10 +354,16 @@ impl Commute for WhichStats {
 struct Stats {
     typ: FieldType,
     sum: Option<TypedSum>,
+    counts: Option<FieldCounts>,
+    distinct: Option<HyperLogLog>,
     minmax: Option<TypedMinMax>,
     online: Option<OnlineStats>,
     mode: Option<Unsorted<Vec<u8>>>,
     median: Option<Unsorted<f64>>,
+    quantiles: Option<Quantiles>,
+    date_format: Option<DateFormat>,
+    /// Whether every date seen so far is known to be in UTC.
+    all_utc: bool,
     which: WhichStats,
 }
 
@@ -260,29 +372,67 @@ impl Stats {
         let (mut sum, mut minmax, mut online, mut mode, mut median) =
             (None, None, None, None, None);
         if which.sum { sum = Some(Default::default()); }
+        let counts = if which.counts { Some(Default::default()) } else { None };
+        let distinct =
+            if which.distinct { Some(Default::default()) } else { None };
         if which.range { minmax = Some(Default::default()); }
         if which.dist { online = Some(Default::default()); }
         if which.mode || which.cardinality { mode = Some(Default::default()); }
         if which.median { median = Some(Default::default()); }
+        let quantiles = if which.quantiles.is_empty() {
+            None
+        } else {
+            Some(Quantiles::new(which.quantile_exact))
+        };
         Stats {
             typ: Default::default(),
             sum: sum,
+            counts,
+            distinct,
             minmax: minmax,
             online: online,
             mode: mode,
             median: median,
+            quantiles,
+            date_format: None,
+            all_utc: true,
             which: which,
         }
     }
 
     fn add(&mut self, sample: &[u8]) {
-        let sample_type = FieldType::from_sample(sample);
+        let mut sample_type = FieldType::from_sample(sample);
+        let date = match sample_type {
+            TInteger if self.which.epoch_seconds => {
+                let date = from_bytes::<i64>(sample)
+                    .and_then(Date::from_epoch_seconds);
+                if date.is_some() {
+                    sample_type = TDateTime;
+                }
+                date
+            }
+            TDate | TDateTime => Date::from_sample(sample),
+            _ => None,
+        };
         self.typ.merge(sample_type);
 
         let t = self.typ;
+        if let Some(ref mut counts) = self.counts {
+            counts.add(sample);
+        }
+        if let Some(ref mut distinct) = self.distinct {
+            distinct.add(sample);
+        }
         self.sum.as_mut().map(|v| v.add(t, sample));
         self.minmax.as_mut().map(|v| v.add(t, sample));
         self.mode.as_mut().map(|v| v.add(sample.to_vec()));
+        if let Some(date) = date {
+            if let Some(ref mut minmax) = self.minmax {
+                minmax.add_date(date);
+            }
+            self.date_format.merge(Some(date.format));
+            self.all_utc &= date.format.is_utc();
+        }
         match self.typ {
             TUnknown => {}
             TNull => {
@@ -290,7 +440,7 @@ impl Stats {
                     self.online.as_mut().map(|v| { v.add_null(); });
                 }
             }
-            TUnicode => {}
+            TUnicode | TDate | TDateTime => {}
             TFloat | TInteger => {
                 if sample_type.is_null() {
                     if self.which.include_nulls {
@@ -299,6 +449,9 @@ impl Stats {
                 } else {
                     let n = from_bytes::<f64>(sample).unwrap();
                     self.median.as_mut().map(|v| { v.add(n); });
+                    if let Some(ref mut quantiles) = self.quantiles {
+                        quantiles.add(n);
+                    }
                     self.online.as_mut().map(|v| { v.add(n); });
                 }
             }
@@ -315,7 +468,8 @@ impl Stats {
             Some(sum) => { pieces.push(sum); }
             None => { pieces.push(empty()); }
         }
-        match self.minmax.as_ref().and_then(|mm| mm.show(typ)) {
+        let utc = self.date_format.is_some() && self.all_utc;
+        match self.minmax.as_ref().and_then(|mm| mm.show(typ, utc)) {
             Some(mm) => { pieces.push(mm.0); pieces.push(mm.1); }
             None => { pieces.push(empty()); pieces.push(empty()); }
         }
@@ -365,6 +519,31 @@ impl Stats {
                 }
             }
         }
+        match self.minmax.as_ref().and_then(|mm| mm.range(typ)) {
+            Some(range) => { pieces.push(range); }
+            None => { pieces.push(empty()); }
+        }
+        match self.date_format {
+            Some(ref format) if typ.is_date() => {
+                pieces.push(format.to_string());
+            }
+            _ => { pieces.push(empty()); }
+        }
+        if let Some(ref counts) = self.counts {
+            pieces.extend(counts.show());
+        }
+        if let Some(ref distinct) = self.distinct {
+            pieces.push(distinct.count().to_string());
+        }
+        for &q in &self.which.quantiles {
+            let value = match self.quantiles {
+                Some(ref mut quantiles) if typ.is_number() => {
+                    quantiles.quantile(q)
+                }
+                _ => None,
+            };
+            pieces.push(value.map_or_else(empty, |v| v.to_string()));
+        }
         csv::StringRecord::from(pieces)
     }
 }
@@ -373,81 +552,151 @@ impl Commute for Stats {
     fn merge(&mut self, other: Stats) {
         self.typ.merge(other.typ);
         self.sum.merge(other.sum);
+        self.counts.merge(other.counts);
+        self.distinct.merge(other.distinct);
         self.minmax.merge(other.minmax);
         self.online.merge(other.online);
         self.mode.merge(other.mode);
         self.median.merge(other.median);
+        self.quantiles.merge(other.quantiles);
+        self.date_format.merge(other.date_format);
+        self.all_utc &= other.all_utc;
         self.which.merge(other.which);
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
-enum FieldType {
-    TUnknown,
-    TNull,
-    TUnicode,
-    TFloat,
-    TInteger,
+/// FieldCounts counts values in a column by their emptiness and surrounding
+/// whitespace.
+#[derive(Clone, Default)]
+struct FieldCounts {
+    count: u64,
+    nulls: u64,
+    leading_whitespace: u64,
+    trailing_whitespace: u64,
 }
 
-impl FieldType {
-    fn from_sample(sample: &[u8]) -> FieldType {
+impl FieldCounts {
+    fn add(&mut self, sample: &[u8]) {
+        self.count += 1;
         if sample.is_empty() {
-            return TNull;
+            self.nulls += 1;
+            return;
         }
-        let string = match str::from_utf8(sample) {
-            Err(_) => return TUnknown,
-            Ok(s) => s,
+        let (leading, trailing) = match str::from_utf8(sample) {
+            Ok(s) => {
+                (s.starts_with(char::is_whitespace),
+                 s.ends_with(char::is_whitespace))
+            }
+            Err(_) => {
+                (sample[0].is_ascii_whitespace(),
+                 sample[sample.len() - 1].is_ascii_whitespace())
+            }
         };
-        if let Ok(_) = string.parse::<i64>() { return TInteger; }
-        if let Ok(_) = string.parse::<f64>() { return TFloat; }
-        TUnicode
+        if leading {
+            self.leading_whitespace += 1;
+        }
+        if trailing {
+            self.trailing_whitespace += 1;
+        }
     }
 
-    fn is_number(&self) -> bool {
-        *self == TFloat || *self == TInteger
+    /// Returns the count, null count, null percentage, non-null count and
+    /// whitespace counts, in that order.
+    fn show(&self) -> Vec<String> {
+        let null_percent = if self.count == 0 {
+            "".to_owned()
+        } else {
+            (100.0 * self.nulls as f64 / self.count as f64).to_string()
+        };
+        vec![
+            self.count.to_string(),
+            self.nulls.to_string(),
+            null_percent,
+            (self.count - self.nulls).to_string(),
+            self.leading_whitespace.to_string(),
+            self.trailing_whitespace.to_string(),
+        ]
     }
+}
 
-    fn is_null(&self) -> bool {
-        *self == TNull
+impl Commute for FieldCounts {
+    fn merge(&mut self, other: FieldCounts) {
+        self.count += other.count;
+        self.nulls += other.nulls;
+        self.leading_whitespace += other.leading_whitespace;
+        self.trailing_whitespace += other.trailing_whitespace;
     }
 }
 
-impl Commute for FieldType {
-    fn merge(&mut self, other: FieldType) {
-        *self = match (*self, other) {
-            (TUnicode, TUnicode) => TUnicode,
-            (TFloat, TFloat) => TFloat,
-            (TInteger, TInteger) => TInteger,
-            // Null does not impact the type.
-            (TNull, any) | (any, TNull) => any,
-            // There's no way to get around an unknown.
-            (TUnknown, _) | (_, TUnknown) => TUnknown,
-            // Integers can degrate to floats.
-            (TFloat, TInteger) | (TInteger, TFloat) => TFloat,
-            // Numbers can degrade to Unicode strings.
-            (TUnicode, TFloat) | (TFloat, TUnicode) => TUnicode,
-            (TUnicode, TInteger) | (TInteger, TUnicode) => TUnicode,
-        };
-    }
+/// Quantiles computes quantiles of a stream of numbers.
+///
+/// Quantiles are computed exactly while at most `exact_limit` numbers have
+/// been seen. Beyond that, the numbers are fed to a t-digest, which estimates
+/// quantiles using constant memory.
+#[derive(Clone)]
+struct Quantiles {
+    exact: Option<Vec<f64>>,
+    exact_limit: usize,
+    digest: TDigest,
 }
 
-impl Default for FieldType {
-    // The default is the most specific type.
-    // Type inference proceeds by assuming the most specific type and then
-    // relaxing the type as counter-examples are found.
-    fn default() -> FieldType { TNull }
+impl Quantiles {
+    fn new(exact_limit: usize) -> Quantiles {
+        Quantiles {
+            exact: Some(vec![]),
+            exact_limit,
+            digest: TDigest::new(200.0),
+        }
+    }
+
+    fn add(&mut self, n: f64) {
+        if let Some(ref mut values) = self.exact {
+            if values.len() < self.exact_limit {
+                values.push(n);
+                return;
+            }
+        }
+        self.spill();
+        self.digest.add(n);
+    }
+
+    /// Move all exactly tracked numbers into the t-digest.
+    fn spill(&mut self) {
+        if let Some(values) = self.exact.take() {
+            for n in values {
+                self.digest.add(n);
+            }
+        }
+    }
+
+    fn quantile(&mut self, q: f64) -> Option<f64> {
+        let values = match self.exact {
+            None => return self.digest.quantile(q),
+            Some(ref mut values) => values,
+        };
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        // Linearly interpolate between the closest ranks.
+        let rank = q * (values.len() - 1) as f64;
+        let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+        Some(values[lo] + (values[hi] - values[lo]) * (rank - lo as f64))
+    }
 }
 
-impl fmt::Display for FieldType {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            TUnknown => write!(f, "Unknown"),
-            TNull => write!(f, "NULL"),
-            TUnicode => write!(f, "Unicode"),
-            TFloat => write!(f, "Float"),
-            TInteger => write!(f, "Integer"),
+impl Commute for Quantiles {
+    fn merge(&mut self, mut other: Quantiles) {
+        if let (Some(values), Some(others)) =
+                (self.exact.as_mut(), other.exact.as_mut()) {
+            if values.len() + others.len() <= self.exact_limit {
+                values.append(others);
+                return;
+            }
         }
+        self.spill();
+        other.spill();
+        self.digest.merge(other.digest);
     }
 }
 
@@ -490,7 +739,7 @@ impl TypedSum {
 
     fn show(&self, typ: FieldType) -> Option<String> {
         match typ {
-            TNull | TUnicode | TUnknown  => None,
+            TNull | TUnicode | TUnknown | TDate | TDateTime => None,
             TInteger => Some(self.integer.to_string()),
             TFloat => Some(self.float.unwrap_or(0.0).to_string()),
         }
@@ -516,6 +765,7 @@ struct TypedMinMax {
     str_len: MinMax<usize>,
     integers: MinMax<i64>,
     floats: MinMax<f64>,
+    dates: MinMax<i64>,
 }
 
 impl TypedMinMax {
@@ -526,7 +776,7 @@ impl TypedMinMax {
         }
         self.strings.add(sample.to_vec());
         match typ {
-            TUnicode | TUnknown | TNull => {}
+            TUnicode | TUnknown | TNull | TDate | TDateTime => {}
             TFloat => {
                 let n = str::from_utf8(&*sample)
                             .ok()
@@ -546,6 +796,10 @@ impl TypedMinMax {
         }
     }
 
+    fn add_date(&mut self, date: Date) {
+        self.dates.add(date.millis);
+    }
+
     fn len_range(&self) -> Option<(String, String)> {
         match (self.str_len.min(), self.str_len.max()) {
             (Some(min), Some(max)) => Some((min.to_string(), max.to_string())),
@@ -553,7 +807,7 @@ impl TypedMinMax {
         }
     }
 
-    fn show(&self, typ: FieldType) -> Option<(String, String)> {
+    fn show(&self, typ: FieldType, utc: bool) -> Option<(String, String)> {
         match typ {
             TNull => None,
             TUnicode | TUnknown => {
@@ -582,6 +836,58 @@ impl TypedMinMax {
                     _ => None
                 }
             }
+            TDate | TDateTime => {
+                match (self.dates.min(), self.dates.max()) {
+                    (Some(&min), Some(&max)) => {
+                        let time = typ == TDateTime;
+                        Some((show_date(min, time, utc),
+                              show_date(max, time, utc)))
+                    }
+                    _ => None
+                }
+            }
+        }
+    }
+
+    /// Returns the difference between the maximum and minimum values.
+    ///
+    /// The range of dates is in days and the range of timestamps is in
+    /// seconds. There is no range if the difference overflows.
+    fn range(&self, typ: FieldType) -> Option<String> {
+        match typ {
+            TNull | TUnicode | TUnknown => None,
+            TInteger => {
+                match (self.integers.min(), self.integers.max()) {
+                    (Some(min), Some(max)) => {
+                        max.checked_sub(*min).map(|r| r.to_string())
+                    }
+                    _ => None
+                }
+            }
+            TFloat => {
+                match (self.floats.min(), self.floats.max()) {
+                    (Some(min), Some(max)) => Some((max - min).to_string()),
+                    _ => None
+                }
+            }
+            TDate => {
+                match (self.dates.min(), self.dates.max()) {
+                    (Some(min), Some(max)) => {
+                        max.checked_sub(*min)
+                           .map(|r| (r / MILLIS_PER_DAY).to_string())
+                    }
+                    _ => None
+                }
+            }
+            TDateTime => {
+                match (self.dates.min(), self.dates.max()) {
+                    (Some(min), Some(max)) => {
+                        max.checked_sub(*min)
+                           .map(|r| (r as f64 / 1000.0).to_string())
+                    }
+                    _ => None
+                }
+            }
         }
     }
 }
@@ -593,6 +899,7 @@ impl Default for TypedMinMax {
             str_len: Default::default(),
             integers: Default::default(),
             floats: Default::default(),
+            dates: Default::default(),
         }
     }
 }
@@ -603,6 +910,7 @@ impl Commute for TypedMinMax {
         self.str_len.merge(other.str_len);
         self.integers.merge(other.integers);
         self.floats.merge(other.floats);
+        self.dates.merge(other.dates);
     }
 }
 
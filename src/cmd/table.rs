@@ -1,10 +1,14 @@
 use std::borrow::Cow;
+use std::io;
 
 use csv;
+use stats::Commute;
 use tabwriter::TabWriter;
+use unicode_width::UnicodeWidthStr;
 
 use CliResult;
-use config::{Config, Delimiter};
+use config::{Config, Delimiter, Output};
+use types::FieldType;
 use util;
 
 static USAGE: &'static str = "
@@ -16,6 +20,17 @@ Note that formatting a table requires buffering all CSV data into memory.
 Therefore, you should use the 'sample' or 'slice' command to trim down large
 CSV data before formatting it with this command.
 
+By default, columns are aligned with tabs and spaces. The --format option
+selects another style instead, where the first row is used as the header:
+
+    markdown  A Markdown (GitHub Flavored) table.
+    html      An HTML table.
+    box       A table drawn with Unicode box drawing characters.
+    rst       A reStructuredText grid table.
+
+In these styles, columns whose values are all numbers are aligned to the
+right, and line breaks in fields are replaced with spaces.
+
 Usage:
     xsv table [options] [<input>]
 
@@ -28,12 +43,16 @@ table options:
                            specified. If the field is UTF-8 encoded, then
                            <arg> refers to the number of code points.
                            Otherwise, it refers to the number of bytes.
+    -f, --format <arg>     The style of table to write: plain, markdown,
+                           html, box or rst. [default: plain]
 
 Common options:
     -h, --help             Display this message
     -o, --output <file>    Write output to <file> instead of stdout.
     -d, --delimiter <arg>  The field delimiter for reading CSV data.
                            Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
 ";
 
 #[derive(Deserialize)]
@@ -43,16 +62,31 @@ struct Args {
     flag_pad: usize,
     flag_output: Option<String>,
     flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
     flag_condense: Option<usize>,
+    flag_format: Format,
+}
+
+#[derive(Clone, Copy, Deserialize, PartialEq)]
+enum Format {
+    Plain,
+    Markdown,
+    Html,
+    Box,
+    Rst,
 }
 
 pub fn run(argv: &[&str]) -> CliResult<()> {
     let args: Args = util::get_args(USAGE, argv)?;
     let rconfig = Config::new(&args.arg_input)
         .delimiter(args.flag_delimiter)
-        .no_headers(true);
+        .no_headers(true)
+        .sniff(args.flag_sniff);
     let wconfig = Config::new(&args.flag_output)
         .delimiter(Some(Delimiter(b'\t')));
+    if args.flag_format != Format::Plain {
+        return args.write_styled(rconfig, wconfig);
+    }
 
     let tw = TabWriter::new(wconfig.io_writer()?)
         .minwidth(args.flag_width)
@@ -66,6 +100,232 @@ pub fn run(argv: &[&str]) -> CliResult<()> {
             util::condense(Cow::Borrowed(f), args.flag_condense)
         }))?;
     }
-    wtr.flush()?;
+    // The table is only written once the tab writer is flushed.
+    let tw = wtr.into_inner().map_err(|err| {
+        io::Error::new(err.error().kind(), err.to_string())
+    })?;
+    let mut out = tw.into_inner().map_err(|err| {
+        io::Error::new(err.error().kind(), err.to_string())
+    })?;
+    out.finish()?;
     Ok(())
 }
+
+impl Args {
+    /// Write the table in one of the styles that has a header row.
+    fn write_styled(&self, rconfig: Config, wconfig: Config) -> CliResult<()> {
+        let mut rdr = rconfig.reader()?;
+        let mut rows: Vec<Vec<String>> = vec![];
+        let mut types: Vec<FieldType> = vec![];
+        let mut record = csv::ByteRecord::new();
+        while rdr.read_byte_record(&mut record)? {
+            if !rows.is_empty() {
+                types.resize(record.len(), FieldType::default());
+                for (typ, field) in types.iter_mut().zip(&record) {
+                    typ.merge(FieldType::from_sample(field));
+                }
+            }
+            rows.push(record.iter().map(|f| {
+                let f = util::condense(Cow::Borrowed(f), self.flag_condense);
+                let f = String::from_utf8_lossy(&f).replace("\r\n", " ");
+                let f = f.replace(['\n', '\r'], " ");
+                match self.flag_format {
+                    Format::Markdown => f.replace('|', "\\|"),
+                    Format::Html => escape_html(&f),
+                    _ => f,
+                }
+            }).collect());
+        }
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let right: Vec<bool> = types.iter().map(|t| t.is_number()).collect();
+        let min_width = if self.flag_format == Format::Markdown {
+            // Rules in Markdown tables need at least three characters.
+            self.flag_width.max(3)
+        } else {
+            self.flag_width
+        };
+        let table = Table::new(rows, right, min_width);
+
+        let mut wtr = io::BufWriter::new(wconfig.io_writer()?);
+        match self.flag_format {
+            Format::Plain => unreachable!(),
+            Format::Markdown => table.write_markdown(&mut wtr)?,
+            Format::Html => table.write_html(&mut wtr)?,
+            Format::Box => table.write_grid(&mut wtr, &BOX)?,
+            Format::Rst => table.write_grid(&mut wtr, &RST)?,
+        }
+        wtr.finish()?;
+        Ok(())
+    }
+}
+
+/// A table whose first row is its header.
+struct Table {
+    rows: Vec<Vec<String>>,
+    widths: Vec<usize>,
+    right: Vec<bool>,
+}
+
+impl Table {
+    fn new(
+        rows: Vec<Vec<String>>,
+        right: Vec<bool>,
+        min_width: usize,
+    ) -> Table {
+        let mut widths = vec![];
+        for row in &rows {
+            for (i, field) in row.iter().enumerate() {
+                if i >= widths.len() {
+                    widths.push(min_width);
+                }
+                widths[i] = widths[i].max(field.width());
+            }
+        }
+        Table { rows, widths, right }
+    }
+
+    fn is_right(&self, i: usize) -> bool {
+        self.right.get(i).cloned().unwrap_or(false)
+    }
+
+    /// Pad the field in column `i` to the width of the column.
+    fn pad(&self, i: usize, field: &str) -> String {
+        let fill = " ".repeat(self.widths[i] - field.width());
+        if self.is_right(i) {
+            format!("{}{}", fill, field)
+        } else {
+            format!("{}{}", field, fill)
+        }
+    }
+
+    /// The padded cells of a row.
+    fn cells<'a>(
+        &'a self,
+        row: &'a [String],
+    ) -> impl Iterator<Item=String> + 'a {
+        (0..self.widths.len()).map(move |i| {
+            self.pad(i, row.get(i).map(|s| &**s).unwrap_or(""))
+        })
+    }
+
+    fn write_markdown<W: io::Write>(&self, wtr: &mut W) -> io::Result<()> {
+        let rule: Vec<String> = self.widths.iter().enumerate().map(|(i, &w)| {
+            if self.is_right(i) {
+                format!("{}:", "-".repeat(w - 1))
+            } else {
+                "-".repeat(w)
+            }
+        }).collect();
+        for (n, row) in self.rows.iter().enumerate() {
+            let cells: Vec<String> = self.cells(row).collect();
+            writeln!(wtr, "| {} |", cells.join(" | "))?;
+            if n == 0 {
+                writeln!(wtr, "| {} |", rule.join(" | "))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_html<W: io::Write>(&self, wtr: &mut W) -> io::Result<()> {
+        writeln!(wtr, "<table>")?;
+        for (n, row) in self.rows.iter().enumerate() {
+            let tag = if n == 0 { "th" } else { "td" };
+            if n == 0 {
+                writeln!(wtr, "<thead>")?;
+            } else if n == 1 {
+                writeln!(wtr, "<tbody>")?;
+            }
+            write!(wtr, "<tr>")?;
+            for i in 0..self.widths.len() {
+                let field = row.get(i).map(|s| &**s).unwrap_or("");
+                if self.is_right(i) {
+                    write!(wtr, "<{} style=\"text-align: right\">{}</{}>",
+                           tag, field, tag)?;
+                } else {
+                    write!(wtr, "<{}>{}</{}>", tag, field, tag)?;
+                }
+            }
+            writeln!(wtr, "</tr>")?;
+            if n == 0 {
+                writeln!(wtr, "</thead>")?;
+            }
+        }
+        if self.rows.len() > 1 {
+            writeln!(wtr, "</tbody>")?;
+        }
+        writeln!(wtr, "</table>")
+    }
+
+    fn write_grid<W: io::Write>(
+        &self,
+        wtr: &mut W,
+        style: &GridStyle,
+    ) -> io::Result<()> {
+        let rule = |wtr: &mut W, chars: [char; 4]| -> io::Result<()> {
+            let lines: Vec<String> = self.widths.iter().map(|&w| {
+                chars[1].to_string().repeat(w + 2)
+            }).collect();
+            let sep = chars[2].to_string();
+            writeln!(wtr, "{}{}{}", chars[0], lines.join(&sep), chars[3])
+        };
+        rule(wtr, style.top)?;
+        for (n, row) in self.rows.iter().enumerate() {
+            let sep = format!(" {} ", style.vertical);
+            let cells: Vec<String> = self.cells(row).collect();
+            writeln!(wtr, "{} {} {}",
+                     style.vertical, cells.join(&sep), style.vertical)?;
+            if n == 0 {
+                rule(wtr, style.header)?;
+            } else if n + 1 < self.rows.len() {
+                if let Some(chars) = style.between {
+                    rule(wtr, chars)?;
+                }
+            }
+        }
+        rule(wtr, style.bottom)
+    }
+}
+
+/// The characters used to draw a grid table. Each rule is given as its
+/// left end, line, column separator and right end.
+struct GridStyle {
+    top: [char; 4],
+    header: [char; 4],
+    between: Option<[char; 4]>,
+    bottom: [char; 4],
+    vertical: char,
+}
+
+static BOX: GridStyle = GridStyle {
+    top: ['┌', '─', '┬', '┐'],
+    header: ['├', '─', '┼', '┤'],
+    between: None,
+    bottom: ['└', '─', '┴', '┘'],
+    vertical: '│',
+};
+
+// reStructuredText grid tables require a rule between every row.
+static RST: GridStyle = GridStyle {
+    top: ['+', '-', '+', '+'],
+    header: ['+', '=', '+', '+'],
+    between: Some(['+', '-', '+', '+']),
+    bottom: ['+', '-', '+', '+'],
+    vertical: '|',
+};
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
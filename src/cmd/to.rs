@@ -0,0 +1,284 @@
+use std::fs;
+use std::io;
+use std::str;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayBuilder, ArrayRef, BinaryBuilder, Date32Builder, Float64Builder,
+    Int64Builder, StringBuilder, TimestampMillisecondBuilder,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use csv;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use stats::Commute;
+
+use CliResult;
+use config::{Config, Delimiter};
+use types::{FieldType, MILLIS_PER_DAY, parse_date_millis};
+use util;
+
+/// The time zone of timestamps, which are normalized to UTC by type
+/// inference.
+const UTC: &str = "+00:00";
+
+static USAGE: &'static str = "
+Convert CSV data to Apache Parquet or Arrow IPC files.
+
+The type of each column is inferred the same way as in 'xsv stats':
+
+    Integer    Int64
+    Float      Float64
+    Date       Date32
+    DateTime   Timestamp (milliseconds, UTC)
+    Unicode    Utf8
+
+Columns with invalid UTF-8 are written as Binary, and columns that are
+entirely empty are written as Utf8. Empty fields are written as nulls.
+
+Since types must be known before anything is written, the CSV data is read
+twice, and so <input> must be a file (which may be compressed). Records are
+converted in batches (see the --batch-size option), so memory use depends on
+the batch size rather than the size of the input. In Parquet files, each batch
+is a row group, compressed with Snappy.
+
+Usage:
+    xsv to parquet [options] <output> <input>
+    xsv to arrow [options] <output> <input>
+    xsv to --help
+
+to options:
+    --batch-size <arg>     The number of records in each batch.
+                           [default: 65536]
+
+Common options:
+    -h, --help             Display this message
+    -n, --no-headers       When set, the first row will NOT be interpreted
+                           as column names. Columns are then named by their
+                           1-based index.
+    -d, --delimiter <arg>  The field delimiter for reading CSV data.
+                           Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
+";
+
+#[derive(Deserialize)]
+struct Args {
+    cmd_parquet: bool,
+    arg_output: String,
+    arg_input: String,
+    flag_batch_size: usize,
+    flag_no_headers: bool,
+    flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
+}
+
+pub fn run(argv: &[&str]) -> CliResult<()> {
+    let args: Args = util::get_args(USAGE, argv)?;
+    if args.flag_batch_size == 0 {
+        return fail_usage!("--batch-size must be greater than 0");
+    }
+    let rconfig = Config::new(&Some(args.arg_input.clone()))
+        .delimiter(args.flag_delimiter)
+        .no_headers(args.flag_no_headers)
+        .sniff(args.flag_sniff);
+
+    if rconfig.is_std() {
+        return fail_usage!("<input> must be a file, not <stdin>");
+    }
+
+    let mut rdr = rconfig.reader()?;
+    let headers = rdr.byte_headers()?.clone();
+    let types = infer_types(&mut rdr, headers.len())?;
+    let schema = Arc::new(Schema::new(
+        headers.iter().zip(&types).enumerate().map(|(i, (name, &typ))| {
+            let name = if rconfig.no_headers {
+                (i+1).to_string()
+            } else {
+                String::from_utf8_lossy(name).into_owned()
+            };
+            Field::new(name, data_type(typ), true)
+        }).collect::<Vec<_>>()
+    ));
+
+    let mut wtr = args.writer(schema.clone())?;
+    let mut rdr = rconfig.reader()?;
+    let mut columns: Vec<Column> =
+        types.iter().map(|&typ| Column::new(typ)).collect();
+    let mut record = csv::ByteRecord::new();
+    let mut nrecords = 0;
+    while rdr.read_byte_record(&mut record)? {
+        for (column, field) in columns.iter_mut().zip(&record) {
+            column.push(field);
+        }
+        nrecords += 1;
+        if nrecords == args.flag_batch_size {
+            wtr.write(finish_batch(&schema, &mut columns)?)?;
+            nrecords = 0;
+        }
+    }
+    if nrecords > 0 {
+        wtr.write(finish_batch(&schema, &mut columns)?)?;
+    }
+    wtr.finish()
+}
+
+impl Args {
+    fn writer(&self, schema: SchemaRef) -> CliResult<Writer> {
+        let file = fs::File::create(&self.arg_output)?;
+        if self.cmd_parquet {
+            let props = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .set_max_row_group_row_count(Some(self.flag_batch_size))
+                .build();
+            let wtr = ArrowWriter::try_new(file, schema, Some(props))?;
+            Ok(Writer::Parquet(wtr))
+        } else {
+            Ok(Writer::Arrow(FileWriter::try_new(file, &schema)?))
+        }
+    }
+}
+
+/// Infer the type of each column from every record in `rdr`.
+fn infer_types(
+    rdr: &mut csv::Reader<Box<dyn io::Read>>,
+    ncols: usize,
+) -> CliResult<Vec<FieldType>> {
+    let mut types = vec![FieldType::default(); ncols];
+    let mut record = csv::ByteRecord::new();
+    while rdr.read_byte_record(&mut record)? {
+        for (typ, field) in types.iter_mut().zip(&record) {
+            typ.merge(FieldType::from_sample(field));
+        }
+    }
+    Ok(types)
+}
+
+fn data_type(typ: FieldType) -> DataType {
+    match typ {
+        FieldType::TInteger => DataType::Int64,
+        FieldType::TFloat => DataType::Float64,
+        FieldType::TDate => DataType::Date32,
+        FieldType::TDateTime => {
+            DataType::Timestamp(TimeUnit::Millisecond, Some(UTC.into()))
+        }
+        FieldType::TNull | FieldType::TUnicode => DataType::Utf8,
+        FieldType::TUnknown => DataType::Binary,
+    }
+}
+
+fn finish_batch(
+    schema: &SchemaRef,
+    columns: &mut [Column],
+) -> CliResult<RecordBatch> {
+    let arrays = columns.iter_mut().map(|c| c.finish()).collect();
+    Ok(RecordBatch::try_new(schema.clone(), arrays)?)
+}
+
+/// A builder for one column of a batch.
+enum Column {
+    Integer(Int64Builder),
+    Float(Float64Builder),
+    Date(Date32Builder),
+    DateTime(TimestampMillisecondBuilder),
+    Unicode(StringBuilder),
+    Binary(BinaryBuilder),
+}
+
+impl Column {
+    fn new(typ: FieldType) -> Column {
+        match typ {
+            FieldType::TInteger => Column::Integer(Int64Builder::new()),
+            FieldType::TFloat => Column::Float(Float64Builder::new()),
+            FieldType::TDate => Column::Date(Date32Builder::new()),
+            FieldType::TDateTime => {
+                Column::DateTime(TimestampMillisecondBuilder::new())
+            }
+            FieldType::TNull | FieldType::TUnicode => {
+                Column::Unicode(StringBuilder::new())
+            }
+            FieldType::TUnknown => Column::Binary(BinaryBuilder::new()),
+        }
+    }
+
+    /// Add a field to this column. Empty fields are added as nulls.
+    ///
+    /// Since the column's type was inferred from every field, parsing a
+    /// field can't fail.
+    fn push(&mut self, field: &[u8]) {
+        if field.is_empty() {
+            return self.push_null();
+        }
+        match *self {
+            Column::Integer(ref mut b) => b.append_option(parse(field)),
+            Column::Float(ref mut b) => b.append_option(parse(field)),
+            Column::Date(ref mut b) => {
+                b.append_option(parse_date_millis(field).map(|ms| {
+                    ms.div_euclid(MILLIS_PER_DAY) as i32
+                }))
+            }
+            Column::DateTime(ref mut b) => {
+                b.append_option(parse_date_millis(field))
+            }
+            Column::Unicode(ref mut b) => {
+                b.append_option(str::from_utf8(field).ok())
+            }
+            Column::Binary(ref mut b) => b.append_value(field),
+        }
+    }
+
+    fn push_null(&mut self) {
+        match *self {
+            Column::Integer(ref mut b) => b.append_null(),
+            Column::Float(ref mut b) => b.append_null(),
+            Column::Date(ref mut b) => b.append_null(),
+            Column::DateTime(ref mut b) => b.append_null(),
+            Column::Unicode(ref mut b) => b.append_null(),
+            Column::Binary(ref mut b) => b.append_null(),
+        }
+    }
+
+    /// Build an array of the fields added so far, and reset the builder.
+    fn finish(&mut self) -> ArrayRef {
+        match *self {
+            Column::DateTime(ref mut b) => {
+                Arc::new(b.finish().with_timezone(UTC))
+            }
+            Column::Integer(ref mut b) => ArrayBuilder::finish(b),
+            Column::Float(ref mut b) => ArrayBuilder::finish(b),
+            Column::Date(ref mut b) => ArrayBuilder::finish(b),
+            Column::Unicode(ref mut b) => ArrayBuilder::finish(b),
+            Column::Binary(ref mut b) => ArrayBuilder::finish(b),
+        }
+    }
+}
+
+fn parse<T: str::FromStr>(field: &[u8]) -> Option<T> {
+    str::from_utf8(field).ok().and_then(|s| s.parse().ok())
+}
+
+enum Writer {
+    Parquet(ArrowWriter<fs::File>),
+    Arrow(FileWriter<fs::File>),
+}
+
+impl Writer {
+    fn write(&mut self, batch: RecordBatch) -> CliResult<()> {
+        match *self {
+            Writer::Parquet(ref mut w) => w.write(&batch)?,
+            Writer::Arrow(ref mut w) => w.write(&batch)?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> CliResult<()> {
+        match self {
+            Writer::Parquet(w) => { w.close()?; }
+            Writer::Arrow(mut w) => w.finish()?,
+        }
+        Ok(())
+    }
+}
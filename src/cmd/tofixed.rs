@@ -0,0 +1,151 @@
+use std::io;
+use std::str;
+
+use csv;
+
+use CliResult;
+use config::{Config, Delimiter, Output};
+use util::{self, FixedLayout, FixedWidths};
+
+static USAGE: &'static str = "
+Convert CSV data to fixed width data.
+
+Each field is padded with spaces or truncated to the width of its column.
+The widths are given either as a list with --widths, or as a spec file (in
+the same format as 'xsv fromfixed') with --spec. When neither is given, the
+width of each column is the width of its widest value, which requires reading
+the CSV data twice. (When reading from stdin, it is kept in memory instead.)
+
+Widths are measured in characters. (If a field is not valid UTF-8, then it
+is measured in bytes.)
+
+The header row is written as the first line, unless --no-headers is given.
+
+Usage:
+    xsv tofixed [options] [<input>]
+    xsv tofixed --help
+
+tofixed options:
+    -w, --widths <arg>     A comma separated list of column widths,
+                           e.g., 10,5,20.
+    --spec <file>          A CSV file describing the columns.
+    --right                Align fields to the right instead of the left.
+
+Common options:
+    -h, --help             Display this message
+    -o, --output <file>    Write output to <file> instead of stdout.
+    -n, --no-headers       When set, the first row will NOT be interpreted
+                           as column names, and will be written like any
+                           other row.
+    -d, --delimiter <arg>  The field delimiter for reading CSV data.
+                           Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
+";
+
+#[derive(Deserialize)]
+struct Args {
+    arg_input: Option<String>,
+    flag_widths: Option<FixedWidths>,
+    flag_spec: Option<String>,
+    flag_right: bool,
+    flag_output: Option<String>,
+    flag_no_headers: bool,
+    flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
+}
+
+pub fn run(argv: &[&str]) -> CliResult<()> {
+    let args: Args = util::get_args(USAGE, argv)?;
+    let rconfig = Config::new(&args.arg_input)
+        .delimiter(args.flag_delimiter)
+        .no_headers(args.flag_no_headers)
+        .sniff(args.flag_sniff);
+    let layout = match (args.flag_widths.as_ref(), args.flag_spec.as_ref()) {
+        (Some(_), Some(_)) => {
+            return fail_usage!("--widths and --spec cannot be used together");
+        }
+        (Some(widths), None) => Some(widths.0.clone()),
+        (None, Some(spec)) => {
+            Some(FixedLayout::from_spec(spec)?.widths)
+        }
+        (None, None) => None,
+    };
+
+    let mut rdr = rconfig.reader()?;
+    let mut wtr = io::BufWriter::new(
+        Config::new(&args.flag_output).io_writer()?);
+    let headers = rdr.byte_headers()?.clone();
+    let mut buffered = vec![];
+    let widths = match layout {
+        Some(widths) => widths,
+        None => {
+            // Measure every field, including the header row.
+            let mut widths: Vec<usize> =
+                headers.iter().map(util::fixed_width).collect();
+            for record in rdr.byte_records() {
+                let record = record?;
+                for (w, field) in widths.iter_mut().zip(&record) {
+                    *w = (*w).max(util::fixed_width(field));
+                }
+                if rconfig.is_std() {
+                    buffered.push(record);
+                }
+            }
+            if !rconfig.is_std() {
+                rdr = rconfig.reader()?;
+                rdr.byte_headers()?;
+            }
+            widths
+        }
+    };
+
+    if !rconfig.no_headers {
+        args.write_line(&mut wtr, &widths, &headers)?;
+    }
+    if rconfig.is_std() && !buffered.is_empty() {
+        for record in &buffered {
+            args.write_line(&mut wtr, &widths, record)?;
+        }
+    } else {
+        let mut record = csv::ByteRecord::new();
+        while rdr.read_byte_record(&mut record)? {
+            args.write_line(&mut wtr, &widths, &record)?;
+        }
+    }
+    wtr.finish()?;
+    Ok(())
+}
+
+impl Args {
+    fn write_line<W: io::Write>(
+        &self,
+        wtr: &mut W,
+        widths: &[usize],
+        record: &csv::ByteRecord,
+    ) -> io::Result<()> {
+        for (i, &width) in widths.iter().enumerate() {
+            let field = truncate(record.get(i).unwrap_or(b""), width);
+            let fill = vec![b' '; width - util::fixed_width(field)];
+            if self.flag_right {
+                wtr.write_all(&fill)?;
+                wtr.write_all(field)?;
+            } else {
+                wtr.write_all(field)?;
+                wtr.write_all(&fill)?;
+            }
+        }
+        wtr.write_all(b"\n")
+    }
+}
+
+/// Truncate a field to at most `width` characters.
+fn truncate(field: &[u8], width: usize) -> &[u8] {
+    match str::from_utf8(field) {
+        Ok(s) => match s.char_indices().nth(width) {
+            None => field,
+            Some((i, _)) => &field[..i],
+        },
+        Err(_) => &field[..width.min(field.len())],
+    }
+}
@@ -0,0 +1,229 @@
+use std::str;
+
+use csv;
+use rusqlite::{self, Connection};
+use rusqlite::types::Value;
+use stats::Commute;
+
+use CliResult;
+use config::{Config, Delimiter};
+use select::SelectColumns;
+use types::FieldType;
+use util;
+
+static USAGE: &'static str = "
+Load CSV data into a table in a SQLite database.
+
+The table is created with a column for each column in the CSV data. The type
+of each column is inferred the same way as in 'xsv stats': Integer columns
+are created as INTEGER, Float columns as REAL and columns with invalid UTF-8
+as BLOB. All other columns (including dates) are created as TEXT. Empty
+fields are inserted as NULL.
+
+Since types must be known before the table is created, the CSV data is read
+twice. When reading from stdin, the CSV data is kept in memory instead.
+
+Records are inserted in transactions of --batch-size records each.
+
+By default, it is an error if the table already exists. With --append,
+records are added to an existing table by column name, and with --replace,
+an existing table is dropped first. With --replace, the table is dropped and
+loaded in a single transaction (regardless of --batch-size), so the existing
+table is kept if loading fails.
+
+Usage:
+    xsv tosqlite [options] <db> <table> [<input>]
+    xsv tosqlite --help
+
+tosqlite options:
+    --append               Append records to the table if it already exists.
+    --replace              Replace the table if it already exists.
+    -s, --select <arg>     Create an index on each of the selected columns.
+                           See 'xsv select --help' for the format details.
+    --batch-size <arg>     The number of records inserted in each
+                           transaction. [default: 50000]
+
+Common options:
+    -h, --help             Display this message
+    -n, --no-headers       When set, the first row will NOT be interpreted
+                           as column names. Columns are then named by their
+                           1-based index.
+    -d, --delimiter <arg>  The field delimiter for reading CSV data.
+                           Must be a single character. (default: ,)
+    --sniff                Infer the delimiter, quoting and header row of
+                           the input from its start. See 'xsv sniff'.
+";
+
+#[derive(Deserialize)]
+struct Args {
+    arg_db: String,
+    arg_table: String,
+    arg_input: Option<String>,
+    flag_append: bool,
+    flag_replace: bool,
+    flag_select: Option<SelectColumns>,
+    flag_batch_size: usize,
+    flag_no_headers: bool,
+    flag_delimiter: Option<Delimiter>,
+    flag_sniff: bool,
+}
+
+pub fn run(argv: &[&str]) -> CliResult<()> {
+    let args: Args = util::get_args(USAGE, argv)?;
+    if args.flag_append && args.flag_replace {
+        return fail_usage!("--append and --replace cannot be used together");
+    }
+    if args.flag_batch_size == 0 {
+        return fail_usage!("--batch-size must be greater than 0");
+    }
+    let rconfig = Config::new(&args.arg_input)
+        .delimiter(args.flag_delimiter)
+        .no_headers(args.flag_no_headers)
+        .sniff(args.flag_sniff);
+
+    let mut rdr = rconfig.reader()?;
+    let headers = rdr.byte_headers()?.clone();
+    let index_cols = match args.flag_select {
+        None => vec![],
+        Some(ref sel) => {
+            sel.selection(&headers, !rconfig.no_headers)?.to_vec()
+        }
+    };
+    let names: Vec<String> = headers.iter().enumerate().map(|(i, name)| {
+        if rconfig.no_headers {
+            (i+1).to_string()
+        } else {
+            String::from_utf8_lossy(name).into_owned()
+        }
+    }).collect();
+
+    // When reading from stdin, keep records around for the second pass.
+    let mut buffered = vec![];
+    let mut types = vec![FieldType::default(); headers.len()];
+    let mut record = csv::ByteRecord::new();
+    while rdr.read_byte_record(&mut record)? {
+        for (typ, field) in types.iter_mut().zip(&record) {
+            typ.merge(FieldType::from_sample(field));
+        }
+        if rconfig.is_std() {
+            buffered.push(record.clone());
+        }
+    }
+
+    let conn = Connection::open(&args.arg_db)?;
+    // With --replace, everything is done in a single transaction, so that
+    // the existing table is only dropped if the new one is loaded.
+    if args.flag_replace {
+        conn.execute_batch("BEGIN")?;
+    }
+    args.create_table(&conn, &names, &types)?;
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        quote(&args.arg_table),
+        names.iter().map(|n| quote(n)).collect::<Vec<_>>().join(", "),
+        vec!["?"; names.len()].join(", "),
+    );
+    let mut insert = conn.prepare(&sql)?;
+    let mut ninserted = 0;
+    let mut insert_record = |record: &csv::ByteRecord| -> CliResult<()> {
+        if !args.flag_replace && ninserted % args.flag_batch_size == 0 {
+            if ninserted > 0 {
+                conn.execute_batch("COMMIT")?;
+            }
+            conn.execute_batch("BEGIN")?;
+        }
+        let values = record.iter().zip(&types).map(|(f, &t)| value(f, t));
+        insert.execute(rusqlite::params_from_iter(values))?;
+        ninserted += 1;
+        Ok(())
+    };
+    if rconfig.is_std() {
+        for record in &buffered {
+            insert_record(record)?;
+        }
+    } else {
+        let mut rdr = rconfig.reader()?;
+        while rdr.read_byte_record(&mut record)? {
+            insert_record(&record)?;
+        }
+    }
+    if !args.flag_replace && ninserted > 0 {
+        conn.execute_batch("COMMIT")?;
+    }
+
+    for &i in &index_cols {
+        conn.execute_batch(&format!(
+            "CREATE INDEX IF NOT EXISTS {} ON {} ({})",
+            quote(&format!("{}_{}", args.arg_table, names[i])),
+            quote(&args.arg_table),
+            quote(&names[i]),
+        ))?;
+    }
+    if args.flag_replace {
+        conn.execute_batch("COMMIT")?;
+    }
+    Ok(())
+}
+
+impl Args {
+    fn create_table(
+        &self,
+        conn: &Connection,
+        names: &[String],
+        types: &[FieldType],
+    ) -> CliResult<()> {
+        if self.flag_replace {
+            conn.execute_batch(
+                &format!("DROP TABLE IF EXISTS {}", quote(&self.arg_table)))?;
+        }
+        let columns: Vec<String> = names.iter().zip(types).map(|(n, &t)| {
+            format!("{} {}", quote(n), sql_type(t))
+        }).collect();
+        conn.execute_batch(&format!(
+            "CREATE TABLE {}{} ({})",
+            if self.flag_append { "IF NOT EXISTS " } else { "" },
+            quote(&self.arg_table),
+            columns.join(", "),
+        ))?;
+        Ok(())
+    }
+}
+
+fn sql_type(typ: FieldType) -> &'static str {
+    match typ {
+        FieldType::TInteger => "INTEGER",
+        FieldType::TFloat => "REAL",
+        FieldType::TUnknown => "BLOB",
+        FieldType::TNull
+        | FieldType::TUnicode
+        | FieldType::TDate
+        | FieldType::TDateTime => "TEXT",
+    }
+}
+
+/// Convert a field to a SQL value of the column's type.
+///
+/// Since the column's type was inferred from every field, parsing a field
+/// can't fail.
+fn value(field: &[u8], typ: FieldType) -> Value {
+    if field.is_empty() {
+        return Value::Null;
+    }
+    let s = match str::from_utf8(field) {
+        Ok(s) => s,
+        Err(_) => return Value::Blob(field.to_vec()),
+    };
+    match typ {
+        FieldType::TInteger => {
+            s.parse().map(Value::Integer).unwrap_or(Value::Null)
+        }
+        FieldType::TFloat => s.parse().map(Value::Real).unwrap_or(Value::Null),
+        FieldType::TUnknown => Value::Blob(field.to_vec()),
+        _ => Value::Text(s.to_owned()),
+    }
+}
+
+/// Quote a SQL identifier.
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
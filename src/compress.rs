@@ -0,0 +1,421 @@
+/*!
+Transparent compression of input and output.
+
+Compressed input is detected from the first few bytes of the data, so that it
+works the same way for files and for stdin. Compressed output is chosen by the
+extension of the output file, e.g., `.csv.gz`.
+
+Gzip and zstd data may be made of several blocks (gzip members or zstd frames)
+that can each be decompressed on their own, e.g., a file compressed by `bgzip`
+or `pzstd`. An index of such a file records where its blocks start (see
+`BlockScanner`), which is enough to read it from any offset without
+decompressing everything before it (see `BlockReader`).
+*/
+
+use std::fs;
+use std::io::{self, BufRead, Read, Seek};
+use std::path::{Path, PathBuf};
+
+use bzip2;
+use flate2;
+use xz2;
+use zstd;
+
+use config::Output;
+
+/// A supported compression format.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+/// The magic bytes at the start of data in each format.
+const MAGIC: &[(Compression, &[u8])] = &[
+    (Compression::Gzip, b"\x1f\x8b"),
+    (Compression::Zstd, b"\x28\xb5\x2f\xfd"),
+    (Compression::Bzip2, b"BZh"),
+    (Compression::Xz, b"\xfd7zXZ\x00"),
+];
+
+/// The length of the longest magic number.
+pub const MAGIC_LEN: usize = 6;
+
+impl Compression {
+    /// Detect the compression format of data that starts with `bytes`.
+    pub fn from_magic(bytes: &[u8]) -> Option<Compression> {
+        MAGIC.iter()
+             .find(|&&(c, magic)| {
+                 // Bzip2's magic is followed by the block size, 1 to 9.
+                 bytes.starts_with(magic)
+                 && (c != Compression::Bzip2
+                     || matches!(bytes.get(3), Some(b'1'..=b'9')))
+             })
+             .map(|&(c, _)| c)
+    }
+
+    /// Choose a compression format from the extension of `path`.
+    pub fn from_path(path: &Path) -> Option<Compression> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match &*ext {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "bz2" => Some(Compression::Bzip2),
+            "xz" => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+
+    /// Wrap `wtr` so that data written to it is compressed.
+    ///
+    /// The compressed stream is ended by `Output::finish`, after which
+    /// nothing more can be written to it.
+    pub fn writer<W: io::Write + 'static>(
+        self,
+        wtr: W,
+    ) -> io::Result<Box<dyn Output>> {
+        Ok(match self {
+            Compression::Gzip => Box::new(Finishing::new(
+                flate2::write::GzEncoder::new(
+                    wtr, flate2::Compression::default()))),
+            Compression::Zstd => {
+                Box::new(Finishing::new(zstd::Encoder::new(wtr, 0)?))
+            }
+            Compression::Bzip2 => Box::new(Finishing::new(
+                bzip2::write::BzEncoder::new(
+                    wtr, bzip2::Compression::default()))),
+            Compression::Xz => {
+                Box::new(Finishing::new(xz2::write::XzEncoder::new(wtr, 6)))
+            }
+        })
+    }
+}
+
+/// A writer of compressed data, whose stream can be finished without
+/// dropping it.
+trait Encoder: io::Write {
+    /// Write the end of the compressed stream and flush it.
+    fn finish_stream(&mut self) -> io::Result<()>;
+}
+
+impl<W: io::Write> Encoder for flate2::write::GzEncoder<W> {
+    fn finish_stream(&mut self) -> io::Result<()> {
+        self.try_finish()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: io::Write> Encoder for zstd::Encoder<'static, W> {
+    fn finish_stream(&mut self) -> io::Result<()> {
+        self.do_finish()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: io::Write> Encoder for bzip2::write::BzEncoder<W> {
+    fn finish_stream(&mut self) -> io::Result<()> {
+        self.try_finish()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: io::Write> Encoder for xz2::write::XzEncoder<W> {
+    fn finish_stream(&mut self) -> io::Result<()> {
+        self.try_finish()?;
+        self.get_mut().flush()
+    }
+}
+
+/// A compressing writer whose stream is ended by `Output::finish`, so that
+/// an error writing the end of the stream is reported instead of being lost
+/// when the encoder is dropped.
+struct Finishing<E: Encoder> {
+    enc: E,
+    finished: bool,
+}
+
+impl<E: Encoder> Finishing<E> {
+    fn new(enc: E) -> Finishing<E> {
+        Finishing { enc: enc, finished: false }
+    }
+}
+
+impl<E: Encoder> io::Write for Finishing<E> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "cannot write compressed data after it has been finished",
+            ));
+        }
+        self.enc.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.enc.flush()
+    }
+}
+
+impl<E: Encoder> Output for Finishing<E> {
+    fn finish(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.finished = true;
+            self.enc.finish_stream()?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: Encoder> Drop for Finishing<E> {
+    fn drop(&mut self) {
+        // Errors can't be reported here. Writers are finished before they
+        // are dropped when it matters.
+        if !self.finished {
+            let _ = self.enc.finish_stream();
+        }
+    }
+}
+
+/// Wrap `rdr` so that compressed data read from it is decompressed.
+/// Uncompressed data is passed through as is.
+pub fn reader<R: Read + 'static>(mut rdr: R) -> io::Result<Box<dyn Read>> {
+    // Peek at the first bytes, and then put them back in front of the rest
+    // of the data.
+    let mut magic = Vec::with_capacity(MAGIC_LEN);
+    (&mut rdr).take(MAGIC_LEN as u64).read_to_end(&mut magic)?;
+    let compression = Compression::from_magic(&magic);
+    let rdr = io::Cursor::new(magic).chain(rdr);
+    match compression {
+        None => Ok(Box::new(rdr)),
+        Some(c) => decoder(c, rdr),
+    }
+}
+
+/// Wrap `rdr`, which reads data compressed with `compression`, so that the
+/// data read from it is decompressed.
+fn decoder<R: Read + 'static>(
+    compression: Compression,
+    rdr: R,
+) -> io::Result<Box<dyn Read>> {
+    Ok(match compression {
+        Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(rdr)),
+        Compression::Zstd => Box::new(zstd::Decoder::new(rdr)?),
+        Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(rdr)),
+        Compression::Xz => {
+            Box::new(xz2::read::XzDecoder::new_multi_decoder(rdr))
+        }
+    })
+}
+
+/// Returns the format of the file at `path` if it is compressed.
+pub fn detect_file(path: &Path) -> io::Result<Option<Compression>> {
+    let mut magic = Vec::with_capacity(MAGIC_LEN);
+    ::std::fs::File::open(path)?
+        .take(MAGIC_LEN as u64)
+        .read_to_end(&mut magic)?;
+    Ok(Compression::from_magic(&magic))
+}
+
+/// Remove a compression extension from `path`, if it has one. For example,
+/// `data.tsv.gz` becomes `data.tsv`.
+pub fn strip_extension(path: &Path) -> PathBuf {
+    if Compression::from_path(path).is_some() {
+        path.with_extension("")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// A block of compressed data that can be decompressed on its own, i.e., a
+/// gzip member or a zstd frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Block {
+    /// The offset of the block in the compressed data.
+    pub compressed: u64,
+    /// The offset of the block's contents in the decompressed data.
+    pub decompressed: u64,
+}
+
+/// A reader of decompressed gzip or zstd data, which also records where
+/// each block of the compressed data starts.
+pub struct BlockScanner<R: BufRead> {
+    compression: Compression,
+    /// The compressed data, when it is between two blocks.
+    rdr: Option<R>,
+    decoder: Option<BlockDecoder<R>>,
+    blocks: Vec<Block>,
+    /// The number of bytes of decompressed data read so far.
+    pos: u64,
+}
+
+enum BlockDecoder<R: BufRead> {
+    Gzip(flate2::bufread::GzDecoder<R>),
+    Zstd(zstd::stream::read::Decoder<'static, R>),
+}
+
+impl<R: BufRead + Seek> BlockScanner<R> {
+    /// Read the data compressed with `compression` from `rdr`, which must be
+    /// at the start of a block.
+    ///
+    /// Only gzip and zstd data is supported.
+    pub fn new(compression: Compression, rdr: R) -> io::Result<Self> {
+        match compression {
+            Compression::Gzip | Compression::Zstd => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("{:?} compressed data can't be read in blocks \
+                             (only gzip and zstd can)", compression),
+                ));
+            }
+        }
+        Ok(BlockScanner {
+            compression: compression,
+            rdr: Some(rdr),
+            decoder: None,
+            blocks: vec![],
+            pos: 0,
+        })
+    }
+
+    /// The blocks found so far (all of them, once everything has been
+    /// read). Blocks without any data are left out.
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    fn start_block(&mut self) -> io::Result<bool> {
+        let mut rdr = self.rdr.take().unwrap();
+        if rdr.fill_buf()?.is_empty() {
+            self.rdr = Some(rdr);
+            return Ok(false);
+        }
+        self.blocks.push(Block {
+            compressed: rdr.stream_position()?,
+            decompressed: self.pos,
+        });
+        self.decoder = Some(match self.compression {
+            Compression::Gzip => {
+                BlockDecoder::Gzip(flate2::bufread::GzDecoder::new(rdr))
+            }
+            _ => {
+                let dec = zstd::stream::read::Decoder::with_buffer(rdr)?;
+                BlockDecoder::Zstd(dec.single_frame())
+            }
+        });
+        Ok(true)
+    }
+
+    fn finish_block(&mut self) {
+        self.rdr = Some(match self.decoder.take().unwrap() {
+            BlockDecoder::Gzip(dec) => dec.into_inner(),
+            BlockDecoder::Zstd(dec) => dec.finish(),
+        });
+        if self.blocks.last().map(|b| b.decompressed) == Some(self.pos) {
+            self.blocks.pop();
+        }
+    }
+}
+
+impl<R: BufRead + Seek> Read for BlockScanner<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.decoder.is_none() && !self.start_block()? {
+                return Ok(0);
+            }
+            let n = match *self.decoder.as_mut().unwrap() {
+                BlockDecoder::Gzip(ref mut dec) => dec.read(buf)?,
+                BlockDecoder::Zstd(ref mut dec) => dec.read(buf)?,
+            };
+            if n > 0 {
+                self.pos += n as u64;
+                return Ok(n);
+            }
+            self.finish_block();
+        }
+    }
+}
+
+/// A reader of the decompressed data of a compressed file, which can seek
+/// to any offset in it given the blocks of the file (see `BlockScanner`).
+///
+/// Seeking decompresses the file from the start of the block with the
+/// offset, unless the reader is already in that block, before the offset.
+/// Only seeking from the start is supported.
+pub struct BlockReader {
+    file: fs::File,
+    compression: Compression,
+    blocks: Vec<Block>,
+    rdr: Box<dyn Read>,
+    /// The offset of the reader in the decompressed data.
+    pos: u64,
+}
+
+impl BlockReader {
+    pub fn new(
+        compression: Compression,
+        mut file: fs::File,
+        blocks: Vec<Block>,
+    ) -> io::Result<BlockReader> {
+        file.seek(io::SeekFrom::Start(0))?;
+        let rdr = decoder(compression, file.try_clone()?)?;
+        Ok(BlockReader {
+            file: file,
+            compression: compression,
+            blocks: blocks,
+            rdr: rdr,
+            pos: 0,
+        })
+    }
+
+    /// The block that contains the offset `pos` of the decompressed data.
+    fn block(&self, pos: u64) -> Block {
+        let i = match self.blocks.binary_search_by_key(
+                &pos, |b| b.decompressed) {
+            Ok(i) => i,
+            Err(0) => return Block { compressed: 0, decompressed: 0 },
+            Err(i) => i - 1,
+        };
+        self.blocks[i]
+    }
+}
+
+impl Read for BlockReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.rdr.read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for BlockReader {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            io::SeekFrom::Start(pos) => pos,
+            io::SeekFrom::Current(0) => return Ok(self.pos),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "compressed data can only be seeked from its start",
+                ));
+            }
+        };
+        let block = self.block(pos);
+        if pos < self.pos || block.decompressed > self.pos {
+            self.file.seek(io::SeekFrom::Start(block.compressed))?;
+            self.rdr = decoder(self.compression, self.file.try_clone()?)?;
+            self.pos = block.decompressed;
+        }
+        let mut skip = (&mut self.rdr).take(pos - self.pos);
+        self.pos += io::copy(&mut skip, &mut io::sink())?;
+        Ok(self.pos)
+    }
+}
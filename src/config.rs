@@ -2,16 +2,24 @@ use std::ascii::AsciiExt;
 use std::borrow::ToOwned;
 use std::env;
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, Read, Seek, Write};
 use std::ops::Deref;
 use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
 
+use chunk::Chunked;
+use compress::{self, BlockReader, BlockScanner, Compression};
 use csv;
-use index::Indexed;
+use csv_core;
+use encoding::{self, InputEncoding};
+use index::{IndexHeader, Indexed, IndexedData, IndexedFile};
+use json::{JsonWriter, OutputFormat};
+use malformed::{OnError, RecordFilter};
 use serde::de::{Deserializer, Deserialize, Error};
 
 use CliResult;
 use select::{SelectColumns, Selection};
+use sniff::{self, Dialect};
 use util;
 
 
@@ -53,12 +61,117 @@ impl<'de> Deserialize<'de> for Delimiter {
     }
 }
 
-#[derive(Debug)]
+/// Options that may be given with any command. They apply to every
+/// `Config`.
+#[derive(Clone, Debug, Default)]
+pub struct GlobalOptions {
+    pub encoding: Option<InputEncoding>,
+    pub on_error: OnError,
+    pub output_format: Option<OutputFormat>,
+}
+
+static GLOBAL_OPTIONS: OnceLock<GlobalOptions> = OnceLock::new();
+
+/// The number of bytes sampled by `Config::sniff`.
+const SNIFF_SAMPLE_LEN: usize = 64 * (1<<10);
+
+/// Dialects inferred by `Config::sniff`, by input path (`None` is stdin).
+/// Commands may create the same `Config` many times, and stdin can only be
+/// sampled once.
+static SNIFFED: Mutex<Vec<(Option<PathBuf>, Dialect)>> = Mutex::new(vec![]);
+
+/// The start of stdin, read by `Config::sniff`. It is read again by
+/// `Config::io_reader`.
+static STDIN_SAMPLE: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+impl GlobalOptions {
+    /// Remove global options from `argv`, returning them along with the
+    /// remaining arguments. Global options are only recognized before the
+    /// command name, so a command's own arguments are never taken for them.
+    pub fn extract(
+        argv: Vec<String>,
+    ) -> Result<(GlobalOptions, Vec<String>), String> {
+        let mut opts = GlobalOptions::default();
+        let mut rest = Vec::with_capacity(argv.len());
+        let mut args = argv.into_iter().peekable();
+        // The program name.
+        rest.extend(args.next());
+        while let Some(arg) = args.peek().cloned() {
+            let (name, value) = match arg.find('=') {
+                Some(i) if arg.starts_with("--") => {
+                    (arg[..i].to_owned(), Some(arg[i+1..].to_owned()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let names = ["--encoding", "--on-error", "--output-format"];
+            if !names.contains(&&*name) {
+                break;
+            }
+            args.next();
+            let value = match value.or_else(|| args.next()) {
+                None => return Err(format!("{} requires an argument.", name)),
+                Some(value) => value,
+            };
+            if name == "--encoding" {
+                opts.encoding = Some(InputEncoding::from_label(&value)?);
+            } else if name == "--on-error" {
+                opts.on_error = OnError::from_arg(&value)?;
+            } else {
+                opts.output_format = Some(OutputFormat::from_arg(&value)?);
+            }
+        }
+        rest.extend(args);
+        Ok((opts, rest))
+    }
+
+    /// Set the global options of this process. This should be called at
+    /// most once, before any `Config` is created.
+    pub fn set(self) {
+        let _ = GLOBAL_OPTIONS.set(self);
+    }
+
+    fn get() -> GlobalOptions {
+        GLOBAL_OPTIONS.get().cloned().unwrap_or_default()
+    }
+}
+
+/// The output of a command.
+///
+/// Some output has to be ended once everything has been written to it,
+/// e.g., the trailer of compressed data or the closing bracket of a JSON
+/// array. Flushing the output doesn't do that, since more may be written
+/// after a flush. Instead, `finish` ends it and reports any error doing so.
+pub trait Output: io::Write {
+    /// Flush and end the output. Nothing more can be written after this.
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl Output for io::Stdout {}
+
+impl Output for fs::File {}
+
+impl<W: Output + ?Sized> Output for Box<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        (**self).finish()
+    }
+}
+
+impl<W: Output> Output for io::BufWriter<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.get_mut().finish()
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Config {
     path: Option<PathBuf>, // None implies <stdin>
     idx_path: Option<PathBuf>,
     select_columns: Option<SelectColumns>,
     delimiter: u8,
+    delimiter_given: bool,
     pub no_headers: bool,
     flexible: bool,
     terminator: csv::Terminator,
@@ -67,6 +180,10 @@ pub struct Config {
     double_quote: bool,
     escape: Option<u8>,
     quoting: bool,
+    encoding: Option<InputEncoding>,
+    on_error: OnError,
+    output_format: Option<OutputFormat>,
+    append: bool,
 }
 
 impl Config {
@@ -76,8 +193,10 @@ impl Config {
             Some(ref s) if s.deref() == "-" => (None, b','),
             Some(ref s) => {
                 let path = PathBuf::from(s);
+                // Look through a compression extension, e.g., `.tsv.gz`.
+                let delim_path = compress::strip_extension(&path);
                 let delim =
-                    if path.extension().map_or(false, |v| v == "tsv") {
+                    if delim_path.extension().map_or(false, |v| v == "tsv") {
                         b'\t'
                     } else {
                         b','
@@ -85,11 +204,13 @@ impl Config {
                 (Some(path), delim)
             }
         };
+        let global = GlobalOptions::get();
         Config {
             path: path,
             idx_path: None,
             select_columns: None,
             delimiter: delim,
+            delimiter_given: false,
             no_headers: false,
             flexible: false,
             terminator: csv::Terminator::Any(b'\n'),
@@ -98,12 +219,17 @@ impl Config {
             double_quote: true,
             escape: None,
             quoting: true,
+            encoding: global.encoding,
+            on_error: global.on_error,
+            output_format: global.output_format,
+            append: false,
         }
     }
 
     pub fn delimiter(mut self, d: Option<Delimiter>) -> Config {
         if let Some(d) = d {
             self.delimiter = d.as_byte();
+            self.delimiter_given = true;
         }
         self
     }
@@ -121,6 +247,12 @@ impl Config {
         self
     }
 
+    /// Add to the end of the output file, instead of replacing it.
+    pub fn append(mut self, yes: bool) -> Config {
+        self.append = yes;
+        self
+    }
+
     pub fn crlf(mut self, yes: bool) -> Config {
         if yes {
             self.terminator = csv::Terminator::CRLF;
@@ -160,6 +292,63 @@ impl Config {
         self
     }
 
+    /// Infer the delimiter, quoting and presence of a header row of the
+    /// input from a sample of it (see the `sniff` module).
+    ///
+    /// This should be called after `delimiter` and `no_headers`, since an
+    /// explicit delimiter or `--no-headers` takes precedence.
+    pub fn sniff(mut self, yes: bool) -> Config {
+        if !yes {
+            return self;
+        }
+        let dialect = match self.sniff_dialect() {
+            Ok(dialect) => dialect,
+            // Any error is reported when the input is read.
+            Err(_) => return self,
+        };
+        if !self.delimiter_given {
+            self.delimiter = dialect.delimiter;
+        }
+        self.quote = dialect.quote;
+        if self.escape.is_none() {
+            self.escape = dialect.escape;
+        }
+        if !dialect.has_headers {
+            self.no_headers = true;
+        }
+        self
+    }
+
+    fn sniff_dialect(&self) -> io::Result<Dialect> {
+        let mut sniffed = SNIFFED.lock().unwrap();
+        if let Some(s) = sniffed.iter().find(|s| s.0 == self.path) {
+            return Ok(s.1.clone());
+        }
+        let rdr = match self.path {
+            Some(_) => self.io_reader()?,
+            None => {
+                let mut raw = vec![];
+                io::stdin().take(SNIFF_SAMPLE_LEN as u64)
+                           .read_to_end(&mut raw)?;
+                *STDIN_SAMPLE.lock().unwrap() = Some(raw.clone());
+                encoding::reader(
+                    compress::reader(io::Cursor::new(raw))?, self.encoding)?
+            }
+        };
+        let mut sample = vec![];
+        // The end of a sample of compressed data can't be decompressed.
+        if let Err(err) = rdr.take(SNIFF_SAMPLE_LEN as u64)
+                             .read_to_end(&mut sample) {
+            if sample.is_empty() {
+                return Err(err);
+            }
+        }
+        let truncated = sample.len() == SNIFF_SAMPLE_LEN;
+        let dialect = sniff::sniff(&sample, truncated);
+        sniffed.push((self.path.clone(), dialect.clone()));
+        Ok(dialect)
+    }
+
     pub fn select(mut self, sel_cols: SelectColumns) -> Config {
         self.select_columns = Some(sel_cols);
         self
@@ -172,10 +361,10 @@ impl Config {
     pub fn selection(
         &self,
         first_record: &csv::ByteRecord,
-    ) -> Result<Selection, String> {
+    ) -> CliResult<Selection> {
         match self.select_columns {
-            None => Err("Config has no 'SelectColums'. Did you call \
-                         Config::select?".to_owned()),
+            None => fail!("Config has no 'SelectColums'. Did you call \
+                           Config::select?"),
             Some(ref sel) => sel.selection(first_record, !self.no_headers),
         }
     }
@@ -192,35 +381,156 @@ impl Config {
         Ok(())
     }
 
+    /// The format of the output, which is given by the `--output-format`
+    /// global option or else by the `XSV_OUTPUT_FORMAT` environment
+    /// variable.
+    pub fn output_format(&self) -> io::Result<OutputFormat> {
+        if let Some(format) = self.output_format {
+            return Ok(format);
+        }
+        match env::var("XSV_OUTPUT_FORMAT") {
+            Err(_) => Ok(OutputFormat::Csv),
+            Ok(f) => OutputFormat::from_arg(&f).map_err(|err| {
+                io::Error::new(io::ErrorKind::InvalidInput,
+                               format!("XSV_OUTPUT_FORMAT: {}", err))
+            }),
+        }
+    }
+
+    /// Create a CSV writer for this configuration.
+    ///
+    /// If the output format is JSON (see `output_format`), then records are
+    /// instead written as JSON objects keyed by the header row (or by the
+    /// index of each field, without a header row).
+    ///
+    /// Flushing the writer does not end JSON or compressed output. Once
+    /// everything has been written, the writer must be passed to
+    /// `Config::finish`, or the end of the output is only written when it
+    /// is dropped and any error doing so is lost.
     pub fn writer(&self)
-                 -> io::Result<csv::Writer<Box<io::Write+'static>>> {
-        Ok(self.from_writer(self.io_writer()?))
+                 -> io::Result<csv::Writer<Box<Output+'static>>> {
+        let (style, numbers) = match self.output_format()? {
+            OutputFormat::Csv => return Ok(self.from_writer(self.io_writer()?)),
+            OutputFormat::Json { style, numbers } => (style, numbers),
+        };
+        if self.append {
+            // The JSON writer needs the headers, and a JSON array can't be
+            // continued.
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "can't append JSON output to an existing file",
+            ));
+        }
+        let json = JsonWriter::new(
+            self.io_writer()?, style, numbers, !self.no_headers);
+        // The JSON writer parses standard CSV, regardless of the delimiter
+        // or quoting rules used for CSV output.
+        Ok(csv::WriterBuilder::new()
+            .flexible(self.flexible)
+            .buffer_capacity(32 * (1<<10))
+            .from_writer(Box::new(json)))
     }
 
+    /// Create a CSV reader for this configuration.
+    ///
+    /// Unless the `--on-error` policy is `fail` (or records may have any
+    /// number of fields), malformed records are skipped (see the
+    /// `malformed` module).
     pub fn reader(&self)
                  -> io::Result<csv::Reader<Box<io::Read+'static>>> {
-        Ok(self.from_reader(self.io_reader()?))
+        let rdr = self.io_reader()?;
+        if self.flexible || self.on_error == OnError::Fail {
+            return Ok(self.from_reader(rdr));
+        }
+        let csv = csv_core::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .quoting(self.quoting)
+            .escape(self.escape)
+            .build();
+        let filter = RecordFilter::new(
+            rdr, csv, self.on_error.clone(), self.path.clone());
+        Ok(self.from_reader(Box::new(filter)))
     }
 
     pub fn reader_file(&self) -> io::Result<csv::Reader<fs::File>> {
+        self.check_offsets_allowed()?;
         match self.path {
             None => Err(io::Error::new(
                 io::ErrorKind::Other, "Cannot use <stdin> here",
             )),
-            Some(ref p) => fs::File::open(p).map(|f| self.from_reader(f)),
+            Some(ref p) => {
+                if compress::detect_file(p)?.is_some() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("Cannot use compressed file {} here",
+                                p.display()),
+                    ));
+                }
+                if encoding::transcodes_file(p, self.encoding)? {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("Cannot use {} here, since it is not UTF-8",
+                                p.display()),
+                    ));
+                }
+                fs::File::open(p).map(|f| self.from_reader(f))
+            }
         }
     }
 
-    pub fn index_files(&self)
-           -> io::Result<Option<(csv::Reader<fs::File>, fs::File)>> {
-        let (csv_file, idx_file) = match (&self.path, &self.idx_path) {
+    /// Read the CSV data of a file compressed in blocks, which `data` is at
+    /// the start of, and find its blocks (see `compress::BlockScanner`).
+    pub fn reader_blocks(
+        &self,
+        compression: Compression,
+        data: fs::File,
+    ) -> io::Result<csv::Reader<BlockScanner<io::BufReader<fs::File>>>> {
+        self.check_offsets_allowed()?;
+        if let Some(ref p) = self.path {
+            if encoding::transcodes_file(p, self.encoding)? {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("Cannot use {} here, since it is not UTF-8",
+                            p.display()),
+                ));
+            }
+        }
+        let data = io::BufReader::new(data);
+        Ok(self.from_reader(BlockScanner::new(compression, data)?))
+    }
+
+    /// Readers that record or seek to byte offsets in the input can't skip
+    /// malformed records, so they refuse any `--on-error` policy but
+    /// `fail`.
+    fn check_offsets_allowed(&self) -> io::Result<()> {
+        if self.on_error == OnError::Fail {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--on-error can't be used here, since the input is read by \
+             byte offset and malformed records can't be skipped",
+        ))
+    }
+
+    /// Open the CSV data and the index file of the input, if it has an
+    /// index.
+    fn index_files(&self) -> CliResult<Option<(fs::File, fs::File)>> {
+        let files = match (&self.path, &self.idx_path) {
             (&None, &None) => return Ok(None),
-            (&None, &Some(_)) => return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Cannot use <stdin> with indexes",
-                // Some(format!("index file: {}", p.display()))
-            )),
+            // Skipping malformed records needs a sequential reader, so the
+            // index is left unused.
+            _ if self.on_error != OnError::Fail => return Ok(None),
+            (&None, &Some(_)) => {
+                return fail!("Cannot use <stdin> with indexes");
+            }
             (&Some(ref p), &None) => {
+                // Offsets in an index can't be used to seek in transcoded
+                // data.
+                if let Ok(true) = encoding::transcodes_file(p, self.encoding) {
+                    return Ok(None);
+                }
                 // We generally don't want to report an error here, since we're
                 // passively trying to find an index.
                 let idx_file = match fs::File::open(&util::idx_path(p)) {
@@ -235,36 +545,111 @@ impl Config {
                 (fs::File::open(p)?, fs::File::open(ip)?)
             }
         };
-        // If the CSV data was last modified after the index file was last
-        // modified, then return an error and demand the user regenerate the
-        // index.
-        let data_modified = util::last_modified(&csv_file.metadata()?);
-        let idx_modified = util::last_modified(&idx_file.metadata()?);
-        if data_modified > idx_modified {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "The CSV file was modified after the index file. \
-                 Please re-create the index.",
-            ));
-        }
-        let csv_rdr = self.from_reader(csv_file);
-        Ok(Some((csv_rdr, idx_file)))
+        Ok(Some(files))
+    }
+
+    /// Read `csv_file` through its index in `idx_file`, whose header has
+    /// already been checked.
+    fn open_indexed(
+        &self,
+        csv_file: fs::File,
+        mut idx_file: fs::File,
+        header: &IndexHeader,
+    ) -> CliResult<IndexedFile> {
+        let data = match header.compression() {
+            None => IndexedData::File(csv_file),
+            Some(c) => {
+                let blocks = header.read_blocks(&mut idx_file)?;
+                IndexedData::Compressed(BlockReader::new(c, csv_file, blocks)?)
+            }
+        };
+        Indexed::open_file(self.from_reader(data), idx_file, header)
     }
 
-    pub fn indexed(&self)
-                  -> CliResult<Option<Indexed<fs::File, fs::File>>> {
+    pub fn indexed(&self) -> CliResult<Option<IndexedFile>> {
+        let (mut csv_file, mut idx_file) = match self.index_files()? {
+            None => return Ok(None),
+            Some(files) => files,
+        };
+        // If the CSV data changed since the index was created, then return
+        // an error and demand the user regenerate the index.
+        let header = self.check_index(&mut csv_file, &mut idx_file)?;
+        self.open_indexed(csv_file, idx_file, &header).map(Some)
+    }
+
+    /// Open the index of the input again, given the header of the index
+    /// when it was first opened (see `Indexed::header`). The CSV data isn't
+    /// checked against the index again, so that jobs reading the same file
+    /// in parallel don't each hash it.
+    pub fn reindexed(&self, header: &IndexHeader) -> CliResult<IndexedFile> {
         match self.index_files()? {
-            None => Ok(None),
-            Some((r, i)) => Ok(Some(Indexed::open(r, i)?)),
+            None => fail!("the index of the input could not be opened"),
+            Some((csv_file, idx_file)) => {
+                self.open_indexed(csv_file, idx_file, header)
+            }
         }
     }
 
+    /// Split the input into chunks that can be read in parallel by `njobs`
+    /// jobs (see the `chunk` module). Returns `None` if the input isn't a
+    /// file that can be split, e.g., because it is compressed or too small.
+    pub fn chunked(&self, njobs: usize) -> CliResult<Option<Chunked>> {
+        let path = match self.path {
+            None => return Ok(None),
+            Some(ref p) => p,
+        };
+        // Byte offsets in compressed or transcoded data can't be used, and
+        // skipping malformed records needs a sequential reader.
+        if njobs <= 1 || self.on_error != OnError::Fail {
+            return Ok(None);
+        }
+        if compress::detect_file(path)?.is_some()
+           || encoding::transcodes_file(path, self.encoding)? {
+            return Ok(None);
+        }
+        let quote = if self.quoting && self.escape.is_none() {
+            Some(self.quote)
+        } else {
+            None
+        };
+        let chunked = Chunked::new(
+            self.clone(), path.clone(), self.delimiter, quote, njobs)?;
+        Ok(chunked)
+    }
+
+    /// The header of an index of the CSV data in `data`, when it is read
+    /// with this configuration.
+    pub fn index_header(&self, data: &mut fs::File)
+                       -> io::Result<IndexHeader> {
+        IndexHeader::new(self.delimiter, self.quote, !self.no_headers, data)
+    }
+
+    /// Check that the index in `idx` is up to date with the CSV data in
+    /// `data`, and return its header. Both files are left at the start of
+    /// their contents.
+    pub fn check_index(
+        &self,
+        data: &mut fs::File,
+        idx: &mut fs::File,
+    ) -> CliResult<IndexHeader> {
+        let header = IndexHeader::read(&mut *idx)?;
+        idx.seek(io::SeekFrom::Start(0))?;
+        header.check(&self.index_header(data)?)?;
+        Ok(header)
+    }
+
+    /// Open the input for reading. Compressed input is decompressed, and
+    /// then transcoded to UTF-8 (see the `encoding` module).
     pub fn io_reader(&self) -> io::Result<Box<io::Read+'static>> {
-        Ok(match self.path {
-                None => Box::new(io::stdin()),
+        let rdr = match self.path {
+                None => {
+                    let sample = STDIN_SAMPLE.lock().unwrap().take();
+                    let sample = io::Cursor::new(sample.unwrap_or_default());
+                    compress::reader(sample.chain(io::stdin()))?
+                }
                 Some(ref p) => {
                     match fs::File::open(p){
-                        Ok(x) => Box::new(x),
+                        Ok(x) => compress::reader(x)?,
                         Err(err) => {
                             let msg = format!(
                                 "failed to open {}: {}", p.display(), err);
@@ -275,7 +660,8 @@ impl Config {
                         }
                     }
                 },
-            })
+            };
+        encoding::reader(rdr, self.encoding)
     }
 
     pub fn from_reader<R: Read>(&self, rdr: R) -> csv::Reader<R> {
@@ -289,10 +675,33 @@ impl Config {
             .from_reader(rdr)
     }
 
-    pub fn io_writer(&self) -> io::Result<Box<io::Write+'static>> {
+    /// Flush `wtr` and finish its output (see `Output::finish`).
+    pub fn finish<W: Output>(wtr: csv::Writer<W>) -> io::Result<()> {
+        let mut out = wtr.into_inner().map_err(|err| {
+            io::Error::new(err.error().kind(), err.to_string())
+        })?;
+        out.finish()
+    }
+
+    /// Open the output for writing. If the output file has a compression
+    /// extension (e.g., `.gz`), then the output is compressed.
+    ///
+    /// Like `writer`, the output must be finished once everything has been
+    /// written.
+    pub fn io_writer(&self) -> io::Result<Box<Output+'static>> {
         Ok(match self.path {
             None => Box::new(io::stdout()),
-            Some(ref p) => Box::new(fs::File::create(p)?),
+            Some(ref p) => {
+                let file = if self.append {
+                    fs::OpenOptions::new().append(true).create(true).open(p)?
+                } else {
+                    fs::File::create(p)?
+                };
+                match Compression::from_path(p) {
+                    None => Box::new(file),
+                    Some(c) => c.writer(file)?,
+                }
+            }
         })
     }
 
@@ -0,0 +1,142 @@
+/*!
+Transcoding of input to UTF-8.
+
+By default, input is read as is, except that a byte order mark (BOM) is
+removed, and input with a UTF-16 BOM is transcoded to UTF-8. An encoding can
+also be given explicitly (using any of the labels in the WHATWG Encoding
+Standard, e.g., `latin1`, `windows-1252` or `utf-16le`), or guessed from a
+sample of the input with `auto`.
+*/
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str;
+
+use encoding_rs::{self, Encoding};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+
+use compress;
+
+/// The number of bytes sampled when guessing an encoding.
+const SAMPLE_LEN: usize = 64 * (1<<10);
+
+/// The length of the longest BOM.
+const BOM_LEN: usize = 3;
+
+/// The encoding of input data.
+#[derive(Clone, Copy, Debug)]
+pub enum InputEncoding {
+    /// Guess the encoding from a sample of the input.
+    Auto,
+    /// Use the given encoding, unless the input starts with a BOM.
+    Fixed(&'static Encoding),
+}
+
+impl InputEncoding {
+    /// Parse an encoding name, which is either `auto` or a WHATWG label.
+    pub fn from_label(label: &str) -> Result<InputEncoding, String> {
+        if label.eq_ignore_ascii_case("auto") {
+            return Ok(InputEncoding::Auto);
+        }
+        match Encoding::for_label(label.as_bytes()) {
+            Some(enc) => Ok(InputEncoding::Fixed(enc)),
+            None => Err(format!("Unknown encoding '{}'.", label)),
+        }
+    }
+
+    fn sample_len(self) -> usize {
+        match self {
+            InputEncoding::Auto => SAMPLE_LEN,
+            InputEncoding::Fixed(_) => BOM_LEN,
+        }
+    }
+}
+
+/// Wrap `rdr` so that data read from it is UTF-8 (unless the input is
+/// read as is, see the module documentation).
+pub fn reader<R: Read + 'static>(
+    mut rdr: R,
+    enc: Option<InputEncoding>,
+) -> io::Result<Box<dyn Read>> {
+    let sample_len = enc.map_or(BOM_LEN, |e| e.sample_len());
+    let mut sample = Vec::with_capacity(sample_len);
+    (&mut rdr).take(sample_len as u64).read_to_end(&mut sample)?;
+    let truncated = sample.len() == sample_len;
+    let enc = resolve(&sample, truncated, enc);
+    if enc == encoding_rs::UTF_8 {
+        // Pass the data through as is, without validating it, but skip a
+        // BOM.
+        let bom_len = match Encoding::for_bom(&sample) {
+            Some((_, n)) => n,
+            None => 0,
+        };
+        sample.drain(..bom_len);
+        return Ok(Box::new(io::Cursor::new(sample).chain(rdr)));
+    }
+    let rdr = io::Cursor::new(sample).chain(rdr);
+    Ok(Box::new(DecodeReaderBytesBuilder::new()
+        .encoding(Some(enc))
+        .bom_override(true)
+        .build(rdr)))
+}
+
+/// Returns true if the data in the file at `path` (after decompressing it)
+/// needs to be transcoded, i.e., if byte offsets in the data don't match byte
+/// offsets of the UTF-8 data.
+pub fn transcodes_file(
+    path: &Path,
+    enc: Option<InputEncoding>,
+) -> io::Result<bool> {
+    let sample_len = enc.map_or(BOM_LEN, |e| e.sample_len());
+    let mut sample = Vec::with_capacity(sample_len);
+    compress::reader(fs::File::open(path)?)?
+        .take(sample_len as u64)
+        .read_to_end(&mut sample)?;
+    let truncated = sample.len() == sample_len;
+    Ok(resolve(&sample, truncated, enc) != encoding_rs::UTF_8)
+}
+
+/// Choose the encoding of data that starts with `sample`. A BOM always
+/// takes precedence.
+fn resolve(
+    sample: &[u8],
+    truncated: bool,
+    enc: Option<InputEncoding>,
+) -> &'static Encoding {
+    if let Some((bom_enc, _)) = Encoding::for_bom(sample) {
+        return bom_enc;
+    }
+    match enc {
+        None => encoding_rs::UTF_8,
+        Some(InputEncoding::Fixed(enc)) => enc,
+        Some(InputEncoding::Auto) => guess(sample, truncated),
+    }
+}
+
+/// Guess the encoding of a sample without a BOM.
+///
+/// If the sample is valid UTF-8, then it's UTF-8. If most of the even or odd
+/// bytes are NUL, then it's UTF-16 (big or little endian respectively).
+/// Otherwise, Windows-1252 (a superset of Latin-1) is assumed.
+fn guess(sample: &[u8], truncated: bool) -> &'static Encoding {
+    match str::from_utf8(sample) {
+        Ok(_) => return encoding_rs::UTF_8,
+        // A character may have been cut off at the end of the sample.
+        Err(err) if truncated && err.error_len().is_none() => {
+            return encoding_rs::UTF_8;
+        }
+        Err(_) => {}
+    }
+    let half = sample.len() / 2;
+    let even_nuls = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd_nuls =
+        sample.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+    if half > 0 && even_nuls > half / 2 && odd_nuls == 0 {
+        encoding_rs::UTF_16BE
+    } else if half > 0 && odd_nuls > half / 2 && even_nuls == 0 {
+        encoding_rs::UTF_16LE
+    } else {
+        encoding_rs::WINDOWS_1252
+    }
+}
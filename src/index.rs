@@ -1,15 +1,508 @@
-use std::io;
+/*!
+Random access to CSV data through an index.
+
+An index file written by `xsv index` starts with an `IndexHeader`, which is
+followed by the offsets of the records in the format of `csv_index`'s
+`RandomAccessSimple`. The header has a version, the dialect used to create
+the index and a fingerprint of the CSV data (its size and a hash of blocks
+sampled from it), which is used to detect an index that is out of date.
+
+The CSV data may also be a gzip or zstd file made of blocks that can be
+decompressed on their own (see the `compress` module). Its records' offsets
+are then offsets in the decompressed data, and they are followed by a table
+of the blocks, with the offset of each block in the compressed file and in
+the decompressed data. So a record's offset maps to a block to decompress,
+and an offset in it.
+
+An index can also be sparse, with only the offset of every `k`th record (see
+`SparseWriter`). Seeking to a record then means seeking to the nearest record
+before it with an offset, and parsing the records in between.
+*/
+
+use std::ascii;
+use std::fs;
+use std::io::{self, Read, Seek, Write};
 use std::ops;
 
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use csv;
 use csv_index::RandomAccessSimple;
 
-use CliResult;
+use {CliError, CliResult};
+use compress::{Block, BlockReader, Compression, MAGIC_LEN};
+
+/// The start of every index file written by `xsv index`.
+const MAGIC: &[u8] = b"XSVIDX";
+
+/// The version of the index format, which is changed whenever the format is.
+const INDEX_VERSION: u16 = 2;
+
+/// The length of an `IndexHeader` in an index file.
+const HEADER_LEN: u64 = 40;
+
+/// The number of offsets in each block of a sparse index.
+const SPARSE_BLOCK_LEN: u64 = 1024;
+
+/// The number of blocks of CSV data hashed in a fingerprint, and their
+/// length. Smaller data is hashed in full.
+const SAMPLE_BLOCKS: u64 = 16;
+const SAMPLE_BLOCK_LEN: u64 = 4096;
+
+/// The header of an index file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexHeader {
+    version: u16,
+    delimiter: u8,
+    quote: u8,
+    has_headers: bool,
+    /// How the CSV data is compressed, if it is.
+    compression: Option<Compression>,
+    /// The number of records per offset in a sparse index, or 0 if the
+    /// index has the offset of every record.
+    sparse: u32,
+    /// The size of the CSV data, in bytes.
+    size: u64,
+    /// A hash of blocks sampled from the CSV data.
+    hash: u64,
+    /// The offset of the table of blocks of compressed CSV data in the
+    /// index file, or 0 if there is none.
+    blocks_start: u64,
+}
+
+impl IndexHeader {
+    /// Create the header of an index of the CSV data in `data`, which is
+    /// read with the given dialect.
+    pub fn new(
+        delimiter: u8,
+        quote: u8,
+        has_headers: bool,
+        data: &mut fs::File,
+    ) -> io::Result<IndexHeader> {
+        let (size, hash) = fingerprint(data)?;
+        let mut magic = vec![];
+        (&mut *data).take(MAGIC_LEN as u64).read_to_end(&mut magic)?;
+        data.seek(io::SeekFrom::Start(0))?;
+        Ok(IndexHeader {
+            version: INDEX_VERSION,
+            delimiter: delimiter,
+            quote: quote,
+            has_headers: has_headers,
+            compression: Compression::from_magic(&magic),
+            sparse: 0,
+            size: size,
+            hash: hash,
+            blocks_start: 0,
+        })
+    }
+
+    /// How the CSV data is compressed, if it is.
+    pub fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+
+    /// Make the index sparse, with the offset of one record out of every
+    /// `every` (if given).
+    pub fn sparse(mut self, every: Option<u32>) -> IndexHeader {
+        self.sparse = every.unwrap_or(0);
+        self
+    }
+
+    /// Read the header of an index file.
+    ///
+    /// An error is returned if the file isn't an index in the current
+    /// format, e.g., because it was written by an older version of xsv.
+    pub fn read<R: io::Read>(mut rdr: R) -> CliResult<IndexHeader> {
+        let mut buf = [0; HEADER_LEN as usize];
+        match rdr.read_exact(&mut buf) {
+            Ok(()) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => {}
+            Err(err) => return Err(From::from(err)),
+        }
+        if &buf[..MAGIC.len()] != MAGIC {
+            return Err(CliError::StaleIndex(
+                "The index file was created by an older version of xsv. \
+                 Please re-create the index.".to_owned(),
+            ));
+        }
+        let mut rdr = &buf[MAGIC.len()..];
+        let version = rdr.read_u16::<BigEndian>()?;
+        if version != INDEX_VERSION {
+            return Err(CliError::StaleIndex(format!(
+                "The index file has version {}, but this version of xsv \
+                 only supports version {}. Please re-create the index.",
+                version, INDEX_VERSION)));
+        }
+        let delimiter = rdr.read_u8()?;
+        let quote = rdr.read_u8()?;
+        let has_headers = rdr.read_u8()? != 0;
+        let compression = match rdr.read_u8()? {
+            0 => None,
+            1 => Some(Compression::Gzip),
+            2 => Some(Compression::Zstd),
+            n => {
+                return Err(CliError::StaleIndex(format!(
+                    "The index file has an unknown compression format ({}). \
+                     Please re-create the index.", n)));
+            }
+        };
+        Ok(IndexHeader {
+            version: version,
+            delimiter: delimiter,
+            quote: quote,
+            has_headers: has_headers,
+            compression: compression,
+            sparse: rdr.read_u32::<BigEndian>()?,
+            size: rdr.read_u64::<BigEndian>()?,
+            hash: rdr.read_u64::<BigEndian>()?,
+            blocks_start: rdr.read_u64::<BigEndian>()?,
+        })
+    }
+
+    pub fn write<W: io::Write>(&self, mut wtr: W) -> io::Result<()> {
+        let compression = match self.compression {
+            None => 0,
+            Some(Compression::Gzip) => 1,
+            Some(Compression::Zstd) => 2,
+            Some(c) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("{:?} compressed data can't be indexed", c),
+                ));
+            }
+        };
+        wtr.write_all(MAGIC)?;
+        wtr.write_u16::<BigEndian>(self.version)?;
+        wtr.write_all(&[
+            self.delimiter, self.quote, self.has_headers as u8, compression,
+        ])?;
+        wtr.write_u32::<BigEndian>(self.sparse)?;
+        wtr.write_u64::<BigEndian>(self.size)?;
+        wtr.write_u64::<BigEndian>(self.hash)?;
+        wtr.write_u64::<BigEndian>(self.blocks_start)?;
+        Ok(())
+    }
+
+    /// Write the offsets of the records read by `rdr` to an index file with
+    /// this header, right after the header.
+    pub fn write_offsets<R: Read, W: Write>(
+        &self,
+        rdr: &mut csv::Reader<R>,
+        wtr: W,
+    ) -> CliResult<()> {
+        if self.sparse == 0 {
+            RandomAccessSimple::create(rdr, wtr)?;
+            return Ok(());
+        }
+        let mut wtr = SparseWriter::new(wtr, self.sparse as u64);
+        if rdr.has_headers() {
+            let header = rdr.byte_headers()?;
+            if !header.is_empty() {
+                wtr.add(header.position().expect("position on header row"))?;
+            }
+        }
+        let mut record = csv::ByteRecord::new();
+        while rdr.read_byte_record(&mut record)? {
+            wtr.add(record.position().expect("position on row"))?;
+        }
+        wtr.finish()?;
+        Ok(())
+    }
+
+    /// Write the table of `blocks` of the compressed CSV data to an index
+    /// file, after the offsets of its records, and then rewrite the header
+    /// at the start of the file to point to it.
+    pub fn write_blocks<W: Write + Seek>(
+        &mut self,
+        mut wtr: W,
+        blocks: &[Block],
+    ) -> io::Result<()> {
+        self.blocks_start = wtr.stream_position()?;
+        wtr.write_u64::<BigEndian>(blocks.len() as u64)?;
+        for block in blocks {
+            wtr.write_u64::<BigEndian>(block.compressed)?;
+            wtr.write_u64::<BigEndian>(block.decompressed)?;
+        }
+        wtr.seek(io::SeekFrom::Start(0))?;
+        self.write(&mut wtr)?;
+        wtr.flush()
+    }
+
+    /// Read the table of blocks of the compressed CSV data from an index
+    /// file with this header.
+    pub fn read_blocks<R: Read + Seek>(&self, mut rdr: R)
+                                      -> io::Result<Vec<Block>> {
+        let mut blocks = vec![];
+        if self.blocks_start == 0 {
+            return Ok(blocks);
+        }
+        rdr.seek(io::SeekFrom::Start(self.blocks_start))?;
+        let len = rdr.read_u64::<BigEndian>()?;
+        for _ in 0..len {
+            blocks.push(Block {
+                compressed: rdr.read_u64::<BigEndian>()?,
+                decompressed: rdr.read_u64::<BigEndian>()?,
+            });
+        }
+        Ok(blocks)
+    }
+
+    /// Check that an index with this header can be used for the CSV data
+    /// described by `current`.
+    ///
+    /// Whether the data has a header row doesn't matter, since the index
+    /// has the offsets of all records either way.
+    pub fn check(&self, current: &IndexHeader) -> CliResult<()> {
+        if self.size != current.size || self.hash != current.hash {
+            return Err(CliError::StaleIndex(
+                "The CSV file was modified after the index file. \
+                 Please re-create the index.".to_owned(),
+            ));
+        }
+        if self.delimiter != current.delimiter || self.quote != current.quote {
+            return Err(CliError::StaleIndex(format!(
+                "The index was created with delimiter '{}' and quote '{}', \
+                 but the CSV file is read with delimiter '{}' and quote \
+                 '{}'. Please re-create the index.",
+                escape(self.delimiter), escape(self.quote),
+                escape(current.delimiter), escape(current.quote))));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the size of the data in `data`, and a hash of blocks sampled
+/// evenly from it (including its first and last block). `data` is left at
+/// its start.
+///
+/// Since the hash is only of samples of the data, it won't catch every
+/// modification of a large file that keeps its size the same.
+fn fingerprint(data: &mut fs::File) -> io::Result<(u64, u64)> {
+    let size = data.seek(io::SeekFrom::End(0))?;
+    data.seek(io::SeekFrom::Start(0))?;
+    let mut hash = fnv1a(FNV_OFFSET_BASIS, &[]);
+    if size <= SAMPLE_BLOCKS * SAMPLE_BLOCK_LEN {
+        let mut buf = vec![];
+        data.read_to_end(&mut buf)?;
+        hash = fnv1a(hash, &buf);
+    } else {
+        let mut buf = vec![0; SAMPLE_BLOCK_LEN as usize];
+        for i in 0..SAMPLE_BLOCKS {
+            let start = i * (size - SAMPLE_BLOCK_LEN) / (SAMPLE_BLOCKS - 1);
+            data.seek(io::SeekFrom::Start(start))?;
+            data.read_exact(&mut buf)?;
+            hash = fnv1a(hash, &buf);
+        }
+    }
+    data.seek(io::SeekFrom::Start(0))?;
+    Ok((size, hash))
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// The 64 bit FNV-1a hash, which (unlike the hashers in `std`) is the same
+/// for every version of xsv.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |h, &b| (h ^ b as u64).wrapping_mul(FNV_PRIME))
+}
+
+fn escape(b: u8) -> String {
+    ascii::escape_default(b).map(|c| c as char).collect()
+}
+
+/// The records offsets of an index file, i.e., an index file without its
+/// header (or table of blocks), as `RandomAccessSimple` expects to read them
+/// from the start to the end.
+struct Body<I> {
+    rdr: I,
+    start: u64,
+    /// The end of the offsets, if they aren't at the end of the file.
+    end: Option<u64>,
+}
+
+impl<I: io::Read> io::Read for Body<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.rdr.read(buf)
+    }
+}
+
+impl<I: io::Seek> io::Seek for Body<I> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let pos = match (pos, self.end) {
+            (io::SeekFrom::Start(n), _) => io::SeekFrom::Start(self.start + n),
+            (io::SeekFrom::End(n), Some(end)) => {
+                io::SeekFrom::Start((end as i64 + n) as u64)
+            }
+            (pos, _) => pos,
+        };
+        Ok(self.rdr.seek(pos)?.saturating_sub(self.start))
+    }
+}
+
+/// A writer of the offsets of a sparse index, which only has the offsets of
+/// every `every`th record (including the header row), starting with the
+/// first.
+///
+/// The offsets are grouped in blocks of at most `SPARSE_BLOCK_LEN` offsets,
+/// and each offset is written as 4 bytes, relative to the first offset of
+/// its block. (A new block is also started when an offset is too far from
+/// the first.) They are followed by a directory of the blocks, with the
+/// number and the offset of the first offset of each, and then by the
+/// number of offsets, of blocks and of records.
+struct SparseWriter<W> {
+    wtr: W,
+    every: u64,
+    /// The number of records so far.
+    len: u64,
+    /// The number of offsets written so far.
+    noffsets: u64,
+    dir: Vec<(u64, u64)>,
+}
+
+impl<W: io::Write> SparseWriter<W> {
+    fn new(wtr: W, every: u64) -> SparseWriter<W> {
+        SparseWriter {
+            wtr: wtr,
+            every: every,
+            len: 0,
+            noffsets: 0,
+            dir: vec![],
+        }
+    }
+
+    fn add(&mut self, pos: &csv::Position) -> io::Result<()> {
+        if self.len.is_multiple_of(self.every) {
+            let offset = pos.byte();
+            let new_block = match self.dir.last() {
+                None => true,
+                Some(&(first, base)) => {
+                    self.noffsets - first >= SPARSE_BLOCK_LEN
+                    || offset - base > u32::MAX as u64
+                }
+            };
+            if new_block {
+                self.dir.push((self.noffsets, offset));
+            }
+            let base = self.dir.last().unwrap().1;
+            self.wtr.write_u32::<BigEndian>((offset - base) as u32)?;
+            self.noffsets += 1;
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        for &(first, base) in &self.dir {
+            self.wtr.write_u64::<BigEndian>(first)?;
+            self.wtr.write_u64::<BigEndian>(base)?;
+        }
+        self.wtr.write_u64::<BigEndian>(self.noffsets)?;
+        self.wtr.write_u64::<BigEndian>(self.dir.len() as u64)?;
+        self.wtr.write_u64::<BigEndian>(self.len)?;
+        self.wtr.flush()
+    }
+}
+
+/// A reader of the offsets written by a `SparseWriter`. Only the directory
+/// of blocks is read in memory.
+struct Sparse<R> {
+    rdr: R,
+    every: u64,
+    len: u64,
+    dir: Vec<(u64, u64)>,
+}
+
+impl<R: io::Read + io::Seek> Sparse<R> {
+    fn open(mut rdr: R, every: u64) -> io::Result<Sparse<R>> {
+        rdr.seek(io::SeekFrom::End(-24))?;
+        let noffsets = rdr.read_u64::<BigEndian>()?;
+        let ndir = rdr.read_u64::<BigEndian>()?;
+        let len = rdr.read_u64::<BigEndian>()?;
+        rdr.seek(io::SeekFrom::Start(noffsets * 4))?;
+        let mut dir = vec![];
+        for _ in 0..ndir {
+            let first = rdr.read_u64::<BigEndian>()?;
+            dir.push((first, rdr.read_u64::<BigEndian>()?));
+        }
+        Ok(Sparse { rdr: rdr, every: every, len: len, dir: dir })
+    }
+
+    /// The position of the last record with an offset at or before record
+    /// `i`.
+    fn get(&mut self, i: u64) -> io::Result<csv::Position> {
+        let n = i / self.every;
+        let block = match self.dir.binary_search_by_key(&n, |d| d.0) {
+            Ok(b) => b,
+            Err(b) => b - 1,
+        };
+        self.rdr.seek(io::SeekFrom::Start(n * 4))?;
+        let rel = self.rdr.read_u32::<BigEndian>()?;
+        let offset = self.dir[block].1 + rel as u64;
+        let mut pos = csv::Position::new();
+        pos.set_byte(offset).set_record(n * self.every);
+        Ok(pos)
+    }
+}
+
+/// The offsets of the records in an index.
+enum Offsets<I> {
+    Dense(RandomAccessSimple<Body<I>>),
+    Sparse(Sparse<Body<I>>),
+}
+
+impl<I: io::Read + io::Seek> Offsets<I> {
+    fn len(&self) -> u64 {
+        match *self {
+            Offsets::Dense(ref idx) => idx.len(),
+            Offsets::Sparse(ref idx) => idx.len,
+        }
+    }
+
+    /// The position of record `i`, or of the nearest record before it with
+    /// an offset.
+    fn get(&mut self, i: u64) -> CliResult<csv::Position> {
+        Ok(match *self {
+            Offsets::Dense(ref mut idx) => idx.get(i)?,
+            Offsets::Sparse(ref mut idx) => idx.get(i)?,
+        })
+    }
+}
+
+/// CSV data that can be read through an index: a file, or a compressed file
+/// made of blocks.
+pub enum IndexedData {
+    File(fs::File),
+    Compressed(BlockReader),
+}
+
+impl io::Read for IndexedData {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            IndexedData::File(ref mut f) => f.read(buf),
+            IndexedData::Compressed(ref mut r) => r.read(buf),
+        }
+    }
+}
+
+impl io::Seek for IndexedData {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        match *self {
+            IndexedData::File(ref mut f) => f.seek(pos),
+            IndexedData::Compressed(ref mut r) => r.seek(pos),
+        }
+    }
+}
+
+/// The CSV data of a file and its index file, as opened by
+/// `Config::indexed`.
+pub type IndexedFile = Indexed<IndexedData, fs::File>;
 
 /// Indexed composes a CSV reader with a simple random access index.
 pub struct Indexed<R, I> {
     csv_rdr: csv::Reader<R>,
-    idx: RandomAccessSimple<I>,
+    idx: Offsets<I>,
+    /// The header of an index file written by `xsv index`.
+    header: Option<IndexHeader>,
 }
 
 impl<R, I> ops::Deref for Indexed<R, I> {
@@ -22,21 +515,55 @@ impl<R, I> ops::DerefMut for Indexed<R, I> {
 }
 
 impl<R: io::Read + io::Seek, I: io::Read + io::Seek> Indexed<R, I> {
-    /// Opens an index.
+    /// Opens an index in the format of `RandomAccessSimple`, without a
+    /// header.
     pub fn open(
         csv_rdr: csv::Reader<R>,
         idx_rdr: I,
     ) -> CliResult<Indexed<R, I>> {
+        let body = Body { rdr: idx_rdr, start: 0, end: None };
+        Ok(Indexed {
+            csv_rdr: csv_rdr,
+            idx: Offsets::Dense(RandomAccessSimple::open(body)?),
+            header: None,
+        })
+    }
+
+    /// Opens an index file written by `xsv index`, which has the given
+    /// header. The header should already have been checked.
+    pub fn open_file(
+        csv_rdr: csv::Reader<R>,
+        idx_rdr: I,
+        header: &IndexHeader,
+    ) -> CliResult<Indexed<R, I>> {
+        let end = if header.blocks_start == 0 {
+            None
+        } else {
+            Some(header.blocks_start)
+        };
+        let body = Body { rdr: idx_rdr, start: HEADER_LEN, end: end };
+        let idx = if header.sparse == 0 {
+            Offsets::Dense(RandomAccessSimple::open(body)?)
+        } else {
+            Offsets::Sparse(Sparse::open(body, header.sparse as u64)?)
+        };
         Ok(Indexed {
             csv_rdr: csv_rdr,
-            idx: RandomAccessSimple::open(idx_rdr)?,
+            idx: idx,
+            header: Some(header.clone()),
         })
     }
 
+    /// The header of the index, if it is an index file written by
+    /// `xsv index`.
+    pub fn header(&self) -> Option<&IndexHeader> {
+        self.header.as_ref()
+    }
+
     /// Return the number of records (not including the header record) in this
     /// index.
     pub fn count(&self) -> u64 {
-        if self.csv_rdr.has_headers() && !self.idx.is_empty() {
+        if self.csv_rdr.has_headers() && self.idx.len() > 0 {
             self.idx.len() - 1
         } else {
             self.idx.len()
@@ -55,7 +582,12 @@ impl<R: io::Read + io::Seek, I: io::Read + io::Seek> Indexed<R, I> {
             i += 1;
         }
         let pos = self.idx.get(i)?;
+        let skip = i - pos.record();
         self.csv_rdr.seek(pos)?;
+        let mut record = csv::ByteRecord::new();
+        for _ in 0..skip {
+            self.csv_rdr.read_byte_record(&mut record)?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,258 @@
+/*!
+Writing CSV data as JSON.
+
+`JsonWriter` is an `io::Write` that parses the CSV data written to it and
+writes each record as a JSON object keyed by the header names. Since it sits
+underneath a `csv::Writer`, any command that writes its output through
+`Config::writer` can produce JSON without knowing about it.
+*/
+
+use std::io;
+use std::mem;
+
+use csv_core::{self, ReadFieldResult};
+
+use config::Output;
+use types::FieldType;
+
+/// The layout of JSON output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JsonStyle {
+    /// A single JSON array with one object per record.
+    Array,
+    /// One JSON object per line (NDJSON).
+    Lines,
+}
+
+/// The format of the output of commands that write CSV data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Csv,
+    /// JSON objects, whose values that look like numbers are written as
+    /// JSON numbers when `numbers` is true (and as strings otherwise).
+    Json { style: JsonStyle, numbers: bool },
+}
+
+impl OutputFormat {
+    /// Parse a format, which is one of `csv`, `json` or `ndjson`, where
+    /// the JSON formats can have a `-typed` suffix to write numbers.
+    pub fn from_arg(arg: &str) -> Result<OutputFormat, String> {
+        let (style, numbers) = match arg {
+            "" | "csv" => return Ok(OutputFormat::Csv),
+            "json" => (JsonStyle::Array, false),
+            "json-typed" => (JsonStyle::Array, true),
+            "ndjson" => (JsonStyle::Lines, false),
+            "ndjson-typed" => (JsonStyle::Lines, true),
+            _ => return Err(format!(
+                "Unknown output format '{}' (expected one of csv, json, \
+                 json-typed, ndjson or ndjson-typed).", arg)),
+        };
+        Ok(OutputFormat::Json { style: style, numbers: numbers })
+    }
+}
+
+/// A writer that converts CSV data to JSON.
+///
+/// The first record written is used as the keys of every object that
+/// follows, unless the data has no header row. Fields beyond the end of the
+/// header row are keyed by their 1-based index.
+///
+/// The JSON is ended (e.g., the closing bracket of an array is written) by
+/// `Output::finish`, after which no more records can be written. A writer
+/// that is dropped without being finished is finished then, but any error
+/// is lost.
+pub struct JsonWriter<W: Output> {
+    wtr: W,
+    rdr: csv_core::Reader,
+    style: JsonStyle,
+    numbers: bool,
+    buf: Vec<u8>,
+    field: Vec<u8>,
+    fields: Vec<Vec<u8>>,
+    headers: Option<Vec<Vec<u8>>>,
+    records: u64,
+    finished: bool,
+}
+
+impl<W: Output> JsonWriter<W> {
+    /// Create a new JSON writer.
+    ///
+    /// When `numbers` is true, values that look like numbers are written as
+    /// JSON numbers instead of strings. When `has_headers` is false, every
+    /// field is keyed by its 1-based index.
+    pub fn new(
+        wtr: W,
+        style: JsonStyle,
+        numbers: bool,
+        has_headers: bool,
+    ) -> JsonWriter<W> {
+        JsonWriter {
+            wtr,
+            rdr: csv_core::Reader::new(),
+            style,
+            numbers,
+            buf: vec![0; 8 * (1<<10)],
+            field: vec![],
+            fields: vec![],
+            headers: if has_headers { None } else { Some(vec![]) },
+            records: 0,
+            finished: false,
+        }
+    }
+
+    fn write_record(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "can't write to JSON output that is already finished",
+            ));
+        }
+        let fields = mem::take(&mut self.fields);
+        let headers = match self.headers {
+            None => {
+                self.headers = Some(fields);
+                return Ok(());
+            }
+            Some(ref headers) => headers,
+        };
+        match self.style {
+            JsonStyle::Array if self.records == 0 => {
+                self.wtr.write_all(b"[\n")?
+            }
+            JsonStyle::Array => self.wtr.write_all(b",\n")?,
+            JsonStyle::Lines => {}
+        }
+        self.wtr.write_all(b"{")?;
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                self.wtr.write_all(b",")?;
+            }
+            match headers.get(i) {
+                Some(key) => write_string(&mut self.wtr, key)?,
+                None => write_string(
+                    &mut self.wtr, (i+1).to_string().as_bytes())?,
+            }
+            self.wtr.write_all(b":")?;
+            if self.numbers && is_number(field) {
+                self.wtr.write_all(field)?;
+            } else {
+                write_string(&mut self.wtr, field)?;
+            }
+        }
+        self.wtr.write_all(b"}")?;
+        if self.style == JsonStyle::Lines {
+            self.wtr.write_all(b"\n")?;
+        }
+        self.records += 1;
+        Ok(())
+    }
+
+}
+
+impl<W: Output> io::Write for JsonWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // An empty input tells the CSV parser that there is no more data, so
+        // never pass one along.
+        let mut input = buf;
+        while !input.is_empty() {
+            let (res, nin, nout) = self.rdr.read_field(input, &mut self.buf);
+            input = &input[nin..];
+            self.field.extend_from_slice(&self.buf[..nout]);
+            match res {
+                ReadFieldResult::InputEmpty
+                | ReadFieldResult::OutputFull
+                | ReadFieldResult::End => {}
+                ReadFieldResult::Field { record_end } => {
+                    let field = mem::take(&mut self.field);
+                    self.fields.push(field);
+                    if record_end {
+                        self.write_record()?;
+                    }
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.wtr.flush()
+    }
+}
+
+impl<W: Output> Output for JsonWriter<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.finished = true;
+            match self.style {
+                JsonStyle::Array if self.records == 0 => {
+                    self.wtr.write_all(b"[]\n")?
+                }
+                JsonStyle::Array => self.wtr.write_all(b"\n]\n")?,
+                JsonStyle::Lines => {}
+            }
+        }
+        self.wtr.finish()
+    }
+}
+
+impl<W: Output> Drop for JsonWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Write `s` as a quoted JSON string. Invalid UTF-8 is replaced with the
+/// Unicode replacement character.
+pub fn write_string<W: io::Write>(mut wtr: W, s: &[u8]) -> io::Result<()> {
+    wtr.write_all(b"\"")?;
+    for c in String::from_utf8_lossy(s).chars() {
+        match c {
+            '"' => wtr.write_all(b"\\\"")?,
+            '\\' => wtr.write_all(b"\\\\")?,
+            '\n' => wtr.write_all(b"\\n")?,
+            '\r' => wtr.write_all(b"\\r")?,
+            '\t' => wtr.write_all(b"\\t")?,
+            c if (c as u32) < 0x20 => write!(wtr, "\\u{:04x}", c as u32)?,
+            c => write!(wtr, "{}", c)?,
+        }
+    }
+    wtr.write_all(b"\"")
+}
+
+/// Returns true if `s` is inferred to be a number and is also written the
+/// way JSON requires numbers to be written. (For example, Rust accepts
+/// `+1`, `.5` and `inf` as floats, but JSON does not.)
+fn is_number(s: &[u8]) -> bool {
+    if !FieldType::from_sample(s).is_number() {
+        return false;
+    }
+    let s = if s.starts_with(b"-") { &s[1..] } else { s };
+    let digits = |s: &[u8]| {
+        s.iter().take_while(|b| b.is_ascii_digit()).count()
+    };
+
+    let n = digits(s);
+    if n == 0 || (n > 1 && s[0] == b'0') {
+        return false;
+    }
+    let mut s = &s[n..];
+    if s.starts_with(b".") {
+        let n = digits(&s[1..]);
+        if n == 0 {
+            return false;
+        }
+        s = &s[1 + n..];
+    }
+    if s.starts_with(b"e") || s.starts_with(b"E") {
+        s = &s[1..];
+        if s.starts_with(b"+") || s.starts_with(b"-") {
+            s = &s[1..];
+        }
+        let n = digits(s);
+        if n == 0 {
+            return false;
+        }
+        s = &s[n..];
+    }
+    s.is_empty()
+}
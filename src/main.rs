@@ -1,18 +1,31 @@
+extern crate arrow;
 extern crate byteorder;
+extern crate bzip2;
 extern crate chan;
 extern crate csv;
+extern crate csv_core;
 extern crate csv_index;
 extern crate docopt;
+extern crate encoding_rs;
+extern crate encoding_rs_io;
 extern crate filetime;
+extern crate flate2;
 extern crate num_cpus;
+extern crate parquet;
 extern crate rand;
 extern crate regex;
+extern crate rusqlite;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
 extern crate stats;
 extern crate tabwriter;
 extern crate threadpool;
+extern crate unicode_width;
+extern crate xz2;
+extern crate zstd;
 
 use std::borrow::ToOwned;
 use std::env;
@@ -22,6 +35,8 @@ use std::process;
 
 use docopt::Docopt;
 
+use config::GlobalOptions;
+
 macro_rules! wout {
     ($($arg:tt)*) => ({
         use std::io::Write;
@@ -40,17 +55,27 @@ macro_rules! fail {
     ($e:expr) => (Err(::std::convert::From::from($e)));
 }
 
+/// Fail with an error about invalid arguments, like a docopt error.
+macro_rules! fail_usage {
+    ($e:expr) => (Err(::CliError::Usage(::std::convert::Into::into($e))));
+}
+
 macro_rules! command_list {
     () => (
 "
     cat         Concatenate by row or column
     count       Count records
+    crosstab    Cross tabulate two columns
     fixlengths  Makes all records have same length
     flatten     Show one field per line
     fmt         Format CSV output (change field delimiter)
+    from        Convert Parquet or Arrow files to CSV
+    fromfixed   Convert fixed width data to CSV
+    fromjson    Convert JSON data to CSV
     frequency   Show frequency tables
     headers     Show header names
     help        Show this usage message.
+    hist        Compute histograms of numeric columns
     index       Create CSV index for faster access
     input       Read CSV data with special quoting rules
     join        Join CSV files
@@ -58,18 +83,30 @@ macro_rules! command_list {
     search      Search CSV data with regexes
     select      Select columns from CSV
     slice       Slice records from CSV
+    sniff       Infer the delimiter and other details of CSV data
     sort        Sort CSV data
     split       Split CSV data into many files
     stats       Compute basic statistics
     table       Align CSV data into columns
+    to          Convert CSV to Parquet or Arrow files
+    tofixed     Convert CSV to fixed width data
+    tosqlite    Load CSV data into a SQLite database
 "
     )
 }
 
+mod chunk;
 mod cmd;
+mod compress;
 mod config;
+mod encoding;
 mod index;
+mod json;
+mod malformed;
 mod select;
+mod sketch;
+mod sniff;
+mod types;
 mod util;
 
 static USAGE: &'static str = concat!("
@@ -83,6 +120,39 @@ Options:
     <command> -h  Display the command help message
     --version     Print version info and exit
 
+Global options:
+    --encoding <arg>  The character encoding of input data, which is
+                      transcoded to UTF-8. Either 'auto' (guess from the
+                      data) or a name like latin1, windows-1252 or
+                      utf-16le. By default, data is read as is, except
+                      that a byte order mark is removed (and UTF-16 data
+                      with a byte order mark is transcoded).
+    --on-error <arg>  What to do with a malformed record, i.e., one with a
+                      different number of fields than the first record or
+                      that isn't valid UTF-8: 'fail', 'skip' it, or
+                      'quarantine=<file>' to also write it (with its line
+                      number and the error) to <file> as CSV. The number
+                      of skipped records is written to stderr. Records
+                      that aren't valid UTF-8 are skipped by every
+                      command, even ones that don't need text. Indexes
+                      aren't used unless this is 'fail'.
+                      [default: fail]
+    --output-format <arg>
+                      The format of CSV data written by a command: 'csv',
+                      'json' (an array of objects) or 'ndjson' (one object
+                      per line). Values are written as JSON strings, unless
+                      'json-typed' or 'ndjson-typed' is used to write
+                      values that look like numbers as JSON numbers. This
+                      overrides the XSV_OUTPUT_FORMAT environment variable.
+
+Global options apply to any command, and must be given before the command
+name, e.g., xsv --encoding latin1 search foo data.csv.
+
+Errors are written to stderr, as a JSON object if the XSV_ERROR_FORMAT
+environment variable is set to 'json'. The exit status tells the kind of
+error: 1 for any other error, 2 for invalid arguments, 3 for I/O errors, 4 for
+errors parsing CSV data and 5 for an index that is out of date.
+
 Commands:", command_list!());
 
 #[derive(Deserialize)]
@@ -92,11 +162,21 @@ struct Args {
 }
 
 fn main() {
+    let argv: Vec<String> = env::args().collect();
+    let (argv, on_error) = match GlobalOptions::extract(argv) {
+        Ok((global, argv)) => {
+            let on_error = global.on_error.clone();
+            global.set();
+            (argv, on_error)
+        }
+        Err(msg) => exit_with(CliError::Flag(docopt::Error::Argv(msg))),
+    };
     let args: Args = Docopt::new(USAGE)
-                            .and_then(|d| d.options_first(true)
+                            .and_then(|d| d.argv(argv.iter())
+                                           .options_first(true)
                                            .version(Some(util::version()))
                                            .deserialize())
-                            .unwrap_or_else(|e| e.exit());
+                            .unwrap_or_else(|e| exit_with(CliError::Flag(e)));
     if args.flag_list {
         wout!(concat!("Installed commands:", command_list!()));
         return;
@@ -111,40 +191,60 @@ Please choose one of the following commands:",
             process::exit(0);
         }
         Some(cmd) => {
-            match cmd.run() {
+            let result = cmd.run(&argv);
+            if let Err(err) = malformed::finish(&on_error) {
+                exit_with(CliError::Io(err));
+            }
+            match result {
                 Ok(()) => process::exit(0),
-                Err(CliError::Flag(err)) => err.exit(),
-                Err(CliError::Csv(err)) => {
-                    werr!("{}", err);
-                    process::exit(1);
-                }
                 Err(CliError::Io(ref err))
                         if err.kind() == io::ErrorKind::BrokenPipe => {
                     process::exit(0);
                 }
-                Err(CliError::Io(err)) => {
-                    werr!("{}", err);
-                    process::exit(1);
-                }
-                Err(CliError::Other(msg)) => {
-                    werr!("{}", msg);
-                    process::exit(1);
-                }
+                Err(err) => exit_with(err),
             }
         }
     }
 }
 
+/// Write `err` to stderr and exit with its exit status.
+///
+/// If the `XSV_ERROR_FORMAT` environment variable is `json`, then the error
+/// is written as a JSON object instead of as text.
+fn exit_with(err: CliError) -> ! {
+    if let CliError::Flag(ref err) = err {
+        // Help and version "errors" go to stdout.
+        if !err.fatal() {
+            err.exit();
+        }
+    }
+    match env::var("XSV_ERROR_FORMAT") {
+        Ok(ref format) if format == "json" => {
+            werr!("{}", serde_json::to_string(&err.to_json()).unwrap());
+        }
+        _ => match err {
+            CliError::Flag(ref err) => werr!("{}\n", err),
+            ref err => werr!("{}", err),
+        },
+    }
+    process::exit(err.exit_code())
+}
+
 #[derive(Debug, Deserialize)]
 enum Command {
     Cat,
     Count,
+    CrossTab,
     FixLengths,
     Flatten,
     Fmt,
+    From,
+    FromFixed,
+    FromJson,
     Frequency,
     Headers,
     Help,
+    Hist,
     Index,
     Input,
     Join,
@@ -153,26 +253,34 @@ enum Command {
     Search,
     Select,
     Slice,
+    Sniff,
     Sort,
     Split,
     Stats,
     Table,
+    To,
+    ToFixed,
+    ToSqlite,
 }
 
 impl Command {
-    fn run(self) -> CliResult<()> {
-        let argv: Vec<_> = env::args().map(|v| v.to_owned()).collect();
+    fn run(self, argv: &[String]) -> CliResult<()> {
         let argv: Vec<_> = argv.iter().map(|s| &**s).collect();
         let argv = &*argv;
         match self {
             Command::Cat => cmd::cat::run(argv),
             Command::Count => cmd::count::run(argv),
+            Command::CrossTab => cmd::crosstab::run(argv),
             Command::FixLengths => cmd::fixlengths::run(argv),
             Command::Flatten => cmd::flatten::run(argv),
             Command::Fmt => cmd::fmt::run(argv),
+            Command::From => cmd::from::run(argv),
+            Command::FromFixed => cmd::fromfixed::run(argv),
+            Command::FromJson => cmd::fromjson::run(argv),
             Command::Frequency => cmd::frequency::run(argv),
             Command::Headers => cmd::headers::run(argv),
             Command::Help => { wout!("{}", USAGE); Ok(()) }
+            Command::Hist => cmd::hist::run(argv),
             Command::Index => cmd::index::run(argv),
             Command::Input => cmd::input::run(argv),
             Command::Join => cmd::join::run(argv),
@@ -181,10 +289,14 @@ impl Command {
             Command::Search => cmd::search::run(argv),
             Command::Select => cmd::select::run(argv),
             Command::Slice => cmd::slice::run(argv),
+            Command::Sniff => cmd::sniff::run(argv),
             Command::Sort => cmd::sort::run(argv),
             Command::Split => cmd::split::run(argv),
             Command::Stats => cmd::stats::run(argv),
             Command::Table => cmd::table::run(argv),
+            Command::To => cmd::to::run(argv),
+            Command::ToFixed => cmd::tofixed::run(argv),
+            Command::ToSqlite => cmd::tosqlite::run(argv),
         }
     }
 }
@@ -196,15 +308,71 @@ pub enum CliError {
     Flag(docopt::Error),
     Csv(csv::Error),
     Io(io::Error),
+    /// An index file that is out of date with its CSV data, or in an old
+    /// format.
+    StaleIndex(String),
+    /// Arguments that docopt accepts, but that are invalid together or out
+    /// of range.
+    Usage(String),
     Other(String),
 }
 
+impl CliError {
+    /// The exit status of xsv when it fails with this error.
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            CliError::Other(_) => 1,
+            CliError::Flag(_) | CliError::Usage(_) => 2,
+            CliError::Io(_) => 3,
+            CliError::Csv(_) => 4,
+            CliError::StaleIndex(_) => 5,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match *self {
+            CliError::Other(_) => "other",
+            CliError::Flag(_) | CliError::Usage(_) => "usage",
+            CliError::Io(_) => "io",
+            CliError::Csv(_) => "csv",
+            CliError::StaleIndex(_) => "stale_index",
+        }
+    }
+
+    /// This error as a JSON object, with its kind, exit status and message
+    /// and, for CSV errors, the position in the CSV data where it occurred.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut obj = serde_json::Map::new();
+        obj.insert("kind".to_owned(), self.kind().into());
+        obj.insert("exit_code".to_owned(), self.exit_code().into());
+        obj.insert("message".to_owned(), self.to_string().into());
+        if let CliError::Csv(ref err) = *self {
+            let pos = match *err.kind() {
+                csv::ErrorKind::Utf8 { ref pos, .. }
+                | csv::ErrorKind::UnequalLengths { ref pos, .. }
+                | csv::ErrorKind::Deserialize { ref pos, .. } => pos.as_ref(),
+                _ => None,
+            };
+            if let Some(pos) = pos {
+                obj.insert("position".to_owned(), json!({
+                    "byte": pos.byte(),
+                    "line": pos.line(),
+                    "record": pos.record(),
+                }));
+            }
+        }
+        serde_json::Value::Object(obj)
+    }
+}
+
 impl fmt::Display for CliError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             CliError::Flag(ref e) => { e.fmt(f) }
             CliError::Csv(ref e) => { e.fmt(f) }
             CliError::Io(ref e) => { e.fmt(f) }
+            CliError::StaleIndex(ref s) => { f.write_str(&**s) }
+            CliError::Usage(ref s) => { f.write_str(&**s) }
             CliError::Other(ref s) => { f.write_str(&**s) }
         }
     }
@@ -246,8 +414,27 @@ impl<'a> From<&'a str> for CliError {
     }
 }
 
+impl From<arrow::error::ArrowError> for CliError {
+    fn from(err: arrow::error::ArrowError) -> CliError {
+        CliError::Other(err.to_string())
+    }
+}
+
+impl From<parquet::errors::ParquetError> for CliError {
+    fn from(err: parquet::errors::ParquetError) -> CliError {
+        CliError::Other(err.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for CliError {
+    fn from(err: rusqlite::Error) -> CliError {
+        CliError::Other(err.to_string())
+    }
+}
+
 impl From<regex::Error> for CliError {
     fn from(err: regex::Error) -> CliError {
-        CliError::Other(format!("{:?}", err))
+        // Regexes are only compiled from the arguments of a command.
+        CliError::Usage(err.to_string())
     }
 }
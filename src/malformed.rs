@@ -0,0 +1,281 @@
+/*!
+Skipping or quarantining malformed records.
+
+By default, a record with a different number of fields than the first record
+(or that isn't valid UTF-8, for commands that need text) is an error that
+stops the command. With `--on-error skip`, such records are
+dropped instead, and with `--on-error quarantine=<file>` they are also
+written to `<file>` (as CSV with the columns `file`, `line`, `error` and
+`record`). Either way, the number of malformed records is written to stderr
+when the command finishes.
+
+The records are filtered before any command sees them, so with `skip` or
+`quarantine`, a record that isn't valid UTF-8 is dropped by every command,
+including the ones that would have handled it without an error.
+
+Malformed records are removed from the raw CSV data before it is parsed (see
+`RecordFilter`), so that every command reading CSV data through
+`Config::reader` gets the same behavior. Since that needs a sequential
+reader, an index isn't used with another policy than `fail`, and commands
+that can only read a file by byte offset (e.g., `index` and `join`) refuse
+it.
+*/
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::str;
+use std::sync::Mutex;
+
+use csv;
+use csv_core::{self, ReadRecordResult};
+
+/// What to do with a malformed record.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum OnError {
+    #[default]
+    Fail,
+    Skip,
+    Quarantine(PathBuf),
+}
+
+impl OnError {
+    /// Parse a policy, which is one of `fail`, `skip` or
+    /// `quarantine=<file>`.
+    pub fn from_arg(arg: &str) -> Result<OnError, String> {
+        match arg {
+            "fail" => Ok(OnError::Fail),
+            "skip" => Ok(OnError::Skip),
+            _ if arg.starts_with("quarantine=") => {
+                let path = &arg["quarantine=".len()..];
+                if path.is_empty() {
+                    return Err("--on-error quarantine requires a file, e.g., \
+                                quarantine=bad.csv".to_owned());
+                }
+                Ok(OnError::Quarantine(PathBuf::from(path)))
+            }
+            _ => Err(format!("Unknown --on-error policy '{}' (expected one \
+                              of fail, skip or quarantine=<file>).", arg)),
+        }
+    }
+}
+
+/// The malformed records found so far, by input and line number. A command
+/// may read the same input more than once, but each record is only reported
+/// once.
+#[derive(Default)]
+struct Report {
+    seen: HashSet<(Option<PathBuf>, u64)>,
+    wtr: Option<csv::Writer<fs::File>>,
+}
+
+static REPORT: Mutex<Option<Report>> = Mutex::new(None);
+
+impl Report {
+    fn writer(&mut self, path: &PathBuf)
+             -> io::Result<&mut csv::Writer<fs::File>> {
+        if self.wtr.is_none() {
+            let mut wtr = csv::Writer::from_writer(fs::File::create(path)?);
+            wtr.write_record(["file", "line", "error", "record"])?;
+            self.wtr = Some(wtr);
+        }
+        Ok(self.wtr.as_mut().unwrap())
+    }
+}
+
+fn report(
+    policy: &OnError,
+    input: &Option<PathBuf>,
+    line: u64,
+    err: &str,
+    record: &[u8],
+) -> io::Result<()> {
+    let mut report = REPORT.lock().unwrap();
+    let report = report.get_or_insert_with(Report::default);
+    if !report.seen.insert((input.clone(), line)) {
+        return Ok(());
+    }
+    if let OnError::Quarantine(ref path) = *policy {
+        let input = match *input {
+            None => "<stdin>".to_owned(),
+            Some(ref p) => p.display().to_string(),
+        };
+        report.writer(path)?.write_record([
+            input.as_bytes(), line.to_string().as_bytes(), err.as_bytes(),
+            record,
+        ])?;
+    }
+    Ok(())
+}
+
+/// Finish writing the quarantine file, and write the number of malformed
+/// records to stderr. This should be called once, when the command is done.
+pub fn finish(policy: &OnError) -> io::Result<()> {
+    let mut report = REPORT.lock().unwrap();
+    let report = report.get_or_insert_with(Report::default);
+    let count = report.seen.len();
+    match *policy {
+        OnError::Fail => {}
+        OnError::Skip => {
+            if count > 0 {
+                werr!("Skipped {} malformed record(s).", count);
+            }
+        }
+        OnError::Quarantine(ref path) => {
+            // The file is written even without malformed records, so that
+            // an old quarantine file isn't mistaken for a new one.
+            report.writer(path)?.flush()?;
+            if count > 0 {
+                werr!("Quarantined {} malformed record(s) in {}.",
+                      count, path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A reader of raw CSV data that drops malformed records, i.e., records
+/// with a different number of fields than the first record, or that aren't
+/// valid UTF-8.
+pub struct RecordFilter<R> {
+    rdr: R,
+    csv: csv_core::Reader,
+    policy: OnError,
+    input: Option<PathBuf>,
+    /// The number of fields in the first record.
+    expected: Option<usize>,
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+    eof: bool,
+    /// The raw bytes of the record being parsed.
+    record: Vec<u8>,
+    fields: Vec<u8>,
+    nfields: usize,
+    ends: Vec<usize>,
+    nends: usize,
+    /// Raw bytes of well formed records, waiting to be read.
+    out: Vec<u8>,
+    out_pos: usize,
+    done: bool,
+}
+
+impl<R: Read> RecordFilter<R> {
+    pub fn new(
+        rdr: R,
+        csv: csv_core::Reader,
+        policy: OnError,
+        input: Option<PathBuf>,
+    ) -> RecordFilter<R> {
+        RecordFilter {
+            rdr: rdr,
+            csv: csv,
+            policy: policy,
+            input: input,
+            expected: None,
+            buf: vec![0; 64 * (1<<10)],
+            pos: 0,
+            len: 0,
+            eof: false,
+            record: vec![],
+            fields: vec![0; 1<<10],
+            nfields: 0,
+            ends: vec![0; 64],
+            nends: 0,
+            out: vec![],
+            out_pos: 0,
+            done: false,
+        }
+    }
+
+    /// Parse the next record, and add it to `out` if it is well formed.
+    fn next_record(&mut self) -> io::Result<()> {
+        loop {
+            if self.pos == self.len && !self.eof {
+                self.pos = 0;
+                self.len = self.rdr.read(&mut self.buf)?;
+                self.eof = self.len == 0;
+            }
+            let input = &self.buf[self.pos..self.len];
+            let (res, nin, nout, nend) = self.csv.read_record(
+                input,
+                &mut self.fields[self.nfields..],
+                &mut self.ends[self.nends..],
+            );
+            self.record.extend_from_slice(&input[..nin]);
+            self.pos += nin;
+            self.nfields += nout;
+            self.nends += nend;
+            match res {
+                ReadRecordResult::InputEmpty => {}
+                ReadRecordResult::OutputFull => {
+                    let len = self.fields.len();
+                    self.fields.resize(len * 2, 0);
+                }
+                ReadRecordResult::OutputEndsFull => {
+                    let len = self.ends.len();
+                    self.ends.resize(len * 2, 0);
+                }
+                ReadRecordResult::Record => {
+                    let nfields = self.nends;
+                    let utf8 =
+                        str::from_utf8(&self.fields[..self.nfields]).is_ok();
+                    self.nfields = 0;
+                    self.nends = 0;
+                    return self.finish_record(nfields, utf8);
+                }
+                ReadRecordResult::End => {
+                    self.out.append(&mut self.record);
+                    self.done = true;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn finish_record(&mut self, nfields: usize, utf8: bool)
+                    -> io::Result<()> {
+        let expected = *self.expected.get_or_insert(nfields);
+        let err = if nfields != expected {
+            format!("found record with {} fields, but the first record has \
+                     {} fields", nfields, expected)
+        } else if !utf8 {
+            "found record that is not valid UTF-8".to_owned()
+        } else {
+            self.out.append(&mut self.record);
+            return Ok(());
+        };
+        // Blank lines before the record are not part of it. (They may
+        // include the end of the previous record.)
+        let start = self.record.iter()
+            .position(|&b| b != b'\r' && b != b'\n')
+            .unwrap_or(self.record.len());
+        let raw = &self.record[start..];
+        let newlines = raw.iter().filter(|&&b| b == b'\n').count() as u64;
+        let line = self.csv.line() - newlines;
+        let end = raw.iter()
+            .rposition(|&b| b != b'\r' && b != b'\n')
+            .map_or(0, |i| i + 1);
+        report(&self.policy, &self.input, line, &err, &raw[..end])?;
+        self.record.clear();
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for RecordFilter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.out_pos == self.out.len() {
+            self.out.clear();
+            self.out_pos = 0;
+            if self.done {
+                return Ok(0);
+            }
+            self.next_record()?;
+        }
+        let n = buf.len().min(self.out.len() - self.out_pos);
+        buf[..n].copy_from_slice(&self.out[self.out_pos..self.out_pos + n]);
+        self.out_pos += n;
+        Ok(n)
+    }
+}
@@ -9,6 +9,10 @@ use std::str::FromStr;
 use csv;
 use serde::de::{Deserializer, Deserialize, Error};
 
+use {CliError, CliResult};
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
 #[derive(Clone)]
 pub struct SelectColumns {
     selectors: Vec<Selector>,
@@ -30,11 +34,15 @@ impl SelectColumns {
         })
     }
 
+    /// The indices selected in a record whose header row is `first_record`.
+    ///
+    /// A selection that doesn't match the header row is an error in the
+    /// arguments of the command.
     pub fn selection(
         &self,
         first_record: &csv::ByteRecord,
         use_names: bool,
-    ) -> Result<Selection, String> {
+    ) -> CliResult<Selection> {
         if self.selectors.is_empty() {
             return Ok(Selection(if self.invert {
                 // Inverting everything means we get nothing.
@@ -47,7 +55,7 @@ impl SelectColumns {
         let mut map = vec![];
         for sel in &self.selectors {
             let idxs = sel.indices(first_record, use_names);
-            map.extend(idxs?.into_iter());
+            map.extend(idxs.map_err(CliError::Usage)?.into_iter());
         }
         if self.invert {
             let set: HashSet<_> = map.into_iter().collect();
@@ -300,6 +308,13 @@ impl OneSelector {
                 }
                 let mut num_found = 0;
                 for (i, field) in first_record.iter().enumerate() {
+                    // The first name may start with a UTF-8 BOM when the
+                    // data is read as is (e.g., by `join`).
+                    let field = if i == 0 && field.starts_with(UTF8_BOM) {
+                        &field[UTF8_BOM.len()..]
+                    } else {
+                        field
+                    };
                     if field == s.as_bytes() {
                         if num_found == sidx {
                             return Ok(i);
@@ -0,0 +1,346 @@
+/*!
+Mergeable summaries of data that use a bounded amount of memory.
+
+Every summary here implements `Commute`, so that summaries computed
+independently (e.g., on different chunks of an indexed CSV file) can be
+combined into one.
+*/
+
+use std::cmp::{self, Ordering};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::f64::consts::PI;
+use std::hash::Hasher;
+
+use stats::Commute;
+
+/// The number of buffered samples, as a multiple of the compression
+/// parameter, that triggers a compression of a `TDigest`.
+const TDIGEST_BUFFER_FACTOR: usize = 5;
+
+/// A t-digest for estimating quantiles of a stream of numbers.
+///
+/// A t-digest summarizes a distribution as a sorted list of weighted
+/// centroids. Centroids near the tails of the distribution are kept small,
+/// which makes estimates of extreme quantiles (like the 99th percentile)
+/// much more accurate than estimates of the median. The number of centroids
+/// is bounded by roughly `compression`, regardless of how many samples are
+/// added.
+///
+/// This is the "merging" variant described in Ted Dunning's paper,
+/// "Computing Extremely Accurate Quantiles Using t-Digests."
+#[derive(Clone, Debug)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<Centroid>,
+    buffer: Vec<f64>,
+    min: f64,
+    max: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+impl TDigest {
+    /// Create a new empty t-digest.
+    ///
+    /// Larger values of `compression` give more accurate estimates at the
+    /// cost of more memory. A value of `100` is a good default.
+    pub fn new(compression: f64) -> TDigest {
+        TDigest {
+            compression,
+            centroids: vec![],
+            buffer: vec![],
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Add a sample to this digest. `NaN` samples are ignored.
+    pub fn add(&mut self, sample: f64) {
+        if sample.is_nan() {
+            return;
+        }
+        self.min = self.min.min(sample);
+        self.max = self.max.max(sample);
+        self.buffer.push(sample);
+        let limit = TDIGEST_BUFFER_FACTOR * self.compression as usize;
+        if self.buffer.len() >= limit {
+            self.compress();
+        }
+    }
+
+    /// Estimate the value at quantile `q`, which must be in `[0, 1]`.
+    ///
+    /// Returns `None` if the digest is empty.
+    pub fn quantile(&mut self, q: f64) -> Option<f64> {
+        self.compress();
+        let cs = &self.centroids;
+        if cs.is_empty() {
+            return None;
+        }
+        if cs.len() == 1 {
+            return Some(cs[0].mean);
+        }
+        let total: f64 = cs.iter().map(|c| c.weight).sum();
+        let target = q * total;
+        // Each centroid's weight is assumed to be centered on its mean, so
+        // interpolate between the centers of neighboring centroids.
+        let first_center = cs[0].weight / 2.0;
+        if target <= first_center {
+            return Some(interpolate(
+                self.min, cs[0].mean, target / first_center));
+        }
+        let mut center = first_center;
+        for w in cs.windows(2) {
+            let next_center = center + (w[0].weight + w[1].weight) / 2.0;
+            if target <= next_center {
+                let t = (target - center) / (next_center - center);
+                return Some(interpolate(w[0].mean, w[1].mean, t));
+            }
+            center = next_center;
+        }
+        let last = cs[cs.len() - 1];
+        let t = (target - center) / (total - center);
+        Some(interpolate(last.mean, self.max, t))
+    }
+
+    /// Merge buffered samples into the list of centroids.
+    fn compress(&mut self) {
+        if !self.buffer.is_empty() {
+            self.recompress();
+        }
+    }
+
+    /// Merge buffered samples and centroids into a new list of centroids
+    /// that respects the size bounds imposed by the scale function.
+    fn recompress(&mut self) {
+        let mut all = Vec::with_capacity(
+            self.centroids.len() + self.buffer.len());
+        all.append(&mut self.centroids);
+        all.extend(self.buffer.drain(..).map(|mean| {
+            Centroid { mean, weight: 1.0 }
+        }));
+        if all.is_empty() {
+            return;
+        }
+        all.sort_by(|c1, c2| {
+            c1.mean.partial_cmp(&c2.mean).unwrap_or(Ordering::Equal)
+        });
+
+        let total: f64 = all.iter().map(|c| c.weight).sum();
+        let mut merged = Vec::with_capacity(self.compression as usize);
+        let mut cur = all[0];
+        let mut weight_so_far = 0.0;
+        let mut weight_limit = total * self.k_inverse(self.k(0.0) + 1.0);
+        for &c in &all[1..] {
+            if weight_so_far + cur.weight + c.weight <= weight_limit {
+                cur.mean += (c.mean - cur.mean) * c.weight
+                            / (cur.weight + c.weight);
+                cur.weight += c.weight;
+            } else {
+                weight_so_far += cur.weight;
+                merged.push(cur);
+                let q = weight_so_far / total;
+                weight_limit = total * self.k_inverse(self.k(q) + 1.0);
+                cur = c;
+            }
+        }
+        merged.push(cur);
+        self.centroids = merged;
+    }
+
+    /// The scale function, which maps a quantile to an index in
+    /// `[-compression/4, compression/4]`. Each centroid spans at most one
+    /// unit of this index.
+    fn k(&self, q: f64) -> f64 {
+        self.compression / (2.0 * PI) * (2.0 * q - 1.0).asin()
+    }
+
+    /// The inverse of the scale function.
+    fn k_inverse(&self, k: f64) -> f64 {
+        let k = k.min(self.compression / 4.0);
+        ((k * 2.0 * PI / self.compression).sin() + 1.0) / 2.0
+    }
+}
+
+impl Commute for TDigest {
+    fn merge(&mut self, mut other: TDigest) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.centroids.append(&mut other.centroids);
+        self.buffer.append(&mut other.buffer);
+        self.recompress();
+    }
+}
+
+fn interpolate(from: f64, to: f64, t: f64) -> f64 {
+    from + (to - from) * t.clamp(0.0, 1.0)
+}
+
+/// The number of bits of a hash used to pick a `HyperLogLog` register.
+const HLL_PRECISION: u32 = 14;
+
+/// A HyperLogLog for estimating the number of distinct values in a stream.
+///
+/// It uses `2^14` one byte registers regardless of the number of values
+/// added, and has a standard error of about 0.8%. Small cardinalities are
+/// estimated with linear counting, which is nearly exact.
+#[derive(Clone)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    /// Create a new empty HyperLogLog.
+    pub fn new() -> HyperLogLog {
+        HyperLogLog { registers: vec![0; 1 << HLL_PRECISION] }
+    }
+
+    /// Add a value to this HyperLogLog.
+    pub fn add(&mut self, value: &[u8]) {
+        // `DefaultHasher::new` always uses the same keys, so hashes are
+        // consistent across HyperLogLogs that are later merged.
+        let mut hasher = DefaultHasher::new();
+        hasher.write(value);
+        let hash = hasher.finish();
+
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        // The position of the leftmost 1 bit in the remaining bits. A sentinel
+        // bit bounds the rank when the remaining bits are all zero.
+        let rest = (hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    /// Estimate the number of distinct values added.
+    pub fn count(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter()
+                                     .map(|&r| 2f64.powi(-(r as i32)))
+                                     .sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+impl Default for HyperLogLog {
+    fn default() -> HyperLogLog {
+        HyperLogLog::new()
+    }
+}
+
+impl Commute for HyperLogLog {
+    fn merge(&mut self, other: HyperLogLog) {
+        for (r1, r2) in self.registers.iter_mut().zip(other.registers) {
+            if r2 > *r1 {
+                *r1 = r2;
+            }
+        }
+    }
+}
+
+/// A summary of the most frequent values in a stream.
+///
+/// This is the Misra-Gries algorithm with batched decrements, which is
+/// mergeable as shown by Agarwal et al. in "Mergeable Summaries". At most
+/// `2 * capacity` values are tracked at any time. The count reported for a
+/// value never exceeds its true count, and falls short of it by at most
+/// `error()`, which itself is at most `N / capacity` where `N` is the number
+/// of values added. Values that are
+/// not tracked have occurred at most `error()` times.
+#[derive(Clone)]
+pub struct HeavyHitters {
+    capacity: usize,
+    counts: HashMap<Vec<u8>, u64>,
+    error: u64,
+    len: u64,
+}
+
+impl HeavyHitters {
+    /// Create a new summary that keeps track of the `capacity` most frequent
+    /// values.
+    pub fn new(capacity: usize) -> HeavyHitters {
+        HeavyHitters {
+            capacity: cmp::max(1, capacity),
+            counts: HashMap::new(),
+            error: 0,
+            len: 0,
+        }
+    }
+
+    /// Add an occurrence of `value`.
+    pub fn add(&mut self, value: &[u8]) {
+        self.len += 1;
+        if let Some(count) = self.counts.get_mut(value) {
+            *count += 1;
+            return;
+        }
+        self.counts.insert(value.to_vec(), 1);
+        if self.counts.len() >= 2 * self.capacity {
+            self.prune();
+        }
+    }
+
+    /// Returns the number of values added, including untracked ones.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns the maximum amount by which any reported count may be
+    /// smaller than the true count.
+    pub fn error(&self) -> u64 {
+        self.error
+    }
+
+    /// Returns the tracked values and their counts, most frequent first.
+    pub fn most_frequent(&self) -> Vec<(&Vec<u8>, u64)> {
+        let mut counts: Vec<_> =
+            self.counts.iter().map(|(v, &c)| (v, c)).collect();
+        counts.sort_by(|&(v1, c1), &(v2, c2)| {
+            c2.cmp(&c1).then_with(|| v1.cmp(v2))
+        });
+        counts
+    }
+
+    /// Reduce the number of tracked values to at most `capacity`, by
+    /// subtracting the count of the `capacity + 1`th most frequent value
+    /// from every count.
+    fn prune(&mut self) {
+        if self.counts.len() <= self.capacity {
+            return;
+        }
+        let mut counts: Vec<u64> = self.counts.values().cloned().collect();
+        counts.sort_by(|c1, c2| c2.cmp(c1));
+        let decrement = counts[self.capacity];
+        self.counts.retain(|_, count| {
+            *count = count.saturating_sub(decrement);
+            *count > 0
+        });
+        self.error += decrement;
+    }
+}
+
+impl Commute for HeavyHitters {
+    fn merge(&mut self, other: HeavyHitters) {
+        for (value, count) in other.counts {
+            *self.counts.entry(value).or_insert(0) += count;
+        }
+        self.error += other.error;
+        self.len += other.len;
+        if self.counts.len() >= 2 * self.capacity {
+            self.prune();
+        }
+    }
+}
@@ -0,0 +1,222 @@
+/*!
+Inference of the dialect of CSV data from a sample of it.
+
+The delimiter and quote character are chosen by parsing the sample with
+every candidate, and picking the one whose records most consistently have the
+same number of fields (more than one). The presence of a header row is
+guessed by comparing the type of each field in the first row with the type of
+the rest of its column, e.g., a column of integers is unlikely to start with
+an integer header.
+*/
+
+use std::fmt;
+
+use csv;
+use stats::Commute;
+
+use types::FieldType;
+
+/// Candidate delimiters, in order of preference.
+const DELIMITERS: &[u8] = b",\t;|:";
+
+/// Candidate quote characters, in order of preference.
+const QUOTES: &[u8] = b"\"'";
+
+/// The inferred dialect of CSV data.
+#[derive(Clone, Debug)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    /// The escape character, if quotes are escaped with it instead of by
+    /// doubling them.
+    pub escape: Option<u8>,
+    pub has_headers: bool,
+    pub terminator: LineTerminator,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineTerminator {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl LineTerminator {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineTerminator::Lf => "\n",
+            LineTerminator::CrLf => "\r\n",
+            LineTerminator::Cr => "\r",
+        }
+    }
+}
+
+impl fmt::Display for LineTerminator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LineTerminator::Lf => write!(f, "LF"),
+            LineTerminator::CrLf => write!(f, "CRLF"),
+            LineTerminator::Cr => write!(f, "CR"),
+        }
+    }
+}
+
+/// Infer the dialect of CSV data that starts with `sample`.
+///
+/// If `truncated` is true, then the sample is the start of longer data, and
+/// its last (possibly partial) line is ignored.
+pub fn sniff(sample: &[u8], truncated: bool) -> Dialect {
+    let terminator = terminator(sample);
+    let end = match terminator.as_str().as_bytes().last() {
+        Some(&b) if truncated => {
+            sample.iter().rposition(|&c| c == b).map_or(0, |i| i + 1)
+        }
+        _ => sample.len(),
+    };
+    let sample = &sample[..end];
+
+    let mut best = Parse::new(sample, b',', b'"', None);
+    if best.ncols <= 1 {
+        best.score = 0.0;
+    }
+    for &delim in DELIMITERS {
+        for &quote in QUOTES {
+            let parse = Parse::new(sample, delim, quote, None);
+            if parse.ncols > 1 && parse.score > best.score {
+                best = parse;
+            }
+        }
+    }
+    // Quotes may be escaped with a backslash instead of being doubled.
+    let escaped = [b'\\', best.quote];
+    if sample.windows(2).any(|w| w == escaped) {
+        let parse =
+            Parse::new(sample, best.delimiter, best.quote, Some(b'\\'));
+        if parse.score >= best.score {
+            best = parse;
+        }
+    }
+    Dialect {
+        delimiter: best.delimiter,
+        quote: best.quote,
+        escape: best.escape,
+        has_headers: has_headers(&best.records),
+        terminator: terminator,
+    }
+}
+
+/// The result of parsing a sample with one candidate dialect.
+struct Parse {
+    delimiter: u8,
+    quote: u8,
+    escape: Option<u8>,
+    records: Vec<csv::ByteRecord>,
+    /// The most common number of fields in a record.
+    ncols: usize,
+    /// The fraction of records with `ncols` fields.
+    score: f64,
+}
+
+impl Parse {
+    fn new(
+        sample: &[u8],
+        delimiter: u8,
+        quote: u8,
+        escape: Option<u8>,
+    ) -> Parse {
+        let mut rdr = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .quote(quote)
+            .escape(escape)
+            .double_quote(escape.is_none())
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(sample);
+        // A parse error just ends the sample early.
+        let records: Vec<csv::ByteRecord> =
+            rdr.byte_records().take_while(|r| r.is_ok())
+               .map(|r| r.unwrap()).collect();
+        let mut counts: Vec<(usize, usize)> = vec![];
+        for r in &records {
+            match counts.iter_mut().find(|c| c.0 == r.len()) {
+                Some(c) => c.1 += 1,
+                None => counts.push((r.len(), 1)),
+            }
+        }
+        let (ncols, freq) = counts.into_iter()
+            .max_by_key(|&(ncols, freq)| (freq, ncols))
+            .unwrap_or((0, 0));
+        let score = if records.is_empty() {
+            0.0
+        } else {
+            freq as f64 / records.len() as f64
+        };
+        Parse {
+            delimiter: delimiter,
+            quote: quote,
+            escape: escape,
+            records: records,
+            ncols: ncols,
+            score: score,
+        }
+    }
+}
+
+fn terminator(sample: &[u8]) -> LineTerminator {
+    match sample.iter().position(|&b| b == b'\n') {
+        Some(i) if i > 0 && sample[i-1] == b'\r' => LineTerminator::CrLf,
+        Some(_) => LineTerminator::Lf,
+        None if sample.contains(&b'\r') => LineTerminator::Cr,
+        None => LineTerminator::Lf,
+    }
+}
+
+/// Guess whether the first record is a header row.
+///
+/// Each column votes for a header row if its first field has a different
+/// type (or, for text, a different length) than the rest of the column, and
+/// against it if the first field is a number or date like the rest. Without
+/// any votes, a header row is assumed, as it is everywhere else in xsv.
+fn has_headers(records: &[csv::ByteRecord]) -> bool {
+    let (first, rest) = match records.split_first() {
+        Some((first, rest)) if !rest.is_empty() => (first, rest),
+        _ => return true,
+    };
+    let mut votes = 0i64;
+    for (i, header) in first.iter().enumerate() {
+        let mut typ = FieldType::default();
+        let mut lengths = vec![];
+        for field in rest.iter().filter_map(|r| r.get(i)) {
+            typ.merge(FieldType::from_sample(field));
+            if !field.is_empty() {
+                lengths.push(field.len());
+            }
+        }
+        let header_typ = FieldType::from_sample(header);
+        match typ {
+            FieldType::TInteger | FieldType::TFloat
+            | FieldType::TDate | FieldType::TDateTime => {
+                if header_typ == FieldType::TUnicode {
+                    votes += 1;
+                } else if is_like(header_typ, typ) {
+                    votes -= 1;
+                }
+            }
+            FieldType::TUnicode => {
+                let same = lengths.windows(2).all(|w| w[0] == w[1]);
+                if same && !lengths.is_empty() && lengths[0] != header.len() {
+                    votes += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    votes >= 0
+}
+
+/// Whether a field of type `a` could be a value in a column of type `b`.
+fn is_like(a: FieldType, b: FieldType) -> bool {
+    (a.is_number() && b.is_number())
+    || (a == b)
+    || (a == FieldType::TDate && b == FieldType::TDateTime)
+}
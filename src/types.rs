@@ -0,0 +1,370 @@
+/*!
+Inference of the types of CSV values.
+
+The type of a column is found by inferring the type of each of its values
+with `FieldType::from_sample`, and merging them. Dates and timestamps are
+recognized in a few common formats (see `Date`).
+*/
+
+use std::fmt;
+use std::str;
+
+use stats::Commute;
+
+use self::FieldType::{
+    TUnknown, TNull, TUnicode, TFloat, TInteger, TDate, TDateTime,
+};
+
+/// The type of a field, as inferred from its values.
+#[derive(Clone, Copy, PartialEq)]
+pub enum FieldType {
+    TUnknown,
+    TNull,
+    TUnicode,
+    TFloat,
+    TInteger,
+    TDate,
+    TDateTime,
+}
+
+impl FieldType {
+    pub fn from_sample(sample: &[u8]) -> FieldType {
+        if sample.is_empty() {
+            return TNull;
+        }
+        let string = match str::from_utf8(sample) {
+            Err(_) => return TUnknown,
+            Ok(s) => s,
+        };
+        if let Ok(_) = string.parse::<i64>() { return TInteger; }
+        if let Ok(_) = string.parse::<f64>() { return TFloat; }
+        match Date::from_sample(sample) {
+            Some(Date { has_time: false, .. }) => TDate,
+            Some(Date { has_time: true, .. }) => TDateTime,
+            None => TUnicode,
+        }
+    }
+
+    pub fn is_number(&self) -> bool {
+        *self == TFloat || *self == TInteger
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == TNull
+    }
+
+    pub fn is_date(&self) -> bool {
+        *self == TDate || *self == TDateTime
+    }
+}
+
+impl Commute for FieldType {
+    fn merge(&mut self, other: FieldType) {
+        *self = match (*self, other) {
+            (TUnicode, TUnicode) => TUnicode,
+            (TFloat, TFloat) => TFloat,
+            (TInteger, TInteger) => TInteger,
+            (TDate, TDate) => TDate,
+            (TDateTime, TDateTime) => TDateTime,
+            // Null does not impact the type.
+            (TNull, any) | (any, TNull) => any,
+            // There's no way to get around an unknown.
+            (TUnknown, _) | (_, TUnknown) => TUnknown,
+            // Integers can degrate to floats.
+            (TFloat, TInteger) | (TInteger, TFloat) => TFloat,
+            // Numbers can degrade to Unicode strings.
+            (TUnicode, TFloat) | (TFloat, TUnicode) => TUnicode,
+            (TUnicode, TInteger) | (TInteger, TUnicode) => TUnicode,
+            // Dates without a time of day can be widened to timestamps.
+            (TDate, TDateTime) | (TDateTime, TDate) => TDateTime,
+            // Dates mixed with anything else are just Unicode strings.
+            (TDate, _) | (_, TDate) => TUnicode,
+            (TDateTime, _) | (_, TDateTime) => TUnicode,
+        };
+    }
+}
+
+impl Default for FieldType {
+    // The default is the most specific type.
+    // Type inference proceeds by assuming the most specific type and then
+    // relaxing the type as counter-examples are found.
+    fn default() -> FieldType { TNull }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TUnknown => write!(f, "Unknown"),
+            TNull => write!(f, "NULL"),
+            TUnicode => write!(f, "Unicode"),
+            TFloat => write!(f, "Float"),
+            TInteger => write!(f, "Integer"),
+            TDate => write!(f, "Date"),
+            TDateTime => write!(f, "DateTime"),
+        }
+    }
+}
+
+pub const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+/// DateFormat identifies how a date or timestamp was written.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DateFormat {
+    /// `YYYY-MM-DD`, optionally followed by a time without a UTC offset.
+    Iso8601,
+    /// `YYYY-MM-DDTHH:MM:SS` followed by `Z` or a UTC offset.
+    Rfc3339,
+    /// `MM/DD/YYYY`, optionally followed by a time.
+    MonthDayYear,
+    /// Integer seconds since the Unix epoch.
+    EpochSeconds,
+    /// More than one of the above was seen in the same column.
+    Mixed,
+}
+
+impl DateFormat {
+    /// Returns true when timestamps in this format are known to be in UTC.
+    pub fn is_utc(&self) -> bool {
+        *self == DateFormat::Rfc3339 || *self == DateFormat::EpochSeconds
+    }
+}
+
+impl Commute for DateFormat {
+    fn merge(&mut self, other: DateFormat) {
+        if *self != other {
+            *self = DateFormat::Mixed;
+        }
+    }
+}
+
+impl fmt::Display for DateFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DateFormat::Iso8601 => write!(f, "ISO 8601"),
+            DateFormat::Rfc3339 => write!(f, "RFC 3339"),
+            DateFormat::MonthDayYear => write!(f, "MM/DD/YYYY"),
+            DateFormat::EpochSeconds => write!(f, "epoch seconds"),
+            DateFormat::Mixed => write!(f, "mixed"),
+        }
+    }
+}
+
+/// Parse a date or timestamp in any of the formats recognized by type
+/// inference, returning milliseconds since the Unix epoch in UTC.
+pub fn parse_date_millis(sample: &[u8]) -> Option<i64> {
+    Date::from_sample(sample).map(|date| date.millis)
+}
+
+/// Date is a date or timestamp recognized in a sample.
+///
+/// Its value is normalized to milliseconds since the Unix epoch in UTC. Dates
+/// and timestamps without a UTC offset are assumed to be in UTC.
+#[derive(Clone, Copy, Debug)]
+pub struct Date {
+    pub format: DateFormat,
+    pub millis: i64,
+    pub has_time: bool,
+}
+
+impl Date {
+    pub fn from_sample(sample: &[u8]) -> Option<Date> {
+        Date::parse_iso(sample).or_else(|| Date::parse_month_day_year(sample))
+    }
+
+    pub fn from_epoch_seconds(secs: i64) -> Option<Date> {
+        secs.checked_mul(1000).map(|millis| Date {
+            format: DateFormat::EpochSeconds,
+            millis,
+            has_time: true,
+        })
+    }
+
+    /// Parses `YYYY-MM-DD` with an optional time and UTC offset.
+    fn parse_iso(b: &[u8]) -> Option<Date> {
+        if b.len() < 10 || b[4] != b'-' || b[7] != b'-' {
+            return None;
+        }
+        let days = days_from_civil(
+            parse_digits(&b[0..4])?,
+            parse_digits(&b[5..7])?,
+            parse_digits(&b[8..10])?,
+        )?;
+        if b.len() == 10 {
+            return Some(Date {
+                format: DateFormat::Iso8601,
+                millis: days * MILLIS_PER_DAY,
+                has_time: false,
+            });
+        }
+        if b[10] != b'T' && b[10] != b't' && b[10] != b' ' {
+            return None;
+        }
+        let (time, rest) = parse_time(&b[11..])?;
+        let (format, offset) = match rest {
+            b"" => (DateFormat::Iso8601, 0),
+            b"Z" | b"z" => (DateFormat::Rfc3339, 0),
+            _ => (DateFormat::Rfc3339, parse_utc_offset(rest)?),
+        };
+        Some(Date {
+            format,
+            millis: days * MILLIS_PER_DAY + time - offset,
+            has_time: true,
+        })
+    }
+
+    /// Parses `MM/DD/YYYY` with an optional time.
+    fn parse_month_day_year(b: &[u8]) -> Option<Date> {
+        let (date, time) = match b.iter().position(|&c| c == b' ') {
+            None => (b, None),
+            Some(i) => (&b[..i], Some(&b[i+1..])),
+        };
+        let mut parts = date.split(|&c| c == b'/');
+        let (month, day, year) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some()
+            || month.len() > 2 || day.len() > 2 || year.len() != 4 {
+            return None;
+        }
+        let days = days_from_civil(
+            parse_digits(year)?,
+            parse_digits(month)?,
+            parse_digits(day)?,
+        )?;
+        let time = match time {
+            None => None,
+            Some(time) => match parse_time(time)? {
+                (millis, b"") => Some(millis),
+                _ => return None,
+            },
+        };
+        Some(Date {
+            format: DateFormat::MonthDayYear,
+            millis: days * MILLIS_PER_DAY + time.unwrap_or(0),
+            has_time: time.is_some(),
+        })
+    }
+}
+
+/// Parses `HH:MM`, `HH:MM:SS` or `HH:MM:SS.fff` into milliseconds since
+/// midnight. The unparsed remainder of `b` is returned too.
+fn parse_time(b: &[u8]) -> Option<(i64, &[u8])> {
+    if b.len() < 5 || b[2] != b':' {
+        return None;
+    }
+    let (hour, minute) = (parse_digits(&b[0..2])?, parse_digits(&b[3..5])?);
+    let (mut second, mut millis, mut i) = (0, 0, 5);
+    if b.len() >= 8 && b[5] == b':' {
+        second = parse_digits(&b[6..8])?;
+        i = 8;
+        if i < b.len() && (b[i] == b'.' || b[i] == b',') {
+            let ndigits = b[i+1..].iter()
+                                  .take_while(|c| c.is_ascii_digit())
+                                  .count();
+            if ndigits == 0 {
+                return None;
+            }
+            // Only millisecond precision is kept.
+            let frac = &b[i+1..i+1+ndigits];
+            for k in 0..3 {
+                millis = millis * 10 + frac.get(k).map_or(0, |&c| {
+                    (c - b'0') as i64
+                });
+            }
+            i += 1 + ndigits;
+        }
+    }
+    // A second of 60 is permitted to accommodate leap seconds.
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let millis = ((hour * 60 + minute) * 60 + second) * 1000 + millis;
+    Some((millis, &b[i..]))
+}
+
+/// Parses a UTC offset of the form `+HH`, `+HHMM` or `+HH:MM` (or with a
+/// `-` sign) into milliseconds.
+fn parse_utc_offset(b: &[u8]) -> Option<i64> {
+    let sign = match b.first() {
+        Some(&b'+') => 1,
+        Some(&b'-') => -1,
+        _ => return None,
+    };
+    let (hours, minutes) = match b.len() {
+        3 => (parse_digits(&b[1..3])?, 0),
+        5 => (parse_digits(&b[1..3])?, parse_digits(&b[3..5])?),
+        6 if b[3] == b':' => {
+            (parse_digits(&b[1..3])?, parse_digits(&b[4..6])?)
+        }
+        _ => return None,
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes) * 60 * 1000)
+}
+
+fn parse_digits(b: &[u8]) -> Option<i64> {
+    if b.is_empty() || !b.iter().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(b.iter().fold(0, |n, &c| n * 10 + (c - b'0') as i64))
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// Returns the number of days since 1970-01-01 for the given date, or `None`
+/// if the date does not exist.
+fn days_from_civil(year: i64, month: i64, day: i64) -> Option<i64> {
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => return None,
+    };
+    if day < 1 || day > days_in_month {
+        return None;
+    }
+    // See http://howardhinnant.github.io/date_algorithms.html
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some(era * 146_097 + doe - 719_468)
+}
+
+/// The inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = (if z >= 0 { z } else { z - 146_096 }) / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Formats milliseconds since the Unix epoch as an ISO 8601 date, or as a
+/// timestamp when `time` is true.
+pub fn show_date(millis: i64, time: bool, utc: bool) -> String {
+    let (year, month, day) = civil_from_days(millis.div_euclid(MILLIS_PER_DAY));
+    let mut s = format!("{:04}-{:02}-{:02}", year, month, day);
+    if time {
+        let ms = millis.rem_euclid(MILLIS_PER_DAY);
+        s.push_str(&format!(
+            "T{:02}:{:02}:{:02}",
+            ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60));
+        if ms % 1000 != 0 {
+            s.push_str(&format!(".{:03}", ms % 1000));
+        }
+        if utc {
+            s.push('Z');
+        }
+    }
+    s
+}
@@ -6,9 +6,9 @@ use std::str;
 use std::thread;
 use std::time;
 
-use csv;
 use docopt::Docopt;
 use num_cpus;
+use regex::Regex;
 use serde::de::{Deserializer, Deserialize, DeserializeOwned, Error};
 
 use CliResult;
@@ -41,7 +41,8 @@ pub fn get_args<T>(usage: &str, argv: &[&str]) -> CliResult<T>
 }
 
 pub fn many_configs(inps: &[String], delim: Option<Delimiter>,
-                    no_headers: bool) -> Result<Vec<Config>, String> {
+                    no_headers: bool, sniff: bool)
+                    -> Result<Vec<Config>, String> {
     let mut inps = inps.to_vec();
     if inps.is_empty() {
         inps.push("-".to_owned()); // stdin
@@ -49,7 +50,8 @@ pub fn many_configs(inps: &[String], delim: Option<Delimiter>,
     let confs = inps.into_iter()
                     .map(|p| Config::new(&Some(p))
                                     .delimiter(delim)
-                                    .no_headers(no_headers))
+                                    .no_headers(no_headers)
+                                    .sniff(sniff))
                     .collect::<Vec<_>>();
     errif_greater_one_stdin(&*confs)?;
     Ok(confs)
@@ -82,11 +84,6 @@ pub fn num_of_chunks(nitems: usize, chunk_size: usize) -> usize {
     n
 }
 
-pub fn last_modified(md: &fs::Metadata) -> u64 {
-    use filetime::FileTime;
-    FileTime::from_last_modification_time(md).seconds_relative_to_1970()
-}
-
 pub fn condense<'a>(val: Cow<'a, [u8]>, n: Option<usize>) -> Cow<'a, [u8]> {
     match n {
         None => val,
@@ -169,28 +166,39 @@ fn create_dir_all_threadsafe(path: &Path) -> io::Result<()> {
 
 /// Represents a filename template of the form `"{}.csv"`, where `"{}"` is
 /// the splace to insert the part of the filename generated by `xsv`.
+///
+/// The placeholder may instead be `"{seq}"`, for the sequence number of the
+/// file (starting at 0). Either one can be padded with zeros to a minimum
+/// width, e.g., `"{:04}"` or `"{seq:04}"`, so that the filenames sort in
+/// order.
 #[derive(Clone, Debug)]
 pub struct FilenameTemplate {
     prefix: String,
     suffix: String,
+    /// Whether the placeholder is `"{seq}"`.
+    seq: bool,
+    /// The minimum width of the inserted value, padded with zeros.
+    width: usize,
 }
 
 impl FilenameTemplate {
-    /// Generate a new filename using `unique_value` to replace the `"{}"`
-    /// in the template.
-    pub fn filename(&self, unique_value: &str) -> String {
-        format!("{}{}{}", &self.prefix, unique_value, &self.suffix)
-    }
-
-    /// Create a new, writable file in directory `path` with a filename
-    /// using `unique_value` to replace the `"{}"` in the template.  Note
-    /// that we do not output headers; the caller must do that if
-    /// desired.
-    pub fn writer<P>(&self, path: P, unique_value: &str)
-                 -> io::Result<csv::Writer<Box<io::Write+'static>>>
+    /// Generate a new filename using `unique_value` (or the sequence number
+    /// `seq` of the file) to replace the `"{}"` in the template.
+    pub fn filename(&self, unique_value: &str, seq: usize) -> String {
+        let seq = seq.to_string();
+        let value = if self.seq { &seq } else { unique_value };
+        format!("{}{:0>width$}{}",
+                &self.prefix, value, &self.suffix, width = self.width)
+    }
+
+    /// The path of the file in directory `path` with a filename using
+    /// `unique_value` (or `seq`) to replace the `"{}"` in the template.
+    /// The directories leading up to the file are created.
+    pub fn path<P>(&self, path: P, unique_value: &str, seq: usize)
+               -> io::Result<PathBuf>
         where P: AsRef<Path>
     {
-        let filename = self.filename(unique_value);
+        let filename = self.filename(unique_value, seq);
         let full_path = path.as_ref().join(filename);
         if let Some(parent) = full_path.parent() {
             // We may be called concurrently, especially by parallel `xsv
@@ -198,8 +206,34 @@ impl FilenameTemplate {
             // condition.
             create_dir_all_threadsafe(parent)?;
         }
-        let spath = Some(full_path.display().to_string());
-        Config::new(&spath).writer()
+        Ok(full_path)
+    }
+}
+
+impl FilenameTemplate {
+    /// Parse a template, which must contain one placeholder.
+    pub fn parse(raw: &str) -> Result<FilenameTemplate, String> {
+        let placeholder = Regex::new(r"\{(seq)?(?::0(\d+))?\}").unwrap();
+        let mut matches = placeholder.captures_iter(&raw);
+        match (matches.next(), matches.next()) {
+            (Some(caps), None) => {
+                let all = caps.get(0).unwrap();
+                let width = match caps.get(2) {
+                    None => 0,
+                    Some(w) => w.as_str().parse().map_err(|_| {
+                        format!("Invalid width in --filename '{}'.", raw)
+                    })?,
+                };
+                Ok(FilenameTemplate {
+                    prefix: raw[..all.start()].to_owned(),
+                    suffix: raw[all.end()..].to_owned(),
+                    seq: caps.get(1).is_some(),
+                    width: width,
+                })
+            }
+            _ => Err("The --filename argument must contain one '{}' (or \
+                      '{seq}').".to_owned()),
+        }
     }
 }
 
@@ -208,15 +242,84 @@ impl<'de> Deserialize<'de> for FilenameTemplate {
         d: D,
     ) -> Result<FilenameTemplate, D::Error> {
         let raw = String::deserialize(d)?;
-        let chunks = raw.split("{}").collect::<Vec<_>>();
-        if chunks.len() == 2 {
-            Ok(FilenameTemplate {
-                prefix: chunks[0].to_owned(),
-                suffix: chunks[1].to_owned(),
-            })
-        } else {
-            Err(D::Error::custom(
-                "The --filename argument must contain one '{}'."))
+        FilenameTemplate::parse(&raw).map_err(D::Error::custom)
+    }
+}
+
+/// The layout of the columns in fixed width data.
+#[derive(Clone, Debug)]
+pub struct FixedLayout {
+    /// The names of the columns, if they were given in a spec file.
+    pub names: Option<Vec<String>>,
+    /// The width of each column, in characters.
+    pub widths: Vec<usize>,
+}
+
+impl FixedLayout {
+    /// Read a layout from a CSV spec file with a `width` column and an
+    /// optional `name` column, with one row for each column.
+    pub fn from_spec(path: &str) -> CliResult<FixedLayout> {
+        let mut rdr = Config::new(&Some(path.to_owned())).reader()?;
+        let headers = rdr.headers()?.clone();
+        let find = |name: &str| headers.iter().position(|h| h == name);
+        let width_col = match find("width") {
+            None => return fail!(format!(
+                "spec file {} has no 'width' column", path)),
+            Some(i) => i,
+        };
+        let name_col = find("name");
+
+        let mut names = vec![];
+        let mut widths = vec![];
+        for row in rdr.records() {
+            let row = row?;
+            match row[width_col].trim().parse::<usize>() {
+                Ok(w) if w > 0 => widths.push(w),
+                _ => return fail!(format!(
+                    "could not parse '{}' in spec file {} as a width",
+                    &row[width_col], path)),
+            }
+            if let Some(i) = name_col {
+                names.push(row[i].to_owned());
+            }
+        }
+        Ok(FixedLayout {
+            names: name_col.map(|_| names),
+            widths,
+        })
+    }
+}
+
+/// FixedWidths is a comma separated list of column widths given on the
+/// command line, e.g., `10,5,20`.
+#[derive(Clone, Debug)]
+pub struct FixedWidths(pub Vec<usize>);
+
+impl<'de> Deserialize<'de> for FixedWidths {
+    fn deserialize<D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<FixedWidths, D::Error> {
+        let raw = String::deserialize(d)?;
+        let mut widths = vec![];
+        for s in raw.split(',') {
+            match s.trim().parse::<usize>() {
+                Ok(w) if w > 0 => widths.push(w),
+                _ => {
+                    let msg = format!("Could not parse '{}' as a column \
+                                       width.", s);
+                    return Err(D::Error::custom(msg));
+                }
+            }
         }
+        Ok(FixedWidths(widths))
+    }
+}
+
+/// Returns the width of a field in fixed width data, which is the number of
+/// characters if it is valid UTF-8, or the number of bytes otherwise.
+pub fn fixed_width(field: &[u8]) -> usize {
+    match str::from_utf8(field) {
+        Ok(s) => s.chars().count(),
+        Err(_) => field.len(),
     }
 }
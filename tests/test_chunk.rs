@@ -0,0 +1,110 @@
+use std::fs;
+
+use workdir::Workdir;
+
+/// Create a file large enough to be split into chunks, where many line
+/// terminators are inside quoted fields.
+fn setup(name: &str) -> Workdir {
+    let wrk = Workdir::new(name);
+    let mut data = String::from("id,name,note\n");
+    for i in 0..100_000 {
+        let note = if i % 3 == 0 {
+            "\"line one\nline two, with \"\"quotes\"\"\""
+        } else {
+            "plain"
+        };
+        data.push_str(&format!("{},name{},{}\n", i, i % 97, note));
+    }
+    fs::write(wrk.path("in.csv"), data).unwrap();
+    wrk
+}
+
+/// Run a command with one job and with four jobs, check that their outputs
+/// are the same, and return it.
+fn same_output(wrk: &Workdir, command: &str, args: &[&str]) -> String {
+    let run = |jobs: &str| -> String {
+        let mut cmd = wrk.command(command);
+        cmd.args(args).arg("--jobs").arg(jobs);
+        wrk.stdout(&mut cmd)
+    };
+    let expected = run("1");
+    let got = run("4");
+    assert_eq!(got, expected);
+    got
+}
+
+#[test]
+fn chunk_count() {
+    let wrk = setup("chunk_count");
+    let got = same_output(&wrk, "count", &["in.csv"]);
+    assert_eq!(got, "100000");
+}
+
+#[test]
+fn chunk_count_no_headers() {
+    let wrk = setup("chunk_count_no_headers");
+    let got = same_output(&wrk, "count", &["--no-headers", "in.csv"]);
+    assert_eq!(got, "100001");
+}
+
+#[test]
+fn chunk_search() {
+    let wrk = setup("chunk_search");
+    let args = &["-s", "name", "^name1$", "in.csv"];
+    let got = same_output(&wrk, "search", args);
+    assert!(got.starts_with("id,name,note\n1,name1,plain\n98,name1,"));
+}
+
+#[test]
+fn chunk_stats() {
+    let wrk = setup("chunk_stats");
+    same_output(&wrk, "stats", &["--everything", "in.csv"]);
+}
+
+#[test]
+fn chunk_frequency() {
+    let wrk = setup("chunk_frequency");
+    same_output(&wrk, "frequency", &["--limit", "0", "-s", "note", "in.csv"]);
+}
+
+#[test]
+fn chunk_split() {
+    let wrk = setup("chunk_split");
+    let mut cmd = wrk.command("split");
+    cmd.arg("--jobs").arg("4").arg("--size").arg("7000").arg("out")
+       .arg("in.csv");
+    wrk.run(&mut cmd);
+
+    let mut names: Vec<String> = fs::read_dir(wrk.path("out")).unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort_by_key(|n| n.trim_end_matches(".csv").parse::<u64>().unwrap());
+    assert_eq!(names.len(), 15);
+    assert_eq!(names[1], "7000.csv");
+
+    let mut count = 0;
+    for name in &names {
+        let data = fs::read_to_string(wrk.path("out").join(name)).unwrap();
+        // Each file starts with the record its name says.
+        let first_id = name.trim_end_matches(".csv");
+        assert!(data.starts_with(&format!("id,name,note\n{},", first_id)));
+
+        let mut cmd = wrk.command("count");
+        cmd.arg(wrk.path("out").join(name));
+        let n: usize = wrk.stdout(&mut cmd);
+        assert!(n == 7000 || name == "98000.csv");
+        count += n;
+    }
+    assert_eq!(count, 100_000);
+}
+
+#[test]
+fn chunk_split_write_error() {
+    // An error in one of the jobs fails the command.
+    let wrk = setup("chunk_split_write_error");
+    fs::create_dir_all(wrk.path("out").join("49000.csv")).unwrap();
+    let mut cmd = wrk.command("split");
+    cmd.arg("--jobs").arg("4").arg("--size").arg("7000").arg("out")
+       .arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
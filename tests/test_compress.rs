@@ -0,0 +1,276 @@
+use std::fs;
+use std::io::{Read, Write};
+
+use bzip2;
+use flate2;
+use xz2;
+use zstd;
+
+use workdir::Workdir;
+
+static DATA: &str = "h1,h2\na,b\nc,d\n";
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut wtr = flate2::write::GzEncoder::new(
+        vec![], flate2::Compression::default());
+    wtr.write_all(data).unwrap();
+    wtr.finish().unwrap()
+}
+
+fn expected() -> Vec<Vec<String>> {
+    vec![svec!["h1", "h2"], svec!["a", "b"], svec!["c", "d"]]
+}
+
+fn compressed_input(name: &str, file: &str, data: Vec<u8>) {
+    let wrk = Workdir::new(name);
+    fs::write(wrk.path(file), data).unwrap();
+    let mut cmd = wrk.command("cat");
+    cmd.arg("rows").arg(file);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert_eq!(got, expected());
+}
+
+#[test]
+fn compress_input_gzip() {
+    compressed_input("compress_input_gzip", "in.csv.gz",
+                     gzip(DATA.as_bytes()));
+}
+
+#[test]
+fn compress_input_zstd() {
+    compressed_input("compress_input_zstd", "in.csv.zst",
+                     zstd::encode_all(DATA.as_bytes(), 0).unwrap());
+}
+
+#[test]
+fn compress_input_bzip2() {
+    let mut wtr = bzip2::write::BzEncoder::new(
+        vec![], bzip2::Compression::default());
+    wtr.write_all(DATA.as_bytes()).unwrap();
+    compressed_input("compress_input_bzip2", "in.csv.bz2",
+                     wtr.finish().unwrap());
+}
+
+#[test]
+fn compress_input_xz() {
+    let mut wtr = xz2::write::XzEncoder::new(vec![], 6);
+    wtr.write_all(DATA.as_bytes()).unwrap();
+    compressed_input("compress_input_xz", "in.csv.xz",
+                     wtr.finish().unwrap());
+}
+
+#[test]
+fn compress_input_no_extension() {
+    // Compression is detected from the data, not the file name.
+    compressed_input("compress_input_no_extension", "in.csv",
+                     gzip(DATA.as_bytes()));
+}
+
+#[test]
+fn compress_input_bzip2_magic_only() {
+    // `BZh` isn't bzip2 data unless it is followed by a block size.
+    let wrk = Workdir::new("compress_input_bzip2_magic_only");
+    fs::write(wrk.path("in.csv"), "BZhang,h2\na,b\n").unwrap();
+    let mut cmd = wrk.command("cat");
+    cmd.arg("rows").arg("in.csv");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert_eq!(got, vec![svec!["BZhang", "h2"], svec!["a", "b"]]);
+}
+
+#[test]
+fn compress_input_stdin() {
+    let wrk = Workdir::new("compress_input_stdin");
+    fs::write(wrk.path("in.gz"), gzip(DATA.as_bytes())).unwrap();
+    let mut cmd = wrk.command("cat");
+    cmd.arg("rows").stdin(fs::File::open(wrk.path("in.gz")).unwrap());
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert_eq!(got, expected());
+}
+
+#[test]
+fn compress_input_tsv() {
+    let wrk = Workdir::new("compress_input_tsv");
+    fs::write(wrk.path("in.tsv.gz"), gzip(b"h1\th2\na\tb\nc\td\n")).unwrap();
+    let mut cmd = wrk.command("cat");
+    cmd.arg("rows").arg("in.tsv.gz");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert_eq!(got, expected());
+}
+
+#[test]
+fn compress_output_gzip() {
+    let wrk = Workdir::new("compress_output_gzip");
+    wrk.create("in.csv", expected());
+    let mut cmd = wrk.command("cat");
+    cmd.arg("rows").arg("in.csv").args(["-o", "out.csv.gz"]);
+    wrk.run(&mut cmd);
+
+    let mut got = String::new();
+    let file = fs::File::open(wrk.path("out.csv.gz")).unwrap();
+    flate2::read::GzDecoder::new(file).read_to_string(&mut got).unwrap();
+    assert_eq!(got, DATA);
+}
+
+#[test]
+fn compress_output_round_trip() {
+    let wrk = Workdir::new("compress_output_round_trip");
+    wrk.create("in.csv", expected());
+    let mut cmd = wrk.command("cat");
+    cmd.arg("rows").arg("in.csv").args(["-o", "out.csv.zst"]);
+    wrk.run(&mut cmd);
+
+    let mut cmd = wrk.command("cat");
+    cmd.arg("rows").arg("out.csv.zst");
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert_eq!(got, expected());
+}
+
+/// CSV data with records that span several lines, and the same data
+/// compressed in blocks of `block_len` bytes by `compress`.
+fn blocks<F>(block_len: usize, compress: F) -> (String, Vec<u8>)
+        where F: Fn(&[u8]) -> Vec<u8> {
+    let mut data = "h1,h2\n".to_owned();
+    for i in 0..500 {
+        data.push_str(&format!("{},\"line 1\nline 2 of {}\"\n", i, i));
+    }
+    let compressed = data.as_bytes()
+                         .chunks(block_len)
+                         .flat_map(&compress)
+                         .collect();
+    (data, compressed)
+}
+
+/// Check that `command` with `args` writes the same output for `in.csv`
+/// and its compressed version `file`, when both are indexed.
+fn same_as_uncompressed(
+    name: &str,
+    file: &str,
+    compressed: (String, Vec<u8>),
+    command: &str,
+    args: &[&str],
+) {
+    let wrk = Workdir::new(name);
+    fs::write(wrk.path("in.csv"), compressed.0).unwrap();
+    fs::write(wrk.path(file), compressed.1).unwrap();
+    for f in &["in.csv", file] {
+        let mut cmd = wrk.command("index");
+        cmd.arg(f);
+        wrk.run(&mut cmd);
+    }
+
+    let mut cmd = wrk.command(command);
+    cmd.args(args).arg("in.csv");
+    let expected: String = wrk.stdout(&mut cmd);
+    let mut cmd = wrk.command(command);
+    cmd.args(args).arg(file);
+    let got: String = wrk.stdout(&mut cmd);
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn compress_index_gzip_slice() {
+    same_as_uncompressed("compress_index_gzip_slice", "in.csv.gz",
+                         blocks(1000, gzip), "slice",
+                         &["-s", "321", "-l", "5"]);
+}
+
+#[test]
+fn compress_index_zstd_slice() {
+    let zstd = |block: &[u8]| zstd::encode_all(block, 0).unwrap();
+    same_as_uncompressed("compress_index_zstd_slice", "in.csv.zst",
+                         blocks(1000, zstd), "slice", &["-i", "499"]);
+}
+
+#[test]
+fn compress_index_single_block() {
+    same_as_uncompressed("compress_index_single_block", "in.csv.gz",
+                         blocks(1 << 20, gzip), "slice", &["-s", "250"]);
+}
+
+#[test]
+fn compress_index_count() {
+    same_as_uncompressed("compress_index_count", "in.csv.gz",
+                         blocks(1000, gzip), "count", &[]);
+}
+
+#[test]
+fn compress_index_stats() {
+    same_as_uncompressed("compress_index_stats", "in.csv.gz",
+                         blocks(1000, gzip), "stats", &["-j", "4"]);
+}
+
+#[test]
+fn compress_index_sample() {
+    let wrk = Workdir::new("compress_index_sample");
+    let (_, compressed) = blocks(1000, gzip);
+    fs::write(wrk.path("in.csv.gz"), compressed).unwrap();
+    let mut cmd = wrk.command("index");
+    cmd.arg("in.csv.gz");
+    wrk.run(&mut cmd);
+
+    let mut cmd = wrk.command("sample");
+    cmd.arg("10").arg("in.csv.gz");
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert_eq!(got.len(), 11);
+    for row in &got[1..] {
+        assert_eq!(row[1], format!("line 1\nline 2 of {}", row[0]));
+    }
+}
+
+#[test]
+fn compress_index_split() {
+    let wrk = Workdir::new("compress_index_split");
+    let (data, compressed) = blocks(1000, gzip);
+    fs::write(wrk.path("in.csv.gz"), compressed).unwrap();
+    let mut cmd = wrk.command("index");
+    cmd.arg("in.csv.gz");
+    wrk.run(&mut cmd);
+
+    let mut cmd = wrk.command("split");
+    cmd.args(["-j", "4", "-s", "100"])
+       .arg(wrk.path("out"))
+       .arg("in.csv.gz");
+    wrk.run(&mut cmd);
+
+    let mut got = String::new();
+    for i in 0..5 {
+        let part = fs::read_to_string(
+            wrk.path(&format!("out/{}.csv", i * 100))).unwrap();
+        got.push_str(if i == 0 { &part } else { &part[6..] });
+    }
+    assert_eq!(got, data);
+}
+
+#[test]
+fn compress_index_outdated() {
+    let wrk = Workdir::new("compress_index_outdated");
+    let (_, compressed) = blocks(1000, gzip);
+    fs::write(wrk.path("in.csv.gz"), compressed).unwrap();
+    let mut cmd = wrk.command("index");
+    cmd.arg("in.csv.gz");
+    wrk.run(&mut cmd);
+
+    fs::write(wrk.path("in.csv.gz"), gzip(DATA.as_bytes())).unwrap();
+    let mut cmd = wrk.command("slice");
+    cmd.args(["-i", "1"]).arg("in.csv.gz");
+    let o = cmd.output().unwrap();
+    assert_eq!(o.status.code(), Some(5));
+}
+
+#[test]
+fn compress_index_error() {
+    // Only gzip and zstd files can be indexed.
+    let wrk = Workdir::new("compress_index_error");
+    let mut wtr = bzip2::write::BzEncoder::new(
+        vec![], bzip2::Compression::default());
+    wtr.write_all(DATA.as_bytes()).unwrap();
+    fs::write(wrk.path("in.csv.bz2"), wtr.finish().unwrap()).unwrap();
+    let mut cmd = wrk.command("index");
+    cmd.arg("in.csv.bz2");
+    wrk.assert_err(&mut cmd);
+    assert!(!wrk.path("in.csv.bz2.idx").exists());
+}
@@ -0,0 +1,119 @@
+use std::process;
+
+use workdir::Workdir;
+
+fn setup(name: &str) -> (Workdir, process::Command) {
+    let rows = vec![
+        svec!["status", "region"],
+        svec!["ok", "east"],
+        svec!["ok", "west"],
+        svec!["failed", "east"],
+        svec!["ok", "east "],
+        svec!["", "west"],
+    ];
+
+    let wrk = Workdir::new(name);
+    wrk.create("in.csv", rows);
+
+    let mut cmd = wrk.command("crosstab");
+    cmd.arg("status").arg("region").arg("in.csv");
+
+    (wrk, cmd)
+}
+
+#[test]
+fn crosstab_matrix() {
+    let (wrk, mut cmd) = setup("crosstab_matrix");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["status", "east", "west"],
+        svec!["(NULL)", "0", "1"],
+        svec!["failed", "1", "0"],
+        svec!["ok", "2", "1"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn crosstab_totals() {
+    let (wrk, mut cmd) = setup("crosstab_totals");
+    cmd.arg("--totals");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["status", "east", "west", "(total)"],
+        svec!["(NULL)", "0", "1", "1"],
+        svec!["failed", "1", "0", "1"],
+        svec!["ok", "2", "1", "3"],
+        svec!["(total)", "3", "2", "5"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn crosstab_percent_column() {
+    let (wrk, mut cmd) = setup("crosstab_percent_column");
+    cmd.arg("--totals").args(&["--percent", "column"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let west: Vec<&str> = got.iter().map(|row| &*row[2]).collect();
+    assert_eq!(west, vec!["west", "50", "0", "50", "100"]);
+    assert_eq!(got[4], svec!["(total)", "100", "100", "100"]);
+}
+
+#[test]
+fn crosstab_long() {
+    let (wrk, mut cmd) = setup("crosstab_long");
+    cmd.arg("--long").args(&["--percent", "all"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["field", "value", "count", "col_field", "col_value", "percent"],
+        svec!["status", "(NULL)", "1", "region", "west", "20"],
+        svec!["status", "failed", "1", "region", "east", "20"],
+        svec!["status", "ok", "2", "region", "east", "40"],
+        svec!["status", "ok", "1", "region", "west", "20"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn crosstab_no_headers() {
+    let (wrk, _) = setup("crosstab_no_headers");
+    let mut cmd = wrk.command("crosstab");
+    cmd.arg("1").arg("2").arg("in.csv").arg("--no-headers").arg("--long");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert_eq!(got[0], svec!["field", "value", "count", "col_field",
+                             "col_value"]);
+    assert_eq!(got[1], svec!["1", "(NULL)", "1", "2", "west"]);
+    assert_eq!(got.len(), 6);
+}
+
+#[test]
+fn crosstab_multiple_columns() {
+    let (wrk, _) = setup("crosstab_multiple_columns");
+    let mut cmd = wrk.command("crosstab");
+    cmd.arg("status,region").arg("region").arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn crosstab_totals_collision() {
+    let wrk = Workdir::new("crosstab_totals_collision");
+    wrk.create("in.csv", vec![
+        svec!["status", "region"],
+        svec!["ok", "east"],
+        svec!["ok", "(total)"],
+    ]);
+    let mut cmd = wrk.command("crosstab");
+    cmd.arg("--totals").arg("status").arg("region").arg("in.csv");
+    wrk.assert_err(&mut cmd);
+
+    // Without --totals, the value is just another column.
+    let mut cmd = wrk.command("crosstab");
+    cmd.arg("status").arg("region").arg("in.csv");
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert_eq!(got[0], svec!["status", "(total)", "east"]);
+}
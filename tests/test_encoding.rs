@@ -0,0 +1,137 @@
+use std::fs;
+
+use workdir::Workdir;
+
+fn setup(name: &str, data: &[u8]) -> Workdir {
+    let wrk = Workdir::new(name);
+    fs::write(wrk.path("in.csv"), data).unwrap();
+    wrk
+}
+
+fn utf16(s: &str, big_endian: bool) -> Vec<u8> {
+    s.encode_utf16().flat_map(|u| {
+        if big_endian { u.to_be_bytes() } else { u.to_le_bytes() }
+    }).collect()
+}
+
+#[test]
+fn encoding_utf8_bom() {
+    let wrk = setup("encoding_utf8_bom", b"\xef\xbb\xbfname,n\nabc,1\n");
+    let mut cmd = wrk.command("select");
+    cmd.arg("name").arg("in.csv");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![svec!["name"], svec!["abc"]];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn encoding_utf8_bom_join() {
+    // join seeks in its input, so the BOM is not removed, but it must not
+    // break selecting the first column by name.
+    let wrk = setup("encoding_utf8_bom_join", b"\xef\xbb\xbfname,n\nabc,1\n");
+    let mut cmd = wrk.command("join");
+    cmd.args(["name", "in.csv", "name", "in.csv"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert_eq!(got[1], svec!["abc", "1", "abc", "1"]);
+}
+
+#[test]
+fn encoding_utf16_bom() {
+    let mut data = b"\xff\xfe".to_vec();
+    data.extend(utf16("name,n\nzo\u{eb},1\n", false));
+    let wrk = setup("encoding_utf16_bom", &data);
+    let mut cmd = wrk.command("select");
+    cmd.arg("name").arg("in.csv");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![svec!["name"], svec!["zo\u{eb}"]];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn encoding_latin1() {
+    let wrk = setup("encoding_latin1", b"name\ncaf\xe9\n");
+    let mut cmd = wrk.command("--encoding");
+    cmd.args(["latin1", "search", "\u{e9}", "in.csv"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![svec!["name"], svec!["caf\u{e9}"]];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn encoding_before_command() {
+    let wrk = setup("encoding_before_command", b"name\ncaf\xe9\n");
+    let mut cmd = wrk.command("--encoding=windows-1252");
+    cmd.arg("select").arg("name").arg("in.csv");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![svec!["name"], svec!["caf\u{e9}"]];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn encoding_auto_latin1() {
+    let wrk = setup("encoding_auto_latin1", b"name\ncaf\xe9\n");
+    let mut cmd = wrk.command("--encoding");
+    cmd.args(["auto", "select", "name", "in.csv"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![svec!["name"], svec!["caf\u{e9}"]];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn encoding_auto_utf8() {
+    let wrk = setup("encoding_auto_utf8", "name\ncaf\u{e9}\n".as_bytes());
+    let mut cmd = wrk.command("--encoding");
+    cmd.args(["auto", "select", "name", "in.csv"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![svec!["name"], svec!["caf\u{e9}"]];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn encoding_auto_utf16_stdin() {
+    let wrk = setup("encoding_auto_utf16_stdin",
+                    &utf16("name,n\nzo\u{eb},1\n", true));
+    let mut cmd = wrk.command("--encoding");
+    cmd.args(["auto", "select", "name"])
+       .stdin(fs::File::open(wrk.path("in.csv")).unwrap());
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![svec!["name"], svec!["zo\u{eb}"]];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn encoding_unknown() {
+    let wrk = setup("encoding_unknown", b"name\n");
+    let mut cmd = wrk.command("--encoding");
+    cmd.args(["nope", "count", "in.csv"]);
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn encoding_after_command() {
+    // Global options are only recognized before the command name.
+    let wrk = setup("encoding_after_command", b"name\ncaf\xe9\n");
+    let mut cmd = wrk.command("select");
+    cmd.args(["--encoding", "latin1", "name", "in.csv"]);
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn encoding_after_double_dash() {
+    // Arguments after `--` are never global options.
+    let wrk = setup("encoding_after_double_dash", b"name\n--encoding\nx\n");
+    let mut cmd = wrk.command("search");
+    cmd.args(["--", "--encoding", "in.csv"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![svec!["name"], svec!["--encoding"]];
+    assert_eq!(got, expected);
+}
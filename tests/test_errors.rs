@@ -0,0 +1,129 @@
+use std::fs;
+use std::process;
+
+use serde_json::{self, Value};
+
+use workdir::Workdir;
+
+fn setup(name: &str) -> Workdir {
+    let wrk = Workdir::new(name);
+    fs::write(wrk.path("in.csv"), "a,b\n1,2\n3\n").unwrap();
+    wrk
+}
+
+fn exit_code(cmd: &mut process::Command) -> i32 {
+    cmd.output().unwrap().status.code().unwrap()
+}
+
+fn json_error(cmd: &mut process::Command) -> Value {
+    cmd.env("XSV_ERROR_FORMAT", "json");
+    let o = cmd.output().unwrap();
+    serde_json::from_slice(&o.stderr).unwrap()
+}
+
+#[test]
+fn errors_exit_code_usage() {
+    let wrk = setup("errors_exit_code_usage");
+    let mut cmd = wrk.command("select");
+    cmd.arg("--bogus").arg("a").arg("in.csv");
+    assert_eq!(exit_code(&mut cmd), 2);
+
+    let mut cmd = wrk.command("--encoding");
+    assert_eq!(exit_code(&mut cmd), 2);
+
+    // Arguments that docopt accepts but the command rejects.
+    let mut cmd = wrk.command("split");
+    cmd.args(["--size", "0", "out", "in.csv"]);
+    assert_eq!(exit_code(&mut cmd), 2);
+}
+
+#[test]
+fn errors_exit_code_help() {
+    let wrk = setup("errors_exit_code_help");
+    let mut cmd = wrk.command("count");
+    cmd.arg("--help");
+    assert_eq!(exit_code(&mut cmd), 0);
+}
+
+#[test]
+fn errors_exit_code_io() {
+    let wrk = setup("errors_exit_code_io");
+    let mut cmd = wrk.command("count");
+    cmd.arg("does-not-exist.csv");
+    assert_eq!(exit_code(&mut cmd), 3);
+}
+
+#[test]
+fn errors_exit_code_csv() {
+    let wrk = setup("errors_exit_code_csv");
+    let mut cmd = wrk.command("select");
+    cmd.arg("a").arg("in.csv");
+    assert_eq!(exit_code(&mut cmd), 4);
+}
+
+#[test]
+fn errors_exit_code_other() {
+    let wrk = setup("errors_exit_code_other");
+    fs::write(wrk.path("in.json"), "[1]").unwrap();
+    let mut cmd = wrk.command("fromjson");
+    cmd.arg("in.json");
+    assert_eq!(exit_code(&mut cmd), 1);
+}
+
+#[test]
+fn errors_exit_code_regex() {
+    let wrk = setup("errors_exit_code_regex");
+    let mut cmd = wrk.command("search");
+    cmd.arg("(").arg("in.csv");
+    assert_eq!(exit_code(&mut cmd), 2);
+
+    // Regex errors are written as text, not in their debug format.
+    let stderr = String::from_utf8(cmd.output().unwrap().stderr).unwrap();
+    assert!(stderr.starts_with("regex parse error:"), "{}", stderr);
+}
+
+#[test]
+fn errors_exit_code_selection() {
+    let wrk = setup("errors_exit_code_selection");
+    let mut cmd = wrk.command("select");
+    cmd.arg("nope").arg("in.csv");
+    assert_eq!(exit_code(&mut cmd), 2);
+
+    let got = json_error(&mut cmd);
+    assert_eq!(got["kind"], "usage");
+}
+
+#[test]
+fn errors_json_csv() {
+    let wrk = setup("errors_json_csv");
+    let mut cmd = wrk.command("select");
+    cmd.arg("a").arg("in.csv");
+    let got = json_error(&mut cmd);
+    assert_eq!(got["kind"], "csv");
+    assert_eq!(got["exit_code"], 4);
+    assert_eq!(got["position"]["record"], 2);
+    assert_eq!(got["position"]["line"], 3);
+    assert_eq!(got["position"]["byte"], 8);
+    assert!(got["message"].as_str().unwrap().contains("found record"));
+}
+
+#[test]
+fn errors_json_usage() {
+    let wrk = setup("errors_json_usage");
+    let mut cmd = wrk.command("select");
+    cmd.arg("--bogus").arg("a").arg("in.csv");
+    let got = json_error(&mut cmd);
+    assert_eq!(got["kind"], "usage");
+    assert_eq!(got["exit_code"], 2);
+    assert!(got.get("position").is_none());
+}
+
+#[test]
+fn errors_json_io() {
+    let wrk = setup("errors_json_io");
+    let mut cmd = wrk.command("count");
+    cmd.arg("does-not-exist.csv");
+    let got = json_error(&mut cmd);
+    assert_eq!(got["kind"], "io");
+    assert_eq!(got["exit_code"], 3);
+}
@@ -260,3 +260,179 @@ fn assert_eq_ftables(got: &FTables, expected: &FTables) -> bool {
     }
     true
 }
+
+fn setup_skewed(name: &str, indexed: bool) -> (Workdir, process::Command) {
+    let mut rows = vec![svec!["h"]];
+    for _ in 0..300 { rows.push(svec!["a"]); }
+    for _ in 0..200 { rows.push(svec!["b"]); }
+    for i in 0..500 { rows.push(vec![i.to_string()]); }
+
+    let wrk = Workdir::new(name);
+    if indexed {
+        wrk.create_indexed("in.csv", rows);
+    } else {
+        wrk.create("in.csv", rows);
+    }
+
+    let mut cmd = wrk.command("frequency");
+    cmd.arg("in.csv");
+    (wrk, cmd)
+}
+
+fn assert_approx_bounds(got: Vec<Vec<String>>) {
+    assert_eq!(got[0], svec!["field", "value", "count", "error"]);
+    assert_eq!(got[1][1], "a");
+    assert_eq!(got[2][1], "b");
+    for (row, &truth) in got[1..].iter().zip(&[300, 200]) {
+        let count: u64 = row[2].parse().unwrap();
+        let error: u64 = row[3].parse().unwrap();
+        assert!(count <= truth && truth <= count + error);
+        assert!(error <= 1000 / 10);
+    }
+}
+
+#[test]
+fn frequency_approx() {
+    let (wrk, mut cmd) = setup_skewed("frequency_approx", false);
+    cmd.args(&["--approx", "10"]).args(&["--limit", "2"]);
+    assert_approx_bounds(wrk.read_stdout(&mut cmd));
+}
+
+#[test]
+fn frequency_approx_index() {
+    let (wrk, mut cmd) = setup_skewed("frequency_approx_index", true);
+    cmd.args(&["--approx", "10"]).args(&["--limit", "2"])
+       .args(&["--jobs", "4"]);
+    assert_approx_bounds(wrk.read_stdout(&mut cmd));
+}
+
+#[test]
+fn frequency_approx_exact_when_small() {
+    let (wrk, mut cmd) = setup("frequency_approx_exact_when_small");
+    cmd.args(&["--approx", "10"]).args(&["--select", "h2"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["field", "value", "count", "error"],
+        svec!["h2", "z", "3", "0"],
+        svec!["h2", "y", "2", "0"],
+        svec!["h2", "x", "1", "0"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn frequency_approx_asc() {
+    let (wrk, mut cmd) = setup("frequency_approx_asc");
+    cmd.args(&["--approx", "10"]).arg("--asc");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn frequency_percent() {
+    let (wrk, mut cmd) = setup("frequency_percent");
+    cmd.arg("--percent").args(&["--select", "h2"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["field", "value", "count", "percent", "cumulative_percent"],
+        svec!["h2", "z", "3", "50", "50"],
+        svec!["h2", "y", "2", "33.333333333333336", "83.33333333333333"],
+        svec!["h2", "x", "1", "16.666666666666668", "100"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn frequency_other() {
+    let (wrk, mut cmd) = setup("frequency_other");
+    cmd.arg("--other").args(&["--limit", "1"]).args(&["--select", "h2"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["field", "value", "count"],
+        svec!["h2", "z", "3"],
+        svec!["h2", "(other)", "3"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn frequency_other_no_tail() {
+    let (wrk, mut cmd) = setup("frequency_other_no_tail");
+    cmd.arg("--other").args(&["--limit", "3"]).args(&["--select", "h2"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert_eq!(got.len(), 4);
+    assert_eq!(got[3], svec!["h2", "x", "1"]);
+}
+
+#[test]
+fn frequency_approx_other() {
+    let (wrk, mut cmd) = setup_skewed("frequency_approx_other", false);
+    cmd.args(&["--approx", "10"]).args(&["--limit", "2"])
+       .arg("--other").arg("--percent");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let total: u64 = got[1..].iter().map(|r| r[2].parse::<u64>().unwrap())
+                             .sum();
+    assert_eq!(total, 1000);
+    assert_eq!(got[3][1], "(other)");
+    assert_eq!(got[3][5], "100");
+}
+
+#[test]
+fn frequency_weight() {
+    let wrk = Workdir::new("frequency_weight");
+    wrk.create("in.csv", vec![
+        svec!["k", "w"],
+        svec!["a", "1.5"],
+        svec!["b", "2"],
+        svec!["a", "x"],
+        svec!["c", "0.5"],
+        svec!["a", "1"],
+    ]);
+    let mut cmd = wrk.command("frequency");
+    cmd.arg("in.csv").args(&["--select", "k"]).args(&["--weight", "w"])
+       .args(&["--limit", "2"]).arg("--other");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["field", "value", "count"],
+        svec!["k", "a", "2.5"],
+        svec!["k", "b", "2"],
+        svec!["k", "(other)", "0.5"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn frequency_weight_index() {
+    let wrk = Workdir::new("frequency_weight_index");
+    wrk.create_indexed("in.csv", vec![
+        svec!["k", "w"],
+        svec!["a", "1"],
+        svec!["b", "5"],
+        svec!["a", "2"],
+        svec!["b", "1"],
+        svec!["a", "4"],
+    ]);
+    let mut cmd = wrk.command("frequency");
+    cmd.arg("in.csv").args(&["--select", "k"]).args(&["--weight", "w"])
+       .args(&["--jobs", "2"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["field", "value", "count"],
+        svec!["k", "a", "7"],
+        svec!["k", "b", "6"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn frequency_weight_approx() {
+    let (wrk, mut cmd) = setup("frequency_weight_approx");
+    cmd.args(&["--approx", "10"]).args(&["--weight", "h2"]);
+    wrk.assert_err(&mut cmd);
+}
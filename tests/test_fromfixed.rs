@@ -0,0 +1,90 @@
+use std::fs;
+
+use workdir::Workdir;
+
+fn setup(name: &str) -> Workdir {
+    let wrk = Workdir::new(name);
+    fs::write(wrk.path("in.txt"), "\
+name  n  city
+alice 12 Zürich
+bob   7\r
+
+carol 300Oslo   extra
+").unwrap();
+    wrk
+}
+
+#[test]
+fn fromfixed() {
+    let wrk = setup("fromfixed");
+    let mut cmd = wrk.command("fromfixed");
+    cmd.arg("--widths").arg("6,3,6").arg("in.txt");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["name", "n", "city"],
+        svec!["alice", "12", "Zürich"],
+        svec!["bob", "7", ""],
+        svec!["carol", "300", "Oslo"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn fromfixed_trim() {
+    let wrk = setup("fromfixed_trim");
+    let mut cmd = wrk.command("fromfixed");
+    cmd.arg("--widths").arg("6,3").arg("--trim").arg("none").arg("in.txt");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["name  ", "n  "],
+        svec!["alice ", "12 "],
+        svec!["bob   ", "7"],
+        svec!["carol ", "300"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn fromfixed_spec() {
+    let wrk = setup("fromfixed_spec");
+    wrk.create("spec.csv", vec![
+        svec!["name", "width"],
+        svec!["who", "6"],
+        svec!["count", "3"],
+    ]);
+    let mut cmd = wrk.command("fromfixed");
+    cmd.arg("--spec").arg("spec.csv").arg("in.txt");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["who", "count"],
+        svec!["alice", "12"],
+        svec!["bob", "7"],
+        svec!["carol", "300"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn fromfixed_no_headers() {
+    let wrk = setup("fromfixed_no_headers");
+    let mut cmd = wrk.command("fromfixed");
+    cmd.arg("--widths").arg("6").arg("--no-headers").arg("in.txt");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["name"], svec!["alice"], svec!["bob"], svec!["carol"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn fromfixed_bad_spec() {
+    let wrk = setup("fromfixed_bad_spec");
+    wrk.create("spec.csv", vec![svec!["name", "size"], svec!["who", "6"]]);
+    let mut cmd = wrk.command("fromfixed");
+    cmd.arg("--spec").arg("spec.csv").arg("in.txt");
+    wrk.assert_err(&mut cmd);
+}
@@ -0,0 +1,146 @@
+use std::fs;
+
+use workdir::Workdir;
+
+fn setup(name: &str, json: &str) -> Workdir {
+    let wrk = Workdir::new(name);
+    fs::write(wrk.path("in.json"), json).unwrap();
+    wrk
+}
+
+#[test]
+fn fromjson_lines() {
+    let wrk = setup("fromjson_lines", "\
+{\"id\": 1, \"name\": \"a\"}
+{\"name\": \"b\", \"id\": 2, \"extra\": true}
+{\"id\": 3}
+");
+    let mut cmd = wrk.command("fromjson");
+    cmd.arg("in.json");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["id", "name", "extra"],
+        svec!["1", "a", ""],
+        svec!["2", "b", "true"],
+        svec!["3", "", ""],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn fromjson_array() {
+    let wrk = setup("fromjson_array", "\
+[
+  {\"id\": 1, \"score\": 1.5},
+  {\"id\": 2, \"score\": null}
+]
+");
+    let mut cmd = wrk.command("fromjson");
+    cmd.arg("in.json");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["id", "score"],
+        svec!["1", "1.5"],
+        svec!["2", ""],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn fromjson_nested() {
+    let wrk = setup("fromjson_nested", "\
+{\"id\": 1, \"user\": {\"name\": \"a\", \"address\": {\"city\": \"x\"}}}
+{\"id\": 2, \"user\": {\"name\": \"b\"}, \"tags\": [\"p\", \"q\"]}
+");
+    let mut cmd = wrk.command("fromjson");
+    cmd.arg("in.json");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["id", "user.name", "user.address.city", "tags"],
+        svec!["1", "a", "x", ""],
+        svec!["2", "b", "", "[\"p\",\"q\"]"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn fromjson_join_arrays() {
+    let wrk = setup("fromjson_join_arrays", "\
+{\"tags\": [\"p\", 1, null, [2]]}
+{\"tags\": []}
+");
+    let mut cmd = wrk.command("fromjson");
+    cmd.arg("--join-arrays").arg("|").arg("in.json");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["tags"],
+        svec!["p|1||2"],
+        svec![""],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn fromjson_stdin() {
+    let wrk = setup("fromjson_stdin", "\
+{\"a\": 1}
+{\"b\": 2}
+");
+    let mut cmd = wrk.command("fromjson");
+    cmd.stdin(fs::File::open(wrk.path("in.json")).unwrap());
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["a", "b"],
+        svec!["1", ""],
+        svec!["", "2"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn fromjson_not_object() {
+    let wrk = setup("fromjson_not_object", "{\"a\": 1}\n[2]\n");
+    let mut cmd = wrk.command("fromjson");
+    cmd.arg("in.json");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn fromjson_invalid() {
+    let wrk = setup("fromjson_invalid", "{\"a\": 1}\n{\"a\":\n");
+    let mut cmd = wrk.command("fromjson");
+    cmd.arg("in.json");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn fromjson_empty_object() {
+    let wrk = setup("fromjson_empty_object", "\
+{\"id\": 1, \"meta\": {}}
+{\"id\": 2, \"meta\": {\"a\": 1}}
+");
+    let mut cmd = wrk.command("fromjson");
+    cmd.arg("in.json");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["id", "meta", "meta.a"],
+        svec!["1", "", ""],
+        svec!["2", "", "1"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn fromjson_flattened_collision() {
+    let wrk = setup("fromjson_flattened_collision",
+                    "{\"a.b\": 1, \"a\": {\"b\": 2}}\n");
+    let mut cmd = wrk.command("fromjson");
+    cmd.arg("in.json");
+    wrk.assert_err(&mut cmd);
+}
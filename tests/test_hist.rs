@@ -0,0 +1,133 @@
+use std::process;
+
+use workdir::Workdir;
+
+fn setup(name: &str, indexed: bool) -> (Workdir, process::Command) {
+    let rows = vec![
+        svec!["n", "name"],
+        svec!["1", "a"],
+        svec!["2", "b"],
+        svec!["2.5", "c"],
+        svec!["", "d"],
+        svec!["x", "e"],
+        svec!["10", "f"],
+    ];
+
+    let wrk = Workdir::new(name);
+    if indexed {
+        wrk.create_indexed("in.csv", rows);
+    } else {
+        wrk.create("in.csv", rows);
+    }
+
+    let mut cmd = wrk.command("hist");
+    cmd.arg("in.csv");
+
+    (wrk, cmd)
+}
+
+#[test]
+fn hist_fixed_width() {
+    let (wrk, mut cmd) = setup("hist_fixed_width", false);
+    cmd.args(&["--bins", "3"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["field", "bin_start", "bin_end", "count"],
+        svec!["n", "1", "4", "3"],
+        svec!["n", "4", "7", "0"],
+        svec!["n", "7", "10", "1"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn hist_fixed_width_index() {
+    let (wrk, mut cmd) = setup("hist_fixed_width_index", true);
+    cmd.args(&["--bins", "3"]).args(&["--jobs", "4"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["field", "bin_start", "bin_end", "count"],
+        svec!["n", "1", "4", "3"],
+        svec!["n", "4", "7", "0"],
+        svec!["n", "7", "10", "1"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn hist_stdin() {
+    let (wrk, _) = setup("hist_stdin", false);
+    let mut cmd = wrk.command("hist");
+    cmd.args(&["--bins", "3"])
+       .stdin(::std::fs::File::open(wrk.path("in.csv")).unwrap());
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["field", "bin_start", "bin_end", "count"],
+        svec!["n", "1", "4", "3"],
+        svec!["n", "4", "7", "0"],
+        svec!["n", "7", "10", "1"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn hist_quantile_bins() {
+    let (wrk, mut cmd) = setup("hist_quantile_bins", false);
+    cmd.args(&["--bins", "2"]).arg("--quantile-bins");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let counts: Vec<&str> = got[1..].iter().map(|r| &*r[3]).collect();
+    assert_eq!(counts, vec!["2", "2"]);
+}
+
+#[test]
+fn hist_single_value() {
+    let wrk = Workdir::new("hist_single_value");
+    wrk.create("in.csv", vec![svec!["n"], svec!["5"], svec!["5"]]);
+    let mut cmd = wrk.command("hist");
+    cmd.arg("in.csv");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["field", "bin_start", "bin_end", "count"],
+        svec!["n", "5", "5", "2"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn hist_no_headers() {
+    let (wrk, mut cmd) = setup("hist_no_headers", false);
+    cmd.args(&["--bins", "1"]).arg("--no-headers");
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["field", "bin_start", "bin_end", "count"],
+        svec!["1", "1", "10", "4"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn hist_bars() {
+    let (wrk, mut cmd) = setup("hist_bars", false);
+    cmd.args(&["--bins", "3"]).arg("--bars").args(&["--width", "6"]);
+
+    let got: String = wrk.stdout(&mut cmd);
+    let expected = "\
+n
+  [1, 4)   3  ######
+  [4, 7)   0  
+  [7, 10]  1  ##";
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn hist_zero_bins() {
+    let (wrk, mut cmd) = setup("hist_zero_bins", false);
+    cmd.args(&["--bins", "0"]);
+    wrk.assert_err(&mut cmd);
+}
@@ -1,23 +1,218 @@
 use std::fs;
 
 use filetime::{FileTime, set_file_times};
+use serde_json::{self, Value};
 
 use workdir::Workdir;
 
 #[test]
 fn index_outdated() {
     let wrk = Workdir::new("index_outdated");
-    wrk.create_indexed("in.csv", vec![svec![""]]);
+    wrk.create_indexed("in.csv", vec![svec!["a"], svec!["1"]]);
+    wrk.create("in.csv", vec![svec!["a"], svec!["1"], svec!["2"]]);
 
-    let md = fs::metadata(&wrk.path("in.csv.idx")).unwrap();
+    let mut cmd = wrk.command("count");
+    cmd.arg("--no-headers").arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn index_outdated_same_size() {
+    let wrk = Workdir::new("index_outdated_same_size");
+    wrk.create_indexed("in.csv", vec![svec!["a"], svec!["1"]]);
+    wrk.create("in.csv", vec![svec!["a"], svec!["2"]]);
+
+    let mut cmd = wrk.command("count");
+    cmd.arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn index_outdated_json() {
+    let wrk = Workdir::new("index_outdated_json");
+    wrk.create_indexed("in.csv", vec![svec!["a"], svec!["1"]]);
+    wrk.create("in.csv", vec![svec!["a"], svec!["1"], svec!["2"]]);
+
+    let mut cmd = wrk.command("count");
+    cmd.arg("--no-headers").arg("in.csv")
+       .env("XSV_ERROR_FORMAT", "json");
+    let o = cmd.output().unwrap();
+    assert_eq!(o.status.code(), Some(5));
+    let got: Value = serde_json::from_slice(&o.stderr).unwrap();
+    assert_eq!(got["kind"], "stale_index");
+}
+
+#[test]
+fn index_touched() {
+    let wrk = Workdir::new("index_touched");
+    wrk.create_indexed("in.csv", vec![svec!["a"], svec!["1"], svec!["2"]]);
+
+    // A new modification time, e.g., after copying the file, doesn't make
+    // the index out of date.
+    let md = fs::metadata(wrk.path("in.csv.idx")).unwrap();
     set_file_times(
-        &wrk.path("in.csv"),
+        wrk.path("in.csv"),
         future_time(FileTime::from_last_modification_time(&md)),
         future_time(FileTime::from_last_access_time(&md)),
     ).unwrap();
 
+    let mut cmd = wrk.command("slice");
+    cmd.arg("-i").arg("1").arg("in.csv");
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert_eq!(got, vec![svec!["a"], svec!["2"]]);
+}
+
+#[test]
+fn index_check() {
+    let wrk = Workdir::new("index_check");
+    wrk.create_indexed("in.csv", vec![svec!["a"], svec!["1"]]);
+
+    let mut cmd = wrk.command("index");
+    cmd.arg("--check").arg("in.csv");
+    wrk.run(&mut cmd);
+
+    wrk.create("in.csv", vec![svec!["a"], svec!["2"]]);
+    let o = cmd.output().unwrap();
+    assert_eq!(o.status.code(), Some(5));
+}
+
+#[test]
+fn index_check_output() {
+    let wrk = Workdir::new("index_check_output");
+    wrk.create("in.csv", vec![svec!["a"], svec!["1"]]);
+    let mut cmd = wrk.command("index");
+    cmd.arg("-o").arg("other.idx").arg("in.csv");
+    wrk.run(&mut cmd);
+
+    let mut cmd = wrk.command("index");
+    cmd.arg("--check").arg("-o").arg("other.idx").arg("in.csv");
+    wrk.run(&mut cmd);
+}
+
+#[test]
+fn index_check_old_format() {
+    let wrk = Workdir::new("index_check_old_format");
+    wrk.create("in.csv", vec![svec!["a"], svec!["1"]]);
+    // An index in the format used before index files had a header.
+    let old: &[u8] = &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2,
+                       0, 0, 0, 0, 0, 0, 0, 2];
+    fs::write(wrk.path("in.csv.idx"), old).unwrap();
+
+    let mut cmd = wrk.command("index");
+    cmd.arg("--check").arg("in.csv");
+    let o = cmd.output().unwrap();
+    assert_eq!(o.status.code(), Some(5));
+
     let mut cmd = wrk.command("count");
-    cmd.arg("--no-headers").arg("in.csv");
+    cmd.arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn index_delimiter_mismatch() {
+    let wrk = Workdir::new("index_delimiter_mismatch");
+    fs::write(wrk.path("in.csv"), "a;b\n1;2\n").unwrap();
+    let mut cmd = wrk.command("index");
+    cmd.arg("-d").arg(";").arg("in.csv");
+    wrk.run(&mut cmd);
+
+    let mut cmd = wrk.command("count");
+    cmd.arg("-d").arg(";").arg("in.csv");
+    let got: String = wrk.stdout(&mut cmd);
+    assert_eq!(got, "1");
+
+    let mut cmd = wrk.command("count");
+    cmd.arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn index_failed_keeps_old_index() {
+    // An index is only replaced once the new one is complete.
+    let wrk = Workdir::new("index_failed_keeps_old_index");
+    wrk.create_indexed("in.csv", vec![svec!["a", "b"], svec!["1", "2"]]);
+    let old = fs::read(wrk.path("in.csv.idx")).unwrap();
+    fs::write(wrk.path("in.csv"), "a,b\n1,2\n3\n").unwrap();
+
+    let mut cmd = wrk.command("index");
+    cmd.arg("in.csv");
+    wrk.assert_err(&mut cmd);
+    assert_eq!(fs::read(wrk.path("in.csv.idx")).unwrap(), old);
+    assert!(!wrk.path("in.csv.idx.tmp").exists());
+}
+
+fn sparse_data() -> Vec<Vec<String>> {
+    let mut rows = vec![svec!["n", "text"]];
+    for i in 0..100 {
+        rows.push(vec![i.to_string(), format!("line 1\nline 2 of {}", i)]);
+    }
+    rows
+}
+
+/// Check that slicing `in.csv` with `args` gives the same records with a
+/// sparse index as with a dense one.
+fn sparse_same_as_dense(wrk: &Workdir, every: &str, args: &[&str]) {
+    let mut cmd = wrk.command("index");
+    cmd.arg("in.csv");
+    wrk.run(&mut cmd);
+    let mut cmd = wrk.command("slice");
+    cmd.args(args).arg("in.csv");
+    let expected: String = wrk.stdout(&mut cmd);
+
+    let mut cmd = wrk.command("index");
+    cmd.args(["--sparse", every]).arg("in.csv");
+    wrk.run(&mut cmd);
+    let mut cmd = wrk.command("slice");
+    cmd.args(args).arg("in.csv");
+    let got: String = wrk.stdout(&mut cmd);
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn index_sparse() {
+    let wrk = Workdir::new("index_sparse");
+    wrk.create("in.csv", sparse_data());
+    for i in &["0", "6", "7", "8", "50", "99"] {
+        sparse_same_as_dense(&wrk, "7", &["-i", i]);
+    }
+    sparse_same_as_dense(&wrk, "7", &["-s", "40", "-l", "20"]);
+}
+
+#[test]
+fn index_sparse_no_headers() {
+    let wrk = Workdir::new("index_sparse_no_headers");
+    wrk.create("in.csv", sparse_data());
+    for i in &["0", "1", "15", "16", "100"] {
+        sparse_same_as_dense(&wrk, "16", &["--no-headers", "-i", i]);
+    }
+}
+
+#[test]
+fn index_sparse_count() {
+    let wrk = Workdir::new("index_sparse_count");
+    wrk.create("in.csv", sparse_data());
+    let mut cmd = wrk.command("index");
+    cmd.args(["--sparse", "10"]).arg("in.csv");
+    wrk.run(&mut cmd);
+    let dense_len = 40 + 8 * 102;
+    assert!(fs::metadata(wrk.path("in.csv.idx")).unwrap().len() < dense_len);
+
+    let mut cmd = wrk.command("count");
+    cmd.arg("in.csv");
+    let got: String = wrk.stdout(&mut cmd);
+    assert_eq!(got, "100");
+
+    let mut cmd = wrk.command("slice");
+    cmd.args(["-i", "100"]).arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn index_sparse_zero() {
+    let wrk = Workdir::new("index_sparse_zero");
+    wrk.create("in.csv", sparse_data());
+    let mut cmd = wrk.command("index");
+    cmd.args(["--sparse", "0"]).arg("in.csv");
     wrk.assert_err(&mut cmd);
 }
 
@@ -0,0 +1,184 @@
+use workdir::Workdir;
+
+fn data() -> Vec<Vec<String>> {
+    vec![
+        svec!["name", "n", "note"],
+        svec!["a", "1", "say \"hi\""],
+        svec!["b", "-0.5", "+2"],
+        svec!["c", "007", ""],
+    ]
+}
+
+fn json_stdout(
+    wrk: &Workdir,
+    cmd: &mut ::std::process::Command,
+    format: &str,
+) -> String {
+    cmd.env("XSV_OUTPUT_FORMAT", format);
+    let o = wrk.output(cmd);
+    String::from_utf8_lossy(&o.stdout).into_owned()
+}
+
+#[test]
+fn json_array() {
+    let wrk = Workdir::new("json_array");
+    wrk.create("in.csv", data());
+    let mut cmd = wrk.command("select");
+    cmd.arg("name,note").arg("in.csv");
+
+    let got = json_stdout(&wrk, &mut cmd, "json");
+    let expected = "\
+[
+{\"name\":\"a\",\"note\":\"say \\\"hi\\\"\"},
+{\"name\":\"b\",\"note\":\"+2\"},
+{\"name\":\"c\",\"note\":\"\"}
+]
+";
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn json_lines() {
+    let wrk = Workdir::new("json_lines");
+    wrk.create("in.csv", data());
+    let mut cmd = wrk.command("slice");
+    cmd.arg("-s").arg("1").arg("in.csv");
+
+    let got = json_stdout(&wrk, &mut cmd, "ndjson");
+    let expected = "\
+{\"name\":\"b\",\"n\":\"-0.5\",\"note\":\"+2\"}
+{\"name\":\"c\",\"n\":\"007\",\"note\":\"\"}
+";
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn json_numbers() {
+    let wrk = Workdir::new("json_numbers");
+    wrk.create("in.csv", data());
+    let mut cmd = wrk.command("select");
+    cmd.arg("n,note").arg("in.csv");
+
+    // Values like `007` and `+2` are numbers to xsv, but not valid JSON
+    // numbers, so they stay strings.
+    let got = json_stdout(&wrk, &mut cmd, "ndjson-typed");
+    let expected = "\
+{\"n\":1,\"note\":\"say \\\"hi\\\"\"}
+{\"n\":-0.5,\"note\":\"+2\"}
+{\"n\":\"007\",\"note\":\"\"}
+";
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn json_empty() {
+    let wrk = Workdir::new("json_empty");
+    wrk.create("in.csv", data());
+    let mut cmd = wrk.command("search");
+    cmd.arg("nomatch").arg("in.csv");
+
+    assert_eq!(json_stdout(&wrk, &mut cmd, "json"), "[]\n");
+}
+
+#[test]
+fn json_frequency() {
+    let wrk = Workdir::new("json_frequency");
+    wrk.create("in.csv", vec![
+        svec!["h"], svec!["x"], svec!["x"], svec!["y"],
+    ]);
+    let mut cmd = wrk.command("frequency");
+    cmd.arg("in.csv");
+
+    let got = json_stdout(&wrk, &mut cmd, "ndjson-typed");
+    let expected = "\
+{\"field\":\"h\",\"value\":\"x\",\"count\":2}
+{\"field\":\"h\",\"value\":\"y\",\"count\":1}
+";
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn json_output_file() {
+    let wrk = Workdir::new("json_output_file");
+    wrk.create("in.csv", data());
+    let mut cmd = wrk.command("select");
+    cmd.arg("name").arg("-o").arg("out.json").arg("in.csv")
+       .env("XSV_OUTPUT_FORMAT", "json");
+    wrk.run(&mut cmd);
+
+    let got: String = wrk.from_str(&wrk.path("out.json"));
+    let expected = "\
+[
+{\"name\":\"a\"},
+{\"name\":\"b\"},
+{\"name\":\"c\"}
+]
+";
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn json_unknown_format() {
+    let wrk = Workdir::new("json_unknown_format");
+    wrk.create("in.csv", data());
+    let mut cmd = wrk.command("select");
+    cmd.arg("name").arg("in.csv").env("XSV_OUTPUT_FORMAT", "xml");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn json_no_headers() {
+    let wrk = Workdir::new("json_no_headers");
+    wrk.create("in.csv", vec![svec!["a", "b"], svec!["1", "2"]]);
+    let mut cmd = wrk.command("select");
+    cmd.arg("-n").arg("1,2").arg("in.csv");
+
+    let got = json_stdout(&wrk, &mut cmd, "ndjson");
+    let expected = "\
+{\"1\":\"a\",\"2\":\"b\"}
+{\"1\":\"1\",\"2\":\"2\"}
+";
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn json_output_format_option() {
+    let wrk = Workdir::new("json_output_format_option");
+    wrk.create("in.csv", data());
+    let mut cmd = wrk.command("--output-format");
+    cmd.arg("ndjson").arg("select").arg("name").arg("in.csv")
+       .env("XSV_OUTPUT_FORMAT", "csv");
+
+    let got = wrk.stdout::<String>(&mut cmd);
+    let expected = "\
+{\"name\":\"a\"}
+{\"name\":\"b\"}
+{\"name\":\"c\"}";
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn json_output_format_unknown() {
+    let wrk = Workdir::new("json_output_format_unknown");
+    wrk.create("in.csv", data());
+    let mut cmd = wrk.command("--output-format");
+    cmd.arg("xml").arg("select").arg("name").arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn json_typed_output_format_option() {
+    let wrk = Workdir::new("json_typed_output_format_option");
+    wrk.create("in.csv", data());
+    let mut cmd = wrk.command("--output-format");
+    cmd.arg("json-typed").arg("select").arg("name,n").arg("in.csv");
+
+    let got: String = wrk.stdout(&mut cmd);
+    let expected = "\
+[
+{\"name\":\"a\",\"n\":1},
+{\"name\":\"b\",\"n\":-0.5},
+{\"name\":\"c\",\"n\":\"007\"}
+]";
+    assert_eq!(got, expected);
+}
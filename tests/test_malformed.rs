@@ -0,0 +1,147 @@
+use std::fs;
+
+use workdir::Workdir;
+
+static DATA: &str = "a,b\n1,2\n3,4,5\n6,\"x\ny\"\n7\n8,9\n";
+
+fn setup(name: &str) -> Workdir {
+    let wrk = Workdir::new(name);
+    fs::write(wrk.path("in.csv"), DATA).unwrap();
+    wrk
+}
+
+#[test]
+fn malformed_fail() {
+    let wrk = setup("malformed_fail");
+    let mut cmd = wrk.command("count");
+    cmd.arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn malformed_skip() {
+    let wrk = setup("malformed_skip");
+    let mut cmd = wrk.command("--on-error");
+    cmd.args(["skip", "select", "a", "in.csv"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![svec!["a"], svec!["1"], svec!["6"], svec!["8"]];
+    assert_eq!(got, expected);
+
+    let stderr = String::from_utf8(wrk.output(&mut cmd).stderr).unwrap();
+    assert_eq!(stderr, "Skipped 2 malformed record(s).\n");
+}
+
+#[test]
+fn malformed_quarantine() {
+    let wrk = setup("malformed_quarantine");
+    let mut cmd = wrk.command("--on-error=quarantine=bad.csv");
+    cmd.args(["count", "in.csv"]);
+
+    let got: usize = wrk.stdout(&mut cmd);
+    assert_eq!(got, 3);
+
+    let mut cmd = wrk.command("cat");
+    cmd.arg("rows").arg("bad.csv");
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["file", "line", "error", "record"],
+        svec!["in.csv", "3",
+              "found record with 3 fields, but the first record has 2 fields",
+              "3,4,5"],
+        svec!["in.csv", "6",
+              "found record with 1 fields, but the first record has 2 fields",
+              "7"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn malformed_quarantine_stdin() {
+    let wrk = setup("malformed_quarantine_stdin");
+    let mut cmd = wrk.command("--on-error");
+    cmd.args(["quarantine=bad.csv", "cat", "rows"])
+       .stdin(fs::File::open(wrk.path("in.csv")).unwrap());
+    wrk.run(&mut cmd);
+
+    let mut cmd = wrk.command("select");
+    cmd.arg("1,2").arg("bad.csv");
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![
+        svec!["file", "line"],
+        svec!["<stdin>", "3"],
+        svec!["<stdin>", "6"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn malformed_quarantine_empty() {
+    // The quarantine file is written even if there are no malformed
+    // records.
+    let wrk = Workdir::new("malformed_quarantine_empty");
+    wrk.create("in.csv", vec![svec!["a", "b"], svec!["1", "2"]]);
+    let mut cmd = wrk.command("--on-error");
+    cmd.args(["quarantine=bad.csv", "count", "in.csv"]);
+    wrk.run(&mut cmd);
+
+    let got = fs::read_to_string(wrk.path("bad.csv")).unwrap();
+    assert_eq!(got, "file,line,error,record\n");
+}
+
+#[test]
+fn malformed_read_twice() {
+    // Records are reported once, even if the input is read twice.
+    let wrk = setup("malformed_read_twice");
+    let mut cmd = wrk.command("--on-error");
+    cmd.args(["skip", "tofixed", "in.csv"]);
+
+    let stderr = String::from_utf8(wrk.output(&mut cmd).stderr).unwrap();
+    assert_eq!(stderr, "Skipped 2 malformed record(s).\n");
+}
+
+#[test]
+fn malformed_unknown_policy() {
+    let wrk = setup("malformed_unknown_policy");
+    let mut cmd = wrk.command("--on-error");
+    cmd.args(["ignore", "count", "in.csv"]);
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn malformed_skip_utf8() {
+    let wrk = Workdir::new("malformed_skip_utf8");
+    fs::write(wrk.path("in.csv"), b"a,b\n1,2\n\xff,3\n4,5\n").unwrap();
+    let mut cmd = wrk.command("--on-error");
+    cmd.args(["skip", "select", "a", "in.csv"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![svec!["a"], svec!["1"], svec!["4"]];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn malformed_index_refused() {
+    // An index records byte offsets, so malformed records can't be skipped.
+    let wrk = setup("malformed_index_refused");
+    let _ = fs::remove_file(wrk.path("in.csv.idx"));
+    let mut cmd = wrk.command("--on-error");
+    cmd.args(["skip", "index", "in.csv"]);
+    wrk.assert_err(&mut cmd);
+    assert!(!wrk.path("in.csv.idx").exists());
+}
+
+#[test]
+fn malformed_skip_ignores_index() {
+    // The index is out of date, but it isn't used when skipping records.
+    let wrk = Workdir::new("malformed_skip_ignores_index");
+    wrk.create_indexed("in.csv", vec![
+        svec!["a", "b"], svec!["1", "2"], svec!["3", "4"],
+    ]);
+    fs::write(wrk.path("in.csv"), "a,b\n1,2\n3\n").unwrap();
+    let mut cmd = wrk.command("--on-error");
+    cmd.args(["skip", "count", "in.csv"]);
+
+    let got: usize = wrk.stdout(&mut cmd);
+    assert_eq!(got, 1);
+}
@@ -1,4 +1,8 @@
 use std::borrow::ToOwned;
+use std::fs;
+use std::io::Read;
+
+use flate2;
 
 use workdir::Workdir;
 
@@ -222,3 +226,270 @@ CA,San Francisco
 CO,Denver
 ");
 }
+
+#[test]
+fn partition_filename_seq() {
+    let wrk = Workdir::new("partition_filename_seq");
+    wrk.create("in.csv", data(true));
+
+    let mut cmd = wrk.command("partition");
+    cmd.args(["--filename", "{seq:02}.csv"])
+       .arg("state").arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    part_eq!(wrk, "00.csv", "\
+state,city
+NY,Manhatten
+NY,Buffalo
+");
+    part_eq!(wrk, "02.csv", "\
+state,city
+TX,Dallas
+TX,Fort Worth
+");
+}
+
+fn dated_data() -> Vec<Vec<String>> {
+    vec![
+        svec!["year", "month", "amount"],
+        svec!["2024", "05", "1"],
+        svec!["2024", "06", "2"],
+        svec!["2023", "05", "3"],
+        svec!["2024", "05", "4"],
+    ]
+}
+
+#[test]
+fn partition_multiple_columns() {
+    let wrk = Workdir::new("partition_multiple_columns");
+    wrk.create("in.csv", dated_data());
+
+    let mut cmd = wrk.command("partition");
+    cmd.arg("year,month").arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    part_eq!(wrk, "2024/05.csv", "\
+year,month,amount
+2024,05,1
+2024,05,4
+");
+    part_eq!(wrk, "2024/06.csv", "\
+year,month,amount
+2024,06,2
+");
+    part_eq!(wrk, "2023/05.csv", "\
+year,month,amount
+2023,05,3
+");
+}
+
+#[test]
+fn partition_hive() {
+    let wrk = Workdir::new("partition_hive");
+    wrk.create("in.csv", dated_data());
+
+    let mut cmd = wrk.command("partition");
+    cmd.arg("--hive").arg("year,month").arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    part_eq!(wrk, "year=2024/month=05/part.csv", "\
+year,month,amount
+2024,05,1
+2024,05,4
+");
+    part_eq!(wrk, "year=2023/month=05/part.csv", "\
+year,month,amount
+2023,05,3
+");
+}
+
+#[test]
+fn partition_hive_escaped() {
+    let wrk = Workdir::new("partition_hive_escaped");
+    wrk.create("in.csv", vec![
+        svec!["a/b", "value"],
+        svec!["x=y", "1"],
+        svec!["", "2"],
+    ]);
+
+    let mut cmd = wrk.command("partition");
+    cmd.args(["--hive", "--filename", "{}.csv"])
+       .arg("1").arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    part_eq!(wrk, "a%2Fb=x%3Dy.csv", "\
+a/b,value
+x=y,1
+");
+    part_eq!(wrk, "a%2Fb=__HIVE_DEFAULT_PARTITION__.csv", "\
+a/b,value
+,2
+");
+}
+
+#[test]
+fn partition_hive_invalid_utf8() {
+    // Bytes that aren't UTF-8 are escaped, so that they don't collide.
+    let wrk = Workdir::new("partition_hive_invalid_utf8");
+    fs::write(wrk.path("in.csv"), b"k,v\n\xff,1\n\xfe,2\n").unwrap();
+
+    let mut cmd = wrk.command("partition");
+    cmd.args(["--hive", "--filename", "{}.csv"])
+       .arg("k").arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    assert_eq!(fs::read(wrk.path("k=%FF.csv")).unwrap(), b"k,v\n\xff,1\n");
+    assert_eq!(fs::read(wrk.path("k=%FE.csv")).unwrap(), b"k,v\n\xfe,2\n");
+}
+
+#[test]
+fn partition_hive_no_headers() {
+    let wrk = Workdir::new("partition_hive_no_headers");
+    wrk.create("in.csv", dated_data());
+
+    let mut cmd = wrk.command("partition");
+    cmd.args(["--hive", "--no-headers"])
+       .arg("1").arg(wrk.path(".")).arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn partition_drop_key() {
+    let wrk = Workdir::new("partition_drop_key");
+    wrk.create("in.csv", dated_data());
+
+    let mut cmd = wrk.command("partition");
+    cmd.args(["--hive", "--drop-key"])
+       .arg("year,month").arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    part_eq!(wrk, "year=2024/month=05/part.csv", "\
+amount
+1
+4
+");
+    part_eq!(wrk, "year=2024/month=06/part.csv", "\
+amount
+2
+");
+}
+
+#[test]
+fn partition_drop_key_no_headers() {
+    let wrk = Workdir::new("partition_drop_key_no_headers");
+    wrk.create("in.csv", data(false));
+
+    let mut cmd = wrk.command("partition");
+    cmd.args(["--drop-key", "--no-headers"])
+       .arg("1").arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    part_eq!(wrk, "NY.csv", "\
+Manhatten
+Buffalo
+");
+}
+
+#[test]
+fn partition_max_open_files() {
+    let wrk = Workdir::new("partition_max_open_files");
+    wrk.create("in.csv", data(true));
+
+    let mut cmd = wrk.command("partition");
+    cmd.args(["--max-open-files", "1"])
+       .arg("state").arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    // Each file is closed and reopened, but its header isn't repeated.
+    part_eq!(wrk, "NY.csv", "\
+state,city
+NY,Manhatten
+NY,Buffalo
+");
+    part_eq!(wrk, "TX.csv", "\
+state,city
+TX,Dallas
+TX,Fort Worth
+");
+}
+
+#[test]
+fn partition_max_open_files_lru() {
+    let wrk = Workdir::new("partition_max_open_files_lru");
+    wrk.create("in.csv", vec![
+        svec!["key", "n"],
+        svec!["a", "1"],
+        svec!["b", "2"],
+        svec!["a", "3"],
+        svec!["c", "4"],
+        svec!["a", "5"],
+        svec!["b", "6"],
+    ]);
+
+    let mut cmd = wrk.command("partition");
+    cmd.args(["--max-open-files", "2", "--drop-key"])
+       .arg("key").arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    part_eq!(wrk, "a.csv", "n\n1\n3\n5\n");
+    part_eq!(wrk, "b.csv", "n\n2\n6\n");
+    part_eq!(wrk, "c.csv", "n\n4\n");
+}
+
+#[test]
+fn partition_max_open_files_gzip() {
+    let wrk = Workdir::new("partition_max_open_files_gzip");
+    wrk.create("in.csv", data(true));
+
+    let mut cmd = wrk.command("partition");
+    cmd.args(["--max-open-files", "1", "--filename", "{}.csv.gz"])
+       .arg("state").arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    // The reopened file has two gzip members, which are read as one.
+    let file = fs::File::open(wrk.path("NY.csv.gz")).unwrap();
+    let mut got = String::new();
+    flate2::read::MultiGzDecoder::new(file)
+        .read_to_string(&mut got).unwrap();
+    assert_eq!(got, "state,city\nNY,Manhatten\nNY,Buffalo\n");
+
+    let mut cmd = wrk.command("cat");
+    cmd.arg("rows").arg("NY.csv.gz");
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert_eq!(got, vec![
+        svec!["state", "city"],
+        svec!["NY", "Manhatten"],
+        svec!["NY", "Buffalo"],
+    ]);
+}
+
+#[test]
+fn partition_max_open_files_zero() {
+    let wrk = Workdir::new("partition_max_open_files_zero");
+    wrk.create("in.csv", data(true));
+
+    let mut cmd = wrk.command("partition");
+    cmd.args(["--max-open-files", "0"])
+       .arg("state").arg(wrk.path(".")).arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn partition_max_open_files_json() {
+    let wrk = Workdir::new("partition_max_open_files_json");
+    wrk.create("in.csv", data(true));
+
+    let mut cmd = wrk.command("partition");
+    cmd.args(["--max-open-files", "1"])
+       .arg("state").arg(wrk.path("out")).arg("in.csv")
+       .env("XSV_OUTPUT_FORMAT", "ndjson");
+    wrk.assert_err(&mut cmd);
+
+    // The error doesn't depend on whether a file has to be reopened, and
+    // nothing is written.
+    let mut cmd = wrk.command("--output-format");
+    cmd.args(["json", "partition", "--max-open-files", "100"])
+       .arg("state").arg(wrk.path("out")).arg("in.csv");
+    wrk.assert_err(&mut cmd);
+    assert!(!wrk.path("out").exists());
+}
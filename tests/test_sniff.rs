@@ -0,0 +1,112 @@
+use std::fs;
+
+use workdir::Workdir;
+
+fn setup(name: &str, data: &str) -> Workdir {
+    let wrk = Workdir::new(name);
+    fs::write(wrk.path("in.csv"), data).unwrap();
+    wrk
+}
+
+fn sniff_json(wrk: &Workdir) -> String {
+    let mut cmd = wrk.command("sniff");
+    cmd.arg("--json").arg("in.csv");
+    wrk.stdout(&mut cmd)
+}
+
+#[test]
+fn sniff_semicolon() {
+    let wrk = setup("sniff_semicolon", "a;b;c\n1;\"x;y\";3\n4;5;6\n");
+    let mut cmd = wrk.command("sniff");
+    cmd.arg("in.csv");
+
+    let got: String = wrk.stdout(&mut cmd);
+    let expected = "\
+Delimiter:       ;
+Quote:           \"
+Escape:          quotes are doubled
+Header row:      yes
+Line terminator: LF";
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn sniff_json_tab_crlf() {
+    let wrk = setup("sniff_json_tab_crlf", "name\tn\r\nabc\t1\r\ndef\t2\r\n");
+    let expected = r#"{"delimiter":"\t","quote":"\"","escape":null,"#
+                 .to_owned()
+                 + r#""has_headers":true,"terminator":"\r\n"}"#;
+    assert_eq!(sniff_json(&wrk), expected);
+}
+
+#[test]
+fn sniff_no_headers() {
+    let wrk = setup("sniff_no_headers", "1|2.5|x\n3|4.5|y\n5|6|z\n");
+    let expected = r#"{"delimiter":"|","quote":"\"","escape":null,"#
+                 .to_owned()
+                 + r#""has_headers":false,"terminator":"\n"}"#;
+    assert_eq!(sniff_json(&wrk), expected);
+}
+
+#[test]
+fn sniff_single_quote() {
+    let wrk = setup("sniff_single_quote", "name,n\n'a, b',1\nc,2\n");
+    let expected = r#"{"delimiter":",","quote":"'","escape":null,"#
+                 .to_owned()
+                 + r#""has_headers":true,"terminator":"\n"}"#;
+    assert_eq!(sniff_json(&wrk), expected);
+}
+
+#[test]
+fn sniff_backslash_escape() {
+    let wrk = setup("sniff_backslash_escape", "a,b\n\"x\\\"y\",1\nz,2\n");
+    let expected = r#"{"delimiter":",","quote":"\"","escape":"\\","#
+                 .to_owned()
+                 + r#""has_headers":true,"terminator":"\n"}"#;
+    assert_eq!(sniff_json(&wrk), expected);
+}
+
+#[test]
+fn sniff_option_select() {
+    let wrk = setup("sniff_option_select", "a;b;c\n1;\"x;y\";3\n4;5;6\n");
+    let mut cmd = wrk.command("select");
+    cmd.args(["--sniff", "b", "in.csv"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![svec!["b"], svec!["x;y"], svec!["5"]];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn sniff_option_no_headers() {
+    let wrk = setup("sniff_option_no_headers", "1|2\n3|4\n5|6\n");
+    let mut cmd = wrk.command("count");
+    cmd.args(["--sniff", "in.csv"]);
+
+    let got: usize = wrk.stdout(&mut cmd);
+    assert_eq!(got, 3);
+}
+
+#[test]
+fn sniff_option_stdin() {
+    let wrk = setup("sniff_option_stdin", "a;b\n1;2\n3;4\n");
+    let mut cmd = wrk.command("select");
+    cmd.args(["--sniff", "b"])
+       .stdin(fs::File::open(wrk.path("in.csv")).unwrap());
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![svec!["b"], svec!["2"], svec!["4"]];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn sniff_option_explicit_delimiter() {
+    // An explicit delimiter takes precedence.
+    let wrk = setup("sniff_option_explicit_delimiter", "a,b;c\n1,2;3\n");
+    let mut cmd = wrk.command("select");
+    cmd.args(["--sniff", "-d", ";", "c", "in.csv"]);
+
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = vec![svec!["c"], svec!["3"]];
+    assert_eq!(got, expected);
+}
@@ -1,4 +1,8 @@
 use std::borrow::ToOwned;
+use std::fs;
+use std::io::Read;
+
+use flate2;
 
 use workdir::Workdir;
 
@@ -266,3 +270,247 @@ fn split_custom_filename() {
     assert!(wrk.path("prefix-2.csv").exists());
     assert!(wrk.path("prefix-4.csv").exists());
 }
+
+#[test]
+fn split_filename_seq() {
+    let wrk = Workdir::new("split_filename_seq");
+    wrk.create("in.csv", data(true));
+
+    let mut cmd = wrk.command("split");
+    cmd.args(["--size", "2"])
+       .args(["--filename", "part-{seq:03}.csv"])
+       .arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    split_eq!(wrk, "part-000.csv", "\
+h1,h2
+a,b
+c,d
+");
+    assert!(wrk.path("part-001.csv").exists());
+    assert!(wrk.path("part-002.csv").exists());
+    assert!(!wrk.path("part-003.csv").exists());
+}
+
+#[test]
+fn split_filename_padded_idx() {
+    let wrk = Workdir::new("split_filename_padded_idx");
+    wrk.create_indexed("in.csv", data(true));
+
+    let mut cmd = wrk.command("split");
+    cmd.args(["--size", "4"])
+       .args(["--filename", "{:02}.csv"])
+       .arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    assert!(wrk.path("00.csv").exists());
+    split_eq!(wrk, "04.csv", "\
+h1,h2
+i,j
+k,l
+");
+}
+
+#[test]
+fn split_chunks() {
+    let wrk = Workdir::new("split_chunks");
+    wrk.create("in.csv", data(true));
+
+    let mut cmd = wrk.command("split");
+    cmd.args(["--chunks", "4"]).arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    split_eq!(wrk, "0.csv", "\
+h1,h2
+a,b
+c,d
+");
+    split_eq!(wrk, "2.csv", "\
+h1,h2
+e,f
+g,h
+");
+    split_eq!(wrk, "4.csv", "\
+h1,h2
+i,j
+");
+    split_eq!(wrk, "5.csv", "\
+h1,h2
+k,l
+");
+}
+
+#[test]
+fn split_chunks_idx() {
+    let wrk = Workdir::new("split_chunks_idx");
+    wrk.create_indexed("in.csv", data(true));
+
+    let mut cmd = wrk.command("split");
+    cmd.args(["--chunks", "4"])
+       .args(["--filename", "{seq}.csv"])
+       .arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    split_eq!(wrk, "1.csv", "\
+h1,h2
+e,f
+g,h
+");
+    split_eq!(wrk, "3.csv", "\
+h1,h2
+k,l
+");
+}
+
+#[test]
+fn split_chunks_more_than_records() {
+    let wrk = Workdir::new("split_chunks_more_than_records");
+    wrk.create("in.csv", data(true));
+
+    let mut cmd = wrk.command("split");
+    cmd.args(["--chunks", "8"])
+       .args(["--filename", "{seq}.csv"])
+       .arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    split_eq!(wrk, "5.csv", "\
+h1,h2
+k,l
+");
+    split_eq!(wrk, "7.csv", "\
+h1,h2
+");
+    assert!(!wrk.path("8.csv").exists());
+}
+
+#[test]
+fn split_chunks_stdin() {
+    let wrk = Workdir::new("split_chunks_stdin");
+    let mut cmd = wrk.command("split");
+    cmd.args(["--chunks", "2"]).arg(wrk.path("."));
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn split_bytes() {
+    let wrk = Workdir::new("split_bytes");
+    wrk.create("in.csv", data(true));
+
+    // The header and two records are 14 bytes, and a third one would make
+    // 18 bytes.
+    let mut cmd = wrk.command("split");
+    cmd.args(["--bytes", "17"]).arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    split_eq!(wrk, "0.csv", "\
+h1,h2
+a,b
+c,d
+");
+    split_eq!(wrk, "2.csv", "\
+h1,h2
+e,f
+g,h
+");
+    split_eq!(wrk, "4.csv", "\
+h1,h2
+i,j
+k,l
+");
+}
+
+#[test]
+fn split_bytes_tsv() {
+    // Records are measured as they are written, without quotes in TSV.
+    let wrk = Workdir::new("split_bytes_tsv");
+    fs::write(wrk.path("in.csv"), "h1,h2\n\"a,b\",c\n\"d,e\",f\n\"g,h\",i\n")
+        .unwrap();
+
+    let mut cmd = wrk.command("split");
+    cmd.args(["--bytes", "18", "--filename", "{}.tsv"])
+       .arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    let got = fs::read_to_string(wrk.path("0.tsv")).unwrap();
+    assert_eq!(got, "h1\th2\na,b\tc\nd,e\tf\n");
+    let got = fs::read_to_string(wrk.path("2.tsv")).unwrap();
+    assert_eq!(got, "h1\th2\ng,h\ti\n");
+}
+
+#[test]
+fn split_bytes_long_record() {
+    let wrk = Workdir::new("split_bytes_long_record");
+    wrk.create("in.csv", data(true));
+
+    let mut cmd = wrk.command("split");
+    cmd.args(["--bytes", "4"])
+       .args(["--filename", "{seq}.csv"])
+       .arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    split_eq!(wrk, "0.csv", "\
+h1,h2
+a,b
+");
+    split_eq!(wrk, "5.csv", "\
+h1,h2
+k,l
+");
+    assert!(!wrk.path("6.csv").exists());
+}
+
+#[test]
+fn split_bytes_units() {
+    let wrk = Workdir::new("split_bytes_units");
+    wrk.create("in.csv", data(true));
+
+    let mut cmd = wrk.command("split");
+    cmd.args(["--bytes", "1KiB"]).arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+    assert!(wrk.path("0.csv").exists());
+    assert!(!wrk.path("2.csv").exists());
+
+    let mut cmd = wrk.command("split");
+    cmd.args(["--bytes", "1XB"]).arg(wrk.path(".")).arg("in.csv");
+    wrk.assert_err(&mut cmd);
+
+    let mut cmd = wrk.command("split");
+    cmd.args(["--bytes", "1KB", "--chunks", "2"])
+       .arg(wrk.path(".")).arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn split_gzip() {
+    let wrk = Workdir::new("split_gzip");
+    wrk.create("in.csv", data(true));
+
+    let mut cmd = wrk.command("split");
+    cmd.args(["--chunks", "2", "--gzip"])
+       .arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    let mut got = String::new();
+    let file = fs::File::open(wrk.path("3.csv.gz")).unwrap();
+    flate2::read::GzDecoder::new(file).read_to_string(&mut got).unwrap();
+    assert_eq!(got, "h1,h2\ng,h\ni,j\nk,l\n");
+    assert!(!wrk.path("3.csv").exists());
+}
+
+#[test]
+fn split_gzip_filename_gz() {
+    // `.gz` isn't added twice.
+    let wrk = Workdir::new("split_gzip_filename_gz");
+    wrk.create("in.csv", data(true));
+
+    let mut cmd = wrk.command("split");
+    cmd.args(["--chunks", "2", "--gzip", "--filename", "{}.csv.gz"])
+       .arg(wrk.path(".")).arg("in.csv");
+    wrk.run(&mut cmd);
+
+    let mut got = String::new();
+    let file = fs::File::open(wrk.path("3.csv.gz")).unwrap();
+    flate2::read::GzDecoder::new(file).read_to_string(&mut got).unwrap();
+    assert_eq!(got, "h1,h2\ng,h\ni,j\nk,l\n");
+    assert!(!wrk.path("3.csv.gz.gz").exists());
+}
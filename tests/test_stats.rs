@@ -91,8 +91,13 @@ fn setup<S>(name: S, rows: &[&str], headers: bool,
 fn get_field_value(wrk: &Workdir, cmd: &mut process::Command, field: &str)
                   -> String {
     if field == "median" { cmd.arg("--median"); }
+    if field.starts_with("q") { cmd.arg("--quantiles").arg(&field[1..]); }
     if field == "cardinality" { cmd.arg("--cardinality"); }
     if field == "mode" { cmd.arg("--mode"); }
+    if field == "distinct_approx" { cmd.arg("--distinct-approx"); }
+    let counts = ["count", "null_count", "null_percent", "non_null_count",
+                  "leading_whitespace", "trailing_whitespace"];
+    if counts.contains(&field) { cmd.arg("--counts"); }
 
     let mut rows: Vec<Vec<String>> = wrk.read_stdout(cmd);
     let headers = rows.remove(0);
@@ -123,6 +128,18 @@ stats_tests!(stats_infer_int_float, "type", &["1", "1.2"], "Float");
 stats_tests!(stats_infer_float_int, "type", &["1.2", "1"], "Float");
 stats_tests!(stats_infer_null_int_float_unicode, "type",
              &["", "1", "1.2", "a"], "Unicode");
+stats_tests!(stats_infer_date, "type", &["2018-03-21"], "Date");
+stats_tests!(stats_infer_date_mdy, "type", &["03/21/2018"], "Date");
+stats_tests!(stats_infer_datetime, "type",
+             &["2018-03-21T14:05:00"], "DateTime");
+stats_tests!(stats_infer_datetime_rfc3339, "type",
+             &["2018-03-21T14:05:00.25+01:00"], "DateTime");
+stats_tests!(stats_infer_date_datetime, "type",
+             &["2018-03-21", "2018-03-21 14:05"], "DateTime");
+stats_tests!(stats_infer_date_null, "type", &["", "2018-03-21"], "Date");
+stats_tests!(stats_infer_date_int, "type", &["2018-03-21", "1"], "Unicode");
+stats_tests!(stats_infer_date_invalid, "type",
+             &["2018-02-30"], "Unicode");
 
 stats_tests!(stats_no_mean, "mean", &["a"], "");
 stats_tests!(stats_no_stddev, "stddev", &["a"], "");
@@ -157,6 +174,35 @@ stats_tests!(stats_max_mix, "max", &["2", "a", "1.1"], "a");
 stats_tests!(stats_min_null, "min", &["", "2", "1.1"], "1.1");
 stats_tests!(stats_max_null, "max", &["2", "1.1", ""], "2");
 
+stats_tests!(stats_min_date, "min",
+             &["2018-03-21", "03/02/2017", "2017-12-31"], "2017-03-02");
+stats_tests!(stats_max_date, "max",
+             &["2018-03-21", "03/02/2017", "2017-12-31"], "2018-03-21");
+stats_tests!(stats_min_datetime_offset, "min",
+             &["2018-03-21T00:30:00+01:00", "2018-03-21T00:00:00Z"],
+             "2018-03-20T23:30:00Z");
+stats_tests!(stats_max_datetime, "max",
+             &["2018-03-21T00:00:00", "2018-03-21T00:00:00.5"],
+             "2018-03-21T00:00:00.500");
+
+stats_tests!(stats_range_int, "range", &["5", "15", "10"], "10");
+stats_tests!(stats_range_unicode, "range", &["a", "b"], "");
+stats_tests!(stats_range_date, "range",
+             &["2018-03-21", "2018-03-01"], "20");
+stats_tests!(stats_range_datetime, "range",
+             &["2018-03-21T00:00:00Z", "2018-03-21T01:00:30Z"], "3630");
+
+stats_tests!(stats_date_format_iso, "date_format",
+             &["2018-03-21"], "ISO 8601");
+stats_tests!(stats_date_format_rfc3339, "date_format",
+             &["2018-03-21T00:00:00Z"], "RFC 3339");
+stats_tests!(stats_date_format_mdy, "date_format",
+             &["03/21/2018"], "MM/DD/YYYY");
+stats_tests!(stats_date_format_mixed, "date_format",
+             &["03/21/2018", "2018-03-22"], "mixed");
+stats_tests!(stats_date_format_unicode, "date_format",
+             &["2018-03-21", "a"], "");
+
 stats_tests!(stats_len_min, "min_length", &["aa", "a"], "1");
 stats_tests!(stats_len_max, "max_length", &["a", "aa"], "2");
 stats_tests!(stats_len_min_null, "min_length", &["", "aa", "a"], "0");
@@ -171,6 +217,18 @@ stats_tests!(stats_mean_mix, "mean", &["5", "15.1", "9.9"], "10");
 stats_tests!(stats_stddev_mix, "stddev", &["1", "2.1", "2.9"],
              "0.7788880963698614");
 
+stats_tests!(stats_count, "count", &["a", "", "b"], "3");
+stats_tests!(stats_null_count, "null_count", &["a", "", "b", ""], "2");
+stats_tests!(stats_null_percent, "null_percent", &["a", "", "b", ""], "50");
+stats_tests!(stats_non_null_count, "non_null_count",
+             &["a", "", "b", ""], "2");
+stats_tests!(stats_leading_whitespace, "leading_whitespace",
+             &[" a", "b ", "\tc", "d"], "2");
+stats_tests!(stats_trailing_whitespace, "trailing_whitespace",
+             &[" a", "b ", "c\u{a0}", "d"], "2");
+stats_tests!(stats_distinct_approx, "distinct_approx",
+             &["a", "b", "a", "c"], "3");
+
 stats_tests!(stats_cardinality, "cardinality", &["a", "b", "a"], "2");
 stats_tests!(stats_mode, "mode", &["a", "b", "a"], "a");
 stats_tests!(stats_mode_null, "mode", &["", "a", "b", "a"], "a");
@@ -180,6 +238,14 @@ stats_tests!(stats_median_even, "median", &["1", "2", "3", "4"], "2.5");
 stats_tests!(stats_median_even_null, "median",
              &["", "1", "2", "3", "4"], "2.5");
 stats_tests!(stats_median_mix, "median", &["1", "2.5", "3"], "2.5");
+stats_tests!(stats_quantile_median, "q0.5", &["3", "1", "2"], "2");
+stats_tests!(stats_quantile_interpolate, "q0.25",
+             &["1", "2", "3", "4", "5"], "2");
+stats_tests!(stats_quantile_between, "q0.1",
+             &["", "10", "20"], "11");
+stats_tests!(stats_quantile_min, "q0", &["3", "1", "2"], "1");
+stats_tests!(stats_quantile_max, "q1", &["3", "1", "2"], "3");
+stats_tests!(stats_quantile_unicode, "q0.5", &["a", "1"], "");
 
 mod stats_infer_nothing {
     // Only test CSV data with headers.
@@ -198,6 +264,11 @@ mod stats_zero_mode {
     stats_test_headers!(stats_zero_mode, "mode", &[], "N/A");
 }
 
+mod stats_zero_null_percent {
+    use super::test_stats;
+    stats_test_headers!(stats_zero_null_percent, "null_percent", &[], "");
+}
+
 mod stats_zero_mean {
     use super::test_stats;
     stats_test_headers!(stats_zero_mean, "mean", &[], "");
@@ -213,3 +284,105 @@ mod stats_header_fields {
     stats_test_headers!(stats_header_field_name, "field", &["a"], "header");
     stats_test_no_headers!(stats_header_no_field_name, "field", &["a"], "0");
 }
+
+#[test]
+fn stats_epoch_seconds() {
+    let (wrk, mut cmd) = setup("stats_epoch_seconds",
+                               &["1521590400", "1521676800"],
+                               true, false, false);
+    cmd.arg("--epoch-seconds");
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = svec![
+        "header", "DateTime", "", "2018-03-21T00:00:00Z",
+        "2018-03-22T00:00:00Z", "10", "10", "", "", "86400", "epoch seconds",
+    ];
+    assert_eq!(got[1], expected);
+}
+
+#[test]
+fn stats_mixed_utc_formats() {
+    // Both formats are in UTC, so min and max still end with `Z`.
+    let (wrk, mut cmd) = setup("stats_mixed_utc_formats",
+                               &["2018-03-21T00:00:00Z", "1521676800"],
+                               true, false, false);
+    cmd.arg("--epoch-seconds");
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert_eq!(&got[1][3..5], &svec!["2018-03-21T00:00:00Z",
+                                     "2018-03-22T00:00:00Z"][..]);
+    let col = got[0].iter().position(|h| h == "date_format").unwrap();
+    assert_eq!(got[1][col], "mixed");
+}
+
+#[test]
+fn stats_column_order() {
+    // Columns added after the original ones come last, so that the
+    // original columns keep their positions.
+    let (wrk, mut cmd) = setup("stats_column_order", &["1"],
+                               true, false, false);
+    cmd.args(&["--median", "--mode", "--cardinality", "--counts"]);
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let expected = svec![
+        "field", "type", "sum", "min", "max", "min_length", "max_length",
+        "mean", "stddev", "median", "mode", "cardinality", "range",
+        "date_format",
+    ];
+    assert_eq!(&got[0][..expected.len()], &expected[..]);
+}
+
+#[test]
+fn stats_quantiles_approximate() {
+    let rows: Vec<String> = (1..10001).map(|n| n.to_string()).collect();
+    let rows: Vec<&str> = rows.iter().map(|s| &**s).collect();
+    let (wrk, mut cmd) = setup("stats_quantiles_approximate", &rows,
+                               true, true, false);
+    cmd.args(&["--quantiles", "0.05,0.5,0.95"])
+       .args(&["--quantile-exact", "100"])
+       .args(&["--jobs", "4"]);
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let start = got[0].iter().position(|h| h == "q0.05").unwrap();
+    let qs: Vec<f64> = got[1][start..start+3].iter()
+                                             .map(|v| v.parse().unwrap())
+                                             .collect();
+    for (&q, &expected) in qs.iter().zip(&[500.0, 5000.0, 9500.0]) {
+        assert!((q - expected).abs() < 100.0,
+                "expected {} but got {}", expected, q);
+    }
+}
+
+#[test]
+fn stats_quantiles_invalid() {
+    let (wrk, mut cmd) = setup("stats_quantiles_invalid", &["1"],
+                               true, false, false);
+    cmd.args(&["--quantiles", "0.5,1.5"]);
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn stats_distinct_approx_large() {
+    let rows: Vec<String> = (0..20000).map(|n| (n % 5000).to_string())
+                                      .collect();
+    let rows: Vec<&str> = rows.iter().map(|s| &**s).collect();
+    let (wrk, mut cmd) = setup("stats_distinct_approx_large", &rows,
+                               true, true, false);
+    cmd.args(&["--jobs", "4", "--distinct-approx"]);
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    let col = got[0].iter().position(|h| h == "distinct_approx").unwrap();
+    let distinct: f64 = got[1][col].parse().unwrap();
+    assert!((distinct - 5000.0).abs() < 150.0,
+            "expected about 5000 but got {}", distinct);
+}
+
+#[test]
+fn stats_counts_not_default() {
+    let (wrk, mut cmd) = setup("stats_counts_not_default", &["a", ""],
+                               true, false, false);
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert!(!got[0].iter().any(|h| h == "null_count"));
+    assert!(!got[0].iter().any(|h| h == "distinct_approx"));
+
+    cmd.arg("--everything");
+    let got: Vec<Vec<String>> = wrk.read_stdout(&mut cmd);
+    assert!(got[0].iter().any(|h| h == "null_count"));
+    assert!(got[0].iter().any(|h| h == "distinct_approx"));
+    assert_eq!(got[0].len(), got[1].len());
+}
@@ -23,3 +23,95 @@ abcdefg  a    a
 a        abc  z\
 ")
 }
+
+fn styled_data() -> Vec<Vec<String>> {
+    vec![
+        svec!["name", "n", "note"],
+        svec!["abcdefg", "1", "a|b"],
+        svec!["x<y", "-20.5", "line\nbreak"],
+        svec!["z", "", "ok"],
+    ]
+}
+
+fn styled(name: &str, format: &str, args: &[&str]) -> String {
+    let wrk = Workdir::new(name);
+    wrk.create("in.csv", styled_data());
+
+    let mut cmd = wrk.command("table");
+    cmd.arg("--format").arg(format).args(args).arg("in.csv");
+    wrk.stdout(&mut cmd)
+}
+
+#[test]
+fn table_markdown() {
+    let got = styled("table_markdown", "markdown", &[]);
+    assert_eq!(&*got, "\
+| name    |     n | note       |
+| ------- | ----: | ---------- |
+| abcdefg |     1 | a\\|b       |
+| x<y     | -20.5 | line break |
+| z       |       | ok         |\
+")
+}
+
+#[test]
+fn table_html() {
+    let got = styled("table_html", "html", &[]);
+    assert_eq!(&*got, "\
+<table>
+<thead>
+<tr><th>name</th><th style=\"text-align: right\">n</th><th>note</th></tr>
+</thead>
+<tbody>
+<tr><td>abcdefg</td><td style=\"text-align: right\">1</td><td>a|b</td></tr>
+<tr><td>x&lt;y</td><td style=\"text-align: right\">-20.5</td>\
+<td>line break</td></tr>
+<tr><td>z</td><td style=\"text-align: right\"></td><td>ok</td></tr>
+</tbody>
+</table>\
+")
+}
+
+#[test]
+fn table_box() {
+    let got = styled("table_box", "box", &[]);
+    assert_eq!(&*got, "\
+┌─────────┬───────┬────────────┐
+│ name    │     n │ note       │
+├─────────┼───────┼────────────┤
+│ abcdefg │     1 │ a|b        │
+│ x<y     │ -20.5 │ line break │
+│ z       │       │ ok         │
+└─────────┴───────┴────────────┘\
+")
+}
+
+#[test]
+fn table_rst() {
+    let got = styled("table_rst", "rst", &[]);
+    assert_eq!(&*got, "\
++---------+-------+------------+
+| name    |     n | note       |
++=========+=======+============+
+| abcdefg |     1 | a|b        |
++---------+-------+------------+
+| x<y     | -20.5 | line break |
++---------+-------+------------+
+| z       |       | ok         |
++---------+-------+------------+\
+")
+}
+
+#[test]
+fn table_box_condense() {
+    let got = styled("table_box_condense", "box", &["--condense", "3"]);
+    assert_eq!(&*got, "\
+┌────────┬────────┬────────┐
+│ nam... │      n │ not... │
+├────────┼────────┼────────┤
+│ abc... │      1 │ a|b    │
+│ x<y    │ -20... │ lin... │
+│ z      │        │ ok     │
+└────────┴────────┴────────┘\
+")
+}
@@ -0,0 +1,114 @@
+use workdir::Workdir;
+
+fn data() -> Vec<Vec<String>> {
+    vec![
+        svec!["id", "score", "day", "at", "name"],
+        svec!["1", "1.5", "2020-01-02", "2020-01-02T03:04:05Z", "a"],
+        svec!["2", "", "2019-12-31", "2020-01-02T03:04:05+01:00", ""],
+        svec!["-3", "2.25", "1969-12-31", "2020-01-02 03:04:05", "x,y"],
+    ]
+}
+
+fn round_trip(wrk: &Workdir, format: &str, args: &[&str]) -> Vec<Vec<String>> {
+    let mut cmd = wrk.command("to");
+    cmd.arg(format).arg("out").arg("in.csv").args(args);
+    wrk.run(&mut cmd);
+
+    let mut cmd = wrk.command("from");
+    cmd.arg(format).arg("out");
+    wrk.read_stdout(&mut cmd)
+}
+
+fn expected() -> Vec<Vec<String>> {
+    vec![
+        svec!["id", "score", "day", "at", "name"],
+        svec!["1", "1.5", "2020-01-02", "2020-01-02T03:04:05Z", "a"],
+        svec!["2", "", "2019-12-31", "2020-01-02T02:04:05Z", ""],
+        svec!["-3", "2.25", "1969-12-31", "2020-01-02T03:04:05Z", "x,y"],
+    ]
+}
+
+#[test]
+fn to_parquet() {
+    let wrk = Workdir::new("to_parquet");
+    wrk.create("in.csv", data());
+    assert_eq!(round_trip(&wrk, "parquet", &[]), expected());
+}
+
+#[test]
+fn to_parquet_batches() {
+    let wrk = Workdir::new("to_parquet_batches");
+    wrk.create("in.csv", data());
+    let got = round_trip(&wrk, "parquet", &["--batch-size", "2"]);
+    assert_eq!(got, expected());
+}
+
+#[test]
+fn to_arrow() {
+    let wrk = Workdir::new("to_arrow");
+    wrk.create("in.csv", data());
+    assert_eq!(round_trip(&wrk, "arrow", &[]), expected());
+}
+
+#[test]
+fn to_arrow_batches() {
+    let wrk = Workdir::new("to_arrow_batches");
+    wrk.create("in.csv", data());
+    let got = round_trip(&wrk, "arrow", &["--batch-size", "1"]);
+    assert_eq!(got, expected());
+}
+
+#[test]
+fn to_mixed_types() {
+    let wrk = Workdir::new("to_mixed_types");
+    wrk.create("in.csv", vec![
+        svec!["a", "b", "c"],
+        svec!["1", "2018-01-01", ""],
+        svec!["1.0", "abc", ""],
+    ]);
+    let got = round_trip(&wrk, "parquet", &[]);
+    let expected = vec![
+        svec!["a", "b", "c"],
+        svec!["1.0", "2018-01-01", ""],
+        svec!["1.0", "abc", ""],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn to_no_headers() {
+    let wrk = Workdir::new("to_no_headers");
+    wrk.create("in.csv", vec![svec!["a", "1"], svec!["b", "2"]]);
+    let got = round_trip(&wrk, "parquet", &["--no-headers"]);
+    let expected = vec![
+        svec!["1", "2"],
+        svec!["a", "1"],
+        svec!["b", "2"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn to_empty() {
+    let wrk = Workdir::new("to_empty");
+    wrk.create("in.csv", vec![svec!["a", "b"]]);
+    let got = round_trip(&wrk, "arrow", &[]);
+    assert_eq!(got, vec![svec!["a", "b"]]);
+}
+
+#[test]
+fn to_stdin() {
+    let wrk = Workdir::new("to_stdin");
+    let mut cmd = wrk.command("to");
+    cmd.arg("parquet").arg("out").arg("-");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn from_invalid() {
+    let wrk = Workdir::new("from_invalid");
+    wrk.create("in.csv", data());
+    let mut cmd = wrk.command("from");
+    cmd.arg("parquet").arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
@@ -0,0 +1,62 @@
+use workdir::Workdir;
+
+fn data() -> Vec<Vec<String>> {
+    vec![
+        svec!["name", "n", "city"],
+        svec!["alice", "12", "Zürich"],
+        svec!["bob", "7", ""],
+    ]
+}
+
+fn tofixed(name: &str, args: &[&str]) -> String {
+    let wrk = Workdir::new(name);
+    wrk.create("in.csv", data());
+    wrk.create("spec.csv", vec![
+        svec!["width"], svec!["3"], svec!["4"], svec!["3"],
+    ]);
+    let mut cmd = wrk.command("tofixed");
+    cmd.args(args).arg("in.csv");
+    let o = wrk.output(&mut cmd);
+    String::from_utf8_lossy(&o.stdout).into_owned()
+}
+
+#[test]
+fn tofixed_measured() {
+    let got = tofixed("tofixed_measured", &[]);
+    assert_eq!(got, "\
+name n city  
+alice12Zürich
+bob  7       
+");
+}
+
+#[test]
+fn tofixed_widths() {
+    let got = tofixed("tofixed_widths", &["--widths", "3,4,3"]);
+    assert_eq!(got, "\
+namn   cit
+ali12  Zür
+bob7      
+");
+}
+
+#[test]
+fn tofixed_spec_right() {
+    let got = tofixed(
+        "tofixed_spec_right", &["--spec", "spec.csv", "--right"]);
+    assert_eq!(got, "\
+nam   ncit
+ali  12Zür
+bob   7   
+");
+}
+
+#[test]
+fn tofixed_no_headers() {
+    let got = tofixed("tofixed_no_headers", &["--no-headers", "-w", "5"]);
+    assert_eq!(got, "\
+name 
+alice
+bob  
+");
+}
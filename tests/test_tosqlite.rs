@@ -0,0 +1,151 @@
+use std::fs;
+
+use rusqlite::Connection;
+
+use workdir::Workdir;
+
+fn data() -> Vec<Vec<String>> {
+    vec![
+        svec!["id", "score", "name"],
+        svec!["1", "1.5", "a"],
+        svec!["2", "", "b"],
+        svec!["3", "2", ""],
+    ]
+}
+
+fn query(wrk: &Workdir, sql: &str) -> Vec<Vec<String>> {
+    let conn = Connection::open(wrk.path("out.db")).unwrap();
+    let mut stmt = conn.prepare(sql).unwrap();
+    let ncols = stmt.column_count();
+    let rows = stmt.query_map([], |row| {
+        (0..ncols).map(|i| {
+            let v: ::rusqlite::types::Value = row.get(i)?;
+            Ok(format!("{:?}", v))
+        }).collect()
+    }).unwrap();
+    rows.map(|r| r.unwrap()).collect()
+}
+
+/// Work directories outlive a test run, so drop any database left behind.
+fn setup(name: &str) -> Workdir {
+    let wrk = Workdir::new(name);
+    let _ = fs::remove_file(wrk.path("out.db"));
+    wrk.create("in.csv", data());
+    wrk
+}
+
+#[test]
+fn tosqlite() {
+    let wrk = setup("tosqlite");
+    let mut cmd = wrk.command("tosqlite");
+    cmd.arg("out.db").arg("t").arg("in.csv");
+    wrk.run(&mut cmd);
+
+    let got = query(&wrk, "SELECT * FROM t");
+    let expected = vec![
+        svec!["Integer(1)", "Real(1.5)", "Text(\"a\")"],
+        svec!["Integer(2)", "Null", "Text(\"b\")"],
+        svec!["Integer(3)", "Real(2.0)", "Null"],
+    ];
+    assert_eq!(got, expected);
+
+    let got = query(&wrk, "SELECT sql FROM sqlite_master");
+    let expected = vec![svec![
+        "Text(\"CREATE TABLE \\\"t\\\" (\\\"id\\\" INTEGER, \
+         \\\"score\\\" REAL, \\\"name\\\" TEXT)\")"
+    ]];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn tosqlite_stdin_batches() {
+    let wrk = setup("tosqlite_stdin_batches");
+    let mut cmd = wrk.command("tosqlite");
+    cmd.arg("--batch-size").arg("2").arg("out.db").arg("t")
+       .stdin(fs::File::open(wrk.path("in.csv")).unwrap());
+    wrk.run(&mut cmd);
+
+    let got = query(&wrk, "SELECT id FROM t");
+    let expected = vec![
+        svec!["Integer(1)"], svec!["Integer(2)"], svec!["Integer(3)"],
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn tosqlite_exists() {
+    let wrk = setup("tosqlite_exists");
+    let mut cmd = wrk.command("tosqlite");
+    cmd.arg("out.db").arg("t").arg("in.csv");
+    wrk.run(&mut cmd);
+
+    let mut cmd = wrk.command("tosqlite");
+    cmd.arg("out.db").arg("t").arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn tosqlite_append() {
+    let wrk = setup("tosqlite_append");
+    for _ in 0..2 {
+        let mut cmd = wrk.command("tosqlite");
+        cmd.arg("--append").arg("out.db").arg("t").arg("in.csv");
+        wrk.run(&mut cmd);
+    }
+    let got = query(&wrk, "SELECT count(*) FROM t");
+    assert_eq!(got, vec![svec!["Integer(6)"]]);
+}
+
+#[test]
+fn tosqlite_replace() {
+    let wrk = setup("tosqlite_replace");
+    wrk.create("other.csv", vec![svec!["x"], svec!["y"]]);
+    let mut cmd = wrk.command("tosqlite");
+    cmd.arg("out.db").arg("t").arg("in.csv");
+    wrk.run(&mut cmd);
+
+    let mut cmd = wrk.command("tosqlite");
+    cmd.arg("--replace").arg("out.db").arg("t").arg("other.csv");
+    wrk.run(&mut cmd);
+
+    let got = query(&wrk, "SELECT * FROM t");
+    assert_eq!(got, vec![svec!["Text(\"y\")"]]);
+}
+
+#[test]
+fn tosqlite_index() {
+    let wrk = setup("tosqlite_index");
+    let mut cmd = wrk.command("tosqlite");
+    cmd.arg("-s").arg("id,name").arg("out.db").arg("t").arg("in.csv");
+    wrk.run(&mut cmd);
+
+    let got = query(
+        &wrk, "SELECT name FROM sqlite_master WHERE type = 'index' \
+               ORDER BY name");
+    assert_eq!(got, vec![svec!["Text(\"t_id\")"], svec!["Text(\"t_name\")"]]);
+}
+
+#[test]
+fn tosqlite_append_replace() {
+    let wrk = setup("tosqlite_append_replace");
+    let mut cmd = wrk.command("tosqlite");
+    cmd.arg("--append").arg("--replace").arg("out.db").arg("t").arg("in.csv");
+    wrk.assert_err(&mut cmd);
+}
+
+#[test]
+fn tosqlite_replace_failed() {
+    let wrk = setup("tosqlite_replace_failed");
+    // A table can't have two columns with the same name.
+    wrk.create("other.csv", vec![svec!["x", "x"], svec!["y", "z"]]);
+    let mut cmd = wrk.command("tosqlite");
+    cmd.arg("out.db").arg("t").arg("in.csv");
+    wrk.run(&mut cmd);
+
+    let mut cmd = wrk.command("tosqlite");
+    cmd.arg("--replace").arg("out.db").arg("t").arg("other.csv");
+    wrk.assert_err(&mut cmd);
+
+    let got = query(&wrk, "SELECT count(*) FROM t");
+    assert_eq!(got, vec![svec!["Integer(3)"]]);
+}
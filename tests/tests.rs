@@ -5,11 +5,17 @@ extern crate log;
 #[macro_use]
 extern crate serde_derive;
 
+extern crate bzip2;
 extern crate csv;
 extern crate filetime;
+extern crate flate2;
 extern crate quickcheck;
 extern crate rand;
+extern crate rusqlite;
+extern crate serde_json;
 extern crate stats;
+extern crate xz2;
+extern crate zstd;
 
 use std::fmt;
 use std::mem::transmute;
@@ -34,22 +40,36 @@ macro_rules! rassert_eq {
 mod workdir;
 
 mod test_cat;
+mod test_chunk;
+mod test_compress;
 mod test_count;
+mod test_crosstab;
+mod test_encoding;
+mod test_errors;
 mod test_fixlengths;
 mod test_flatten;
 mod test_fmt;
+mod test_fromfixed;
+mod test_fromjson;
 mod test_frequency;
 mod test_headers;
+mod test_hist;
 mod test_index;
 mod test_join;
+mod test_json;
+mod test_malformed;
 mod test_partition;
 mod test_search;
 mod test_select;
 mod test_slice;
+mod test_sniff;
 mod test_sort;
 mod test_split;
 mod test_stats;
 mod test_table;
+mod test_to;
+mod test_tofixed;
+mod test_tosqlite;
 
 fn qcheck<T: Testable>(p: T) {
     QuickCheck::new().gen(StdGen::new(thread_rng(), 5)).quickcheck(p);